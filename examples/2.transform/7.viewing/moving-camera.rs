@@ -1,5 +1,5 @@
 use yatsr::prelude::*;
-use yatsr::sdl::frame;
+use yatsr::sdl::resizable_frame;
 use yatsr::shaders::FlatShader;
 fn main() {
   let font = get_cal_lite();
@@ -8,7 +8,10 @@ fn main() {
   let mut angle = 0.;
 
   let mut depth_buffer = vec![f32::MIN; 500 * 500];
-  frame("hello", 500, 500, move |mut img, fps| {
+  resizable_frame("hello", 500, 500, move |mut img, ctx| {
+    if ctx.resized {
+      depth_buffer = vec![f32::MIN; (ctx.width * ctx.height) as usize];
+    }
     depth_buffer.fill(f32::MIN);
     let pos = &transform::rotate_y(angle) * &Vec3::new(0., 2., 2.);
     render(
@@ -18,7 +21,7 @@ fn main() {
         Mat4::identity(),
         transform::camera(Vec3::new(0., 1., 0.), pos, Vec3::new(0., 0., 0.)),
         Mat4::identity(),
-        transform::viewport(500., 500.),
+        transform::viewport(ctx.width as f32, ctx.height as f32),
       ),
       &model,
       false,
@@ -30,7 +33,7 @@ fn main() {
       10,
       4,
       Vec3::new(1., 0., 0.),
-      format!("fps:{}", fps).as_str(),
+      format!("fps:{:.0}", ctx.fps()).as_str(),
     );
   })
 }
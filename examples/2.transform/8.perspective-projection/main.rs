@@ -17,7 +17,7 @@ fn main() {
   let mut angle = 0.;
   let font = get_cal_lite();
   let p = perspective(-1., 1., -1., 1., -3., -1.);
-  sdl::frame("Perspective", 500, 500, |mut img, fps| {
+  sdl::frame("Perspective", 500, 500, |mut img, ctx| {
     let mut depth_buffer = vec![f32::MIN; 500 * 500];
     let pos = &transform::rotate_y(angle) * &Vec3::new(0., 4., 4.);
     let persp = perspective(-1., 1., -1., 1., -3., -1.);
@@ -65,7 +65,7 @@ fn main() {
       10,
       4,
       Vec3::new(1., 0., 0.),
-      format!("fps:{}", fps).as_str(),
+      format!("fps:{:.0}", ctx.fps()).as_str(),
     );
   })
 }
@@ -42,14 +42,14 @@ impl<'a, O: yatsr::model::Model> Shader<O> for MyShader<'a> {
 
 fn main() {
   let model = Object::from_file("./models/spot/spot_triangulated.obj").unwrap();
-  let texture = util::load_image("./models/spot/spot_texture.tga");
+  let texture = util::load_image("./models/spot/spot_texture.tga").unwrap();
   // let texture = if texture.image_origin() == yatsr::image::ImageOriginPos::LeftTop {
   //   texture.flip_y()
   // } else {
   //   texture
   // };
   let mut degree = 0.;
-  sdl::frame("diffuse texture", 500, 500, |mut img, fps| {
+  sdl::frame("diffuse texture", 500, 500, |mut img, ctx| {
     let mut depth = vec![f32::MIN; (img.1 * img.2) as usize];
     let m = Transform::new().rotate_y(degree * 3.14 / 180.).build();
     let v = transform::camera(
@@ -72,11 +72,8 @@ fn main() {
       &model,
       false,
     );
-    if fps > 0. {
-      let t = 1. / fps;
-      let speed = 45.; // per seconds
-      degree += t * speed;
-    }
+    let speed = 45.; // per seconds
+    degree += ctx.delta.as_secs_f32() * speed;
   })
 }
 
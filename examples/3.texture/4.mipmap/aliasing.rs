@@ -26,7 +26,7 @@ fn main() {
       &mut img,
       &mut depth_buffer,
       &mut MyShader {
-        texture: Texture::new(util::load_image("./textures/grid1.tga")),
+        texture: Texture::new(util::load_image("./textures/grid1.tga").unwrap()),
         mat: Transform::new()
           .rotate_x(-90. * 3.14 / 180.)
           .camera(
@@ -133,10 +133,10 @@ fn main() {
         if f == "color" {
           mipmap_visual()
         } else {
-          Mipmap::new(util::load_image("./textures/grid1.tga"))
+          Mipmap::new(util::load_image("./textures/grid1.tga").unwrap())
         }
       })
-      .unwrap_or(Mipmap::new(util::load_image("./textures/grid1.tga")));
+      .unwrap_or(Mipmap::new(util::load_image("./textures/grid1.tga").unwrap()));
     let mut depth_buffer = vec![f32::MIN; 600 * 600];
     let model = shape::Plane::new();
     let mat = Transform::new()
@@ -117,7 +117,7 @@ impl<M: Model> pipeline2::Shader<M> for MyShader {
 
 fn main() {
   sdl::one_frame("SAT", 600, 600, |mut img| {
-    let ripmap = SAT::new(util::load_image("./textures/grid1.tga"));
+    let ripmap = SAT::new(util::load_image("./textures/grid1.tga").unwrap());
     let mut depth_buffer = vec![f32::MIN; 600 * 600];
     let model = shape::Plane::new();
     let mat = Transform::new()
@@ -149,7 +149,7 @@ impl<M: Model> pipeline2::Shader<M> for MyShader {
 
 fn main() {
   sdl::one_frame("Unconstrained anisotropic", 600, 600, |mut img| {
-    let ripmap = Mipmap::new(util::load_image("./textures/grid1.tga"));
+    let ripmap = Mipmap::new(util::load_image("./textures/grid1.tga").unwrap());
     let mut depth_buffer = vec![f32::MIN; 600 * 600];
     let model = shape::Plane::new();
     let mat = Transform::new()
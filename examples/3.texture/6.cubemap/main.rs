@@ -181,7 +181,7 @@ fn main() {
         Cubemap::colored()
       } else {
         Cubemap::new(
-          &util::load_image("./models/earth/texture.tga"),
+          &util::load_image("./models/earth/texture.tga").unwrap(),
           [
             //(x,y,width,height)
             //front
@@ -201,7 +201,7 @@ fn main() {
       }
     })
     .unwrap_or(Cubemap::new(
-      &util::load_image("./models/earth/texture.tga"),
+      &util::load_image("./models/earth/texture.tga").unwrap(),
       [
         //front
         (0., 1. / 3., 1. / 4., 1. / 3.),
@@ -219,7 +219,7 @@ fn main() {
     ));
   let mut model = Object::from_file("./models/earth/earth.obj").unwrap();
   model.normalize_verts();
-  sdl::frame("earth", 400, 400, move |mut img, fps| {
+  sdl::frame("earth", 400, 400, move |mut img, ctx| {
     let mut depth = vec![f32::MIN; (img.width() * img.height()) as usize];
     let mat = Transform::new()
       .rotate_y(degree * 3.14 / 180.)
@@ -240,10 +240,7 @@ fn main() {
       &model,
       0,
     );
-    if fps > 0. {
-      let t = 1. / fps;
-      let speed = 90.; // per seconds
-      degree += t * speed;
-    }
+    let speed = 90.; // per seconds
+    degree += ctx.delta.as_secs_f32() * speed;
   });
 }
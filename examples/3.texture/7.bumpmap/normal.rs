@@ -52,7 +52,7 @@ fn main() {
       &mut img,
       &mut depth,
       &mut MyShader {
-        normal_texture: Texture::neareat(util::load_image("./models/diablo/diablo3_pose_nm.tga")),
+        normal_texture: Texture::neareat(util::load_image("./models/diablo/diablo3_pose_nm.tga").unwrap()),
         frag_shader: if frag == "texture" {
           normal_from_texture
         } else {
@@ -98,7 +98,7 @@ fn main() {
     let mut shader = MyShader{
         texture: Texture::neareat(util::load_image(
           "./models/diablo/diablo3_pose_nm_tangent.tga",
-        )),
+        ).unwrap()),
         varying_uv: [Vec3::default(), Vec3::default(), Vec3::default()],
         varying_normals: [Vec3::default(), Vec3::default(), Vec3::default()],
         varying_tangent:Vec3::default(),
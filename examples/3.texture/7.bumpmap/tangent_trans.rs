@@ -106,7 +106,7 @@ fn main() {
     let mut shader = MyShader{
         texture: Texture::neareat(util::load_image(
           "./models/diablo/diablo3_pose_nm_tangent.tga",
-        )),
+        ).unwrap()),
         rotate: 45.,
         varying_uv: [Vec3::default(), Vec3::default(), Vec3::default()],
         varying_normals: [Vec3::default(), Vec3::default(), Vec3::default()],
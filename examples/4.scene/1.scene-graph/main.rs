@@ -0,0 +1,63 @@
+use yatsr::prelude::*;
+use yatsr::raytracer::Light;
+use yatsr::scene_graph::{LambertShader, Node, SceneGraph, Trs};
+
+fn main() {
+  let mut sphere = Object::from_file("./models/earth/earth.obj").unwrap();
+  sphere.normalize_verts();
+
+  let mut scene = SceneGraph::new();
+  // 太阳不受光照影响，同时是场景中的点光源
+  let sun = scene.add(
+    Node::new("sun")
+      .model(
+        &sphere,
+        LambertShader::new(Vec3::new(1., 0.8, 0.2)).ambient(Vec3::new(1., 1., 1.)),
+      )
+      .light(Light::Point {
+        pos: Vec3::new(0., 0., 0.),
+        color: Vec3::new(12., 12., 12.),
+      }),
+  );
+  // 地球的公转轨道，旋转这个节点使地球绕太阳转动
+  let earth_orbit = scene.add(Node::new("earth orbit"));
+  let earth = scene.add_child(
+    earth_orbit,
+    Node::new("earth").model(&sphere, LambertShader::new(Vec3::new(0.2, 0.4, 1.))),
+  );
+  let moon_orbit = scene.add_child(earth, Node::new("moon orbit"));
+  scene.add_child(
+    moon_orbit,
+    Node::new("moon")
+      .transform(Trs::new().translate(2.5, 0., 0.).scale(0.3, 0.3, 0.3))
+      .model(&sphere, LambertShader::new(Vec3::new(0.7, 0.7, 0.7))),
+  );
+  let camera = scene.add(
+    Node::new("camera")
+      .transform(Trs::new().translate(0., 3., 7.).rotate(-0.4, 0., 0.))
+      .camera(transform::perspective(60., 1., -0.1, -100.)),
+  );
+
+  let mut t = 0.;
+  let mut depth = vec![f32::MIN; 500 * 500];
+  sdl::resizable_frame("scene graph", 500, 500, move |mut img, ctx| {
+    if ctx.resized {
+      depth = vec![f32::MIN; (ctx.width * ctx.height) as usize];
+    }
+    depth.fill(f32::MIN);
+    scene.node_mut(sun).transform = Trs::new().rotate(0., t * 0.2, 0.);
+    scene.node_mut(earth_orbit).transform = Trs::new().rotate(0., t * 0.5, 0.);
+    scene.node_mut(earth).transform = Trs::new()
+      .translate(4., 0., 0.)
+      .rotate(0., t * 2., 0.)
+      .scale(0.5, 0.5, 0.5);
+    scene.node_mut(moon_orbit).transform = Trs::new().rotate(0., t, 0.);
+    scene.render(
+      &mut img,
+      &mut depth,
+      camera,
+      &pipeline2::RenderOptions::new(),
+    );
+    t += ctx.delta.as_secs_f32();
+  })
+}
@@ -95,9 +95,9 @@ fn main() {
   let light_pos = Vec3::new(1., 1., 1.);
   let mut light_degree = 0.;
   let mut time = 0.;
-  let texture = Texture::new(util::load_image("./a.tga"));
+  let texture = Texture::new(util::load_image("./a.tga").unwrap());
 
-  sdl::frame("A", 800, 800, |mut img, fps| {
+  sdl::frame("A", 800, 800, |mut img, ctx| {
     let mut depth = vec![f32::MIN; img.width() as usize * img.height() as usize];
     let model = shape::Plane::new();
     let light_pos = &transform::rotate_y(light_degree) * &light_pos;
@@ -114,11 +114,9 @@ fn main() {
       &model,
       0,
     );
-    if fps > 0. {
-      let t = 1. / fps;
-      let speed = 2.; // per seconds
-      time += t;
-      //light_degree += t * speed;
-    }
+    let t = ctx.delta.as_secs_f32();
+    let speed = 2.; // per seconds
+    time += t;
+    //light_degree += t * speed;
   })
 }
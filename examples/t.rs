@@ -69,7 +69,7 @@ fn main() {
     .get(1)
     .map(|f| f.clone())
     .unwrap_or(String::from("./models/earth/earth.obj"));
-  let texture = TGA.decode(std::fs::read("./models/earth/texture.tga").unwrap());
+  let texture = TGA::new().decode(std::fs::read("./models/earth/texture.tga").unwrap()).unwrap();
   save_image("t.ppm",&texture,PPM);
 
   let mut model = Model::from_file(model_path).expect("Failed to load model:,");
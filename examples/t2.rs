@@ -8,7 +8,7 @@ fn main() {
   let mut angle = 0.;
 
   let mut depth_buffer = vec![f32::MIN;1000 * 1000];
-  frame("hello", 1000, 1000, move |mut img, fps| {
+  frame("hello", 1000, 1000, move |mut img, ctx| {
     depth_buffer.fill(f32::MIN);
     let pos = &transform::rotate_y(angle) * &Vec3::new(0., 0., 2.);
     render(
@@ -30,7 +30,7 @@ fn main() {
       10,
       4,
       Vec3::new(1., 0., 0.),
-      format!("fps:{}", fps).as_str(),
+      format!("fps:{:.0}", ctx.fps()).as_str(),
     );
   })
 }
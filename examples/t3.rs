@@ -33,9 +33,9 @@ impl<'a> Shader for S<'a> {
 fn main() {
   let mut model = Model::from_file("./models/earth/earth.obj").unwrap();
   model.normalize_verts();
-  let loader = yatsr::image_decoder::TGA;
+  let loader = yatsr::image_decoder::TGA::new();
   let texture =
-    loader.decode(std::fs::read("/home/yj/projects/sync/tinyrenderer/grid.tga").unwrap());
+    loader.decode(std::fs::read("/home/yj/projects/sync/tinyrenderer/grid.tga").unwrap()).unwrap();
   frame("Demo", 500, 500, |mut img, _| {
     let mut depth = vec![f32::MIN; (img.1 * img.2) as usize];
     render(
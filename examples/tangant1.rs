@@ -110,7 +110,7 @@ fn main() {
           img:RefCell::new(out),
         texture: Texture::new(util::load_image(
           "/home/yj/Downloads/african_head_nm_tangent.tga",
-        )),
+        ).unwrap()),
         varying_uv: [Vec3::default(), Vec3::default(), Vec3::default()],
         varying_normals: [Vec3::default(), Vec3::default(), Vec3::default()],
         varying_tangant:Vec3::default(),
@@ -81,7 +81,7 @@ fn main() {
       &mut MyShader {
         texture: Texture::new(util::load_image(
           "/home/yj/Downloads/african_head_nm_tangent.tga",
-        )),
+        ).unwrap()),
         varying_uv: [Vec3::default(), Vec3::default(), Vec3::default()],
         varying_normals: [Vec3::default(), Vec3::default(), Vec3::default()],
         varying_tangant:Vec3::default(),
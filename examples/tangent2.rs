@@ -105,7 +105,7 @@ fn main() {
           img:RefCell::new(out),
         texture: Texture::new(util::load_image(
           "./output_tangant.tga",
-        )),
+        ).unwrap()),
 
         // texture: Texture::new(util::load_image(
         //   "/home/yj/Downloads/african_head_nm_tangent.tga",
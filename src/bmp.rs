@@ -0,0 +1,441 @@
+// BMP 编解码。
+// 解码：支持 BITMAPCOREHEADER、BITMAPINFOHEADER 和 V2~V5 的头，1/4/8 位调色板(8 位可以是 RLE8 压缩)，
+// 16/24/32 位(包括 BI_BITFIELDS 的掩码)，以及从下到上和从上到下两种行顺序。
+// 编码：24 位，带透明度时使用 BITMAPV4HEADER 的 32 位。
+use crate::{
+  error::{Error, Result},
+  image::{Image, ImageOriginPos, PixImage},
+  image_decoder::Decoder,
+  image_encoder::Encoder,
+};
+
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+const FILE_HEADER_SIZE: usize = 14;
+const CORE_HEADER_SIZE: u32 = 12;
+const INFO_HEADER_SIZE: u32 = 40;
+const V4_HEADER_SIZE: u32 = 108;
+// 72 DPI
+const PIXELS_PER_METER: i32 = 2835;
+// 32 位 BGRA 的 R、G、B、A 掩码
+const MASKS_BGRA: [u32; 4] = [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000];
+// 8192x8192
+const DEFAULT_MAX_PIXELS: usize = 1 << 26;
+
+pub struct BMP {
+  // 为 true 时保存为带透明度的 32 位
+  pub alpha: bool,
+  // 解码 RLE8 时允许的最大像素数。RLE8 的一个结束标记就可以表示整个图片，
+  // 图片大小不受数据长度限制，超过时返回错误
+  pub max_pixels: usize,
+}
+impl BMP {
+  pub fn new() -> BMP {
+    BMP {
+      alpha: false,
+      max_pixels: DEFAULT_MAX_PIXELS,
+    }
+  }
+  pub fn alpha(mut self, alpha: bool) -> BMP {
+    self.alpha = alpha;
+    self
+  }
+  pub fn max_pixels(mut self, max_pixels: usize) -> BMP {
+    self.max_pixels = max_pixels;
+    self
+  }
+}
+impl Default for BMP {
+  fn default() -> BMP {
+    BMP::new()
+  }
+}
+
+impl Encoder for BMP {
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    let (w, h) = (img.width(), img.height());
+    let bytes_per_pixel = if self.alpha { 4 } else { 3 };
+    // 每行补齐到 4 字节
+    let stride = (w as usize * bytes_per_pixel).next_multiple_of(4);
+    let header_size = if self.alpha {
+      V4_HEADER_SIZE
+    } else {
+      INFO_HEADER_SIZE
+    };
+    let offset = FILE_HEADER_SIZE as u32 + header_size;
+    let image_size = (stride * h as usize) as u32;
+    let mut out = Vec::with_capacity((offset + image_size) as usize);
+    out.extend(b"BM");
+    out.extend((offset + image_size).to_le_bytes());
+    out.extend([0; 4]);
+    out.extend(offset.to_le_bytes());
+    out.extend(header_size.to_le_bytes());
+    out.extend((w as i32).to_le_bytes());
+    // 高度为正表示从下到上
+    out.extend((h as i32).to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend((bytes_per_pixel as u16 * 8).to_le_bytes());
+    let compression = if self.alpha { BI_BITFIELDS } else { BI_RGB };
+    out.extend(compression.to_le_bytes());
+    out.extend(image_size.to_le_bytes());
+    out.extend(PIXELS_PER_METER.to_le_bytes());
+    out.extend(PIXELS_PER_METER.to_le_bytes());
+    // 没有调色板
+    out.extend([0; 8]);
+    if self.alpha {
+      for mask in MASKS_BGRA {
+        out.extend(mask.to_le_bytes());
+      }
+      // 色彩空间为 LCS_sRGB，不使用端点和 gamma
+      out.extend(b"BGRs");
+      out.extend([0; 48]);
+    }
+    for y in 0..h {
+      for x in 0..w {
+        let c = img.get_rgba(x, y);
+        out.extend([c.z, c.y, c.x]);
+        if self.alpha {
+          out.push(c.w);
+        }
+      }
+      out.resize(out.len() + stride - w as usize * bytes_per_pixel, 0);
+    }
+    out
+  }
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16> {
+  data
+    .get(pos..pos + 2)
+    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    .ok_or_else(|| Error::format("truncated header"))
+}
+fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+  data
+    .get(pos..pos + 4)
+    .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    .ok_or_else(|| Error::format("truncated header"))
+}
+
+// 按掩码取出一个通道并缩放到 8 位，掩码为 0 的透明度通道为不透明
+fn channel(v: u32, mask: u32, default: u8) -> u8 {
+  if mask == 0 {
+    return default;
+  }
+  let max = (1u64 << mask.count_ones()) - 1;
+  let c = ((v & mask) >> mask.trailing_zeros()) as u64;
+  ((c * 255 + max / 2) / max) as u8
+}
+
+// RLE8：(个数, 索引) 重复一个索引，个数为 0 时第二个字节是转义码。
+// 跳过的像素保持为透明的黑色，数据提前结束时视为位图结束
+fn decode_rle8(data: &[u8], w: usize, h: usize, palette: &[[u8; 4]], out: &mut [u8]) -> Result<()> {
+  let mut put = |x: usize, y: usize, index: u8| -> Result<()> {
+    let c = palette
+      .get(index as usize)
+      .ok_or_else(|| Error::format("palette index out of range"))?;
+    if x < w && y < h {
+      out[(y * w + x) * 4..][..4].copy_from_slice(c);
+    }
+    Ok(())
+  };
+  let (mut x, mut y, mut pos) = (0, 0, 0);
+  while let (Some(&count), Some(&value)) = (data.get(pos), data.get(pos + 1)) {
+    pos += 2;
+    if count > 0 {
+      for _ in 0..count {
+        put(x, y, value)?;
+        x += 1;
+      }
+      continue;
+    }
+    match value {
+      // 行结束
+      0 => {
+        x = 0;
+        y += 1;
+      }
+      // 位图结束
+      1 => break,
+      // 移动位置
+      2 => {
+        let delta = data
+          .get(pos..pos + 2)
+          .ok_or_else(|| Error::format("truncated RLE data"))?;
+        x += delta[0] as usize;
+        y += delta[1] as usize;
+        pos += 2;
+      }
+      // n 个不压缩的索引，补齐到 2 字节
+      n => {
+        let n = n as usize;
+        let run = data
+          .get(pos..pos + n)
+          .ok_or_else(|| Error::format("truncated RLE data"))?;
+        for &index in run {
+          put(x, y, index)?;
+          x += 1;
+        }
+        pos += n + n % 2;
+      }
+    }
+  }
+  Ok(())
+}
+
+fn decode_bmp(data: &[u8], max_pixels: usize) -> Result<PixImage> {
+  if data.get(..2) != Some(b"BM") {
+    return Err(Error::format("missing BMP signature"));
+  }
+  let offset = read_u32(data, 10)? as usize;
+  let header_size = read_u32(data, FILE_HEADER_SIZE)?;
+  let dib = FILE_HEADER_SIZE;
+  let (width, height, bits, compression) = match header_size {
+    CORE_HEADER_SIZE => (
+      read_u16(data, dib + 4)? as i32,
+      read_u16(data, dib + 6)? as i32,
+      read_u16(data, dib + 10)?,
+      BI_RGB,
+    ),
+    40.. => (
+      read_u32(data, dib + 4)? as i32,
+      read_u32(data, dib + 8)? as i32,
+      read_u16(data, dib + 14)?,
+      read_u32(data, dib + 16)?,
+    ),
+    _ => {
+      return Err(Error::format(format!(
+        "unknown header size {}",
+        header_size
+      )))
+    }
+  };
+  if width <= 0 || height == 0 {
+    return Err(Error::format(format!(
+      "invalid image size {}x{}",
+      width, height
+    )));
+  }
+  // 高度为负表示从上到下
+  let top_down = height < 0;
+  let (w, h) = (width as usize, height.unsigned_abs() as usize);
+  match (bits, compression) {
+    (1 | 4 | 8 | 16 | 24 | 32, BI_RGB) | (8, BI_RLE8) => {}
+    (16 | 32, BI_BITFIELDS | BI_ALPHABITFIELDS) => {}
+    _ => {
+      return Err(Error::unsupported(format!(
+        "bit count {} with compression {}",
+        bits, compression
+      )))
+    }
+  }
+  if compression == BI_RLE8 && top_down {
+    return Err(Error::format("RLE8 image can't be top-down"));
+  }
+
+  let mut masks = match bits {
+    16 => [0x7c00, 0x03e0, 0x001f, 0],
+    _ => [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0],
+  };
+  if compression == BI_BITFIELDS || compression == BI_ALPHABITFIELDS {
+    // INFO 头之后紧跟着掩码，V2 以上的头本身包含掩码，V3 以上包含透明度的掩码
+    let count = if compression == BI_ALPHABITFIELDS || header_size >= 56 {
+      4
+    } else {
+      3
+    };
+    for (i, mask) in masks[..count].iter_mut().enumerate() {
+      *mask = read_u32(data, dib + 40 + 4 * i)?;
+    }
+  }
+  let mut palette = vec![];
+  if bits <= 8 {
+    let (entry_size, used) = if header_size == CORE_HEADER_SIZE {
+      (3, 0)
+    } else {
+      (4, read_u32(data, dib + 32)? as usize)
+    };
+    let start = dib + header_size as usize;
+    // 有些文件的调色板比声明的短，以像素数据的位置为准
+    let count = if used == 0 { 1 << bits } else { used.min(256) }
+      .min(offset.saturating_sub(start) / entry_size);
+    let entries = data
+      .get(start..start + count * entry_size)
+      .ok_or_else(|| Error::format("truncated palette"))?;
+    palette = entries
+      .chunks_exact(entry_size)
+      .map(|c| [c[2], c[1], c[0], 255])
+      .collect();
+  }
+
+  let pixels = data
+    .get(offset..)
+    .ok_or_else(|| Error::format("invalid pixel data offset"))?;
+  // 宽高来自文件，先确认像素数据足够再分配。RLE8 的大小不受数据长度限制，改为检查 max_pixels
+  let too_large = || Error::format("image is too large");
+  let bits = bits as usize;
+  let row_len = w.checked_mul(bits).ok_or_else(too_large)?.div_ceil(8);
+  let stride = row_len.div_ceil(4) * 4;
+  if compression == BI_RLE8 {
+    if w.checked_mul(h).is_none_or(|n| n > max_pixels) {
+      return Err(Error::unsupported(format!(
+        "RLE8 image {}x{} exceeds {} pixels",
+        w, h, max_pixels
+      )));
+    }
+  } else {
+    let size = stride
+      .checked_mul(h - 1)
+      .and_then(|n| n.checked_add(row_len))
+      .ok_or_else(too_large)?;
+    if pixels.len() < size {
+      return Err(Error::format("image data is too short"));
+    }
+  }
+  let size = w
+    .checked_mul(h)
+    .and_then(|n| n.checked_mul(4))
+    .ok_or_else(too_large)?;
+  let mut out = vec![0u8; size];
+  if compression == BI_RLE8 {
+    decode_rle8(pixels, w, h, &palette, &mut out)?;
+  } else {
+    for y in 0..h {
+      let row = pixels
+        .get(y * stride..y * stride + row_len)
+        .ok_or_else(|| Error::format("image data is too short"))?;
+      for x in 0..w {
+        let rgba = match bits {
+          24 => [row[3 * x + 2], row[3 * x + 1], row[3 * x], 255],
+          16 | 32 => {
+            let bytes = &row[x * bits / 8..(x + 1) * bits / 8];
+            let v = bytes.iter().rev().fold(0, |v, &b| v << 8 | b as u32);
+            [
+              channel(v, masks[0], 0),
+              channel(v, masks[1], 0),
+              channel(v, masks[2], 0),
+              channel(v, masks[3], 255),
+            ]
+          }
+          // 调色板索引从字节的高位开始排列
+          _ => {
+            let bit = x * bits;
+            let index = row[bit / 8] >> (8 - bits - bit % 8) & ((1 << bits) - 1) as u8;
+            *palette
+              .get(index as usize)
+              .ok_or_else(|| Error::format("palette index out of range"))?
+          }
+        };
+        out[(y * w + x) * 4..][..4].copy_from_slice(&rgba);
+      }
+    }
+  }
+  // 从下到上存储时第一行是图片的最下面一行
+  let origin = if top_down {
+    ImageOriginPos::LeftBottom
+  } else {
+    ImageOriginPos::LeftTop
+  };
+  Ok(PixImage::from_data(out, w as u32, h as u32, origin))
+}
+
+impl Decoder for BMP {
+  fn decode(&self, data: Vec<u8>) -> Result<PixImage> {
+    decode_bmp(&data, self.max_pixels)
+  }
+}
+
+#[test]
+fn test_bmp() {
+  use crate::geometry::Vec4;
+  let rgba = |c: Vec4<u8>| [c.x, c.y, c.z, c.w];
+  let mut img = PixImage::new(3, 2);
+  for y in 0..2 {
+    for x in 0..3 {
+      img.set_rgba32(
+        x,
+        y,
+        Vec4::new(x as u8 * 100, y as u8 * 200, 7, 50 + x as u8),
+      );
+    }
+  }
+  for alpha in [false, true] {
+    let data = BMP::new().alpha(alpha).encode(&img);
+    // 24 位的行补齐到 12 字节
+    let size = if alpha { 14 + 108 + 24 } else { 14 + 40 + 24 };
+    assert_eq!(data.len(), size);
+    assert_eq!(read_u32(&data, 2).unwrap(), size as u32);
+    let decoded = BMP::new().decode(data).unwrap();
+    for y in 0..2 {
+      for x in 0..3 {
+        let [r, g, b, a] = rgba(img.get_rgba(x, y));
+        let a = if alpha { a } else { 255 };
+        assert_eq!(rgba(decoded.get_rgba(x, y)), [r, g, b, a]);
+      }
+    }
+  }
+
+  // 手工构造的 INFO 头，extra 为调色板或掩码
+  let build = |w: i32, h: i32, bits: u16, compression: u32, extra: &[u8], pixels: &[u8]| {
+    let offset = 14 + 40 + extra.len() as u32;
+    let mut data = b"BM".to_vec();
+    data.extend((offset + pixels.len() as u32).to_le_bytes());
+    data.extend([0; 4]);
+    data.extend(offset.to_le_bytes());
+    data.extend(40u32.to_le_bytes());
+    data.extend(w.to_le_bytes());
+    data.extend(h.to_le_bytes());
+    data.extend(1u16.to_le_bytes());
+    data.extend(bits.to_le_bytes());
+    data.extend(compression.to_le_bytes());
+    data.extend([0; 20]);
+    data.extend(extra);
+    data.extend(pixels);
+    data
+  };
+  let bmp = |w, h, bits, compression, extra: &[u8], pixels: &[u8]| {
+    BMP::new()
+      .decode(build(w, h, bits, compression, extra, pixels))
+      .unwrap()
+  };
+  let rows = |img: &PixImage| -> Vec<Vec<[u8; 4]>> {
+    (0..img.height)
+      .rev()
+      .map(|y| (0..img.width).map(|x| rgba(img.get_rgba(x, y))).collect())
+      .collect()
+  };
+
+  // 从上到下的 24 位，第一行是最上面一行
+  let img = bmp(1, -2, 24, BI_RGB, &[], &[1, 2, 3, 0, 4, 5, 6, 0]);
+  assert_eq!(rows(&img), [[[3, 2, 1, 255]], [[6, 5, 4, 255]]]);
+
+  // RGB565 的 16 位
+  let masks: Vec<u8> = [0xf800u32, 0x07e0, 0x001f]
+    .iter()
+    .flat_map(|m| m.to_le_bytes())
+    .collect();
+  let img = bmp(2, 1, 16, BI_BITFIELDS, &masks, &[0x00, 0xf8, 0x1f, 0x00]);
+  assert_eq!(rows(&img), [[[255, 0, 0, 255], [0, 0, 255, 255]]]);
+
+  // RLE8：最下面一行为重复 3 次的 1 和不压缩的 2 1 2，换行并移动到 x=2 后重复 2 次的 2
+  let palette = [0, 0, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0];
+  let rle = [3, 1, 0, 3, 2, 1, 2, 0, 0, 0, 0, 2, 2, 0, 2, 2, 0, 1];
+  let img = bmp(6, 2, 8, BI_RLE8, &palette, &rle);
+  let (k, r, b, t) = ([0, 0, 0, 255], [255, 0, 0, 255], [0, 0, 255, 255], [0; 4]);
+  assert_eq!(rows(&img), [[t, t, b, b, t, t], [r, r, r, b, r, b]]);
+
+  // 1 位调色板，补齐到 4 字节
+  let img = bmp(3, 1, 1, BI_RGB, &palette[..8], &[0b1010_0000, 0, 0, 0]);
+  assert_eq!(rows(&img), [[r, k, r]]);
+
+  // 头中的大小与像素数据不符时返回错误，不按头分配内存
+  let huge = build(i32::MAX, i32::MIN + 1, 32, BI_RGB, &[], &[0; 16]);
+  assert!(BMP::new().decode(huge).is_err());
+  let huge = build(60000, 60000, 8, BI_RLE8, &palette, &[0, 1]);
+  assert!(BMP::new().decode(huge).is_err());
+  let rle = build(6, 2, 8, BI_RLE8, &palette, &rle);
+  assert!(BMP::new().max_pixels(11).decode(rle.clone()).is_err());
+  assert!(BMP::new().max_pixels(12).decode(rle).is_ok());
+}
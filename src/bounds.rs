@@ -0,0 +1,230 @@
+use crate::{geometry::Vec3, mat::Mat4, model::Model};
+
+// 轴对齐包围盒
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+  pub min: Vec3<f32>,
+  pub max: Vec3<f32>,
+}
+impl Aabb {
+  pub fn new(min: Vec3<f32>, max: Vec3<f32>) -> Aabb {
+    Aabb { min, max }
+  }
+  // 不包含任何点的包围盒，extend 之后才有意义
+  pub fn empty() -> Aabb {
+    Aabb {
+      min: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+      max: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    }
+  }
+  pub fn is_empty(&self) -> bool {
+    self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+  }
+  pub fn extend(&mut self, p: Vec3<f32>) {
+    self.min = Vec3::new(
+      self.min.x.min(p.x),
+      self.min.y.min(p.y),
+      self.min.z.min(p.z),
+    );
+    self.max = Vec3::new(
+      self.max.x.max(p.x),
+      self.max.y.max(p.y),
+      self.max.z.max(p.z),
+    );
+  }
+  pub fn union(&self, other: &Aabb) -> Aabb {
+    let mut r = *self;
+    r.extend(other.min);
+    r.extend(other.max);
+    r
+  }
+  pub fn center(&self) -> Vec3<f32> {
+    (self.min + self.max) * 0.5
+  }
+  pub fn size(&self) -> Vec3<f32> {
+    self.max - self.min
+  }
+  pub fn surface_area(&self) -> f32 {
+    if self.is_empty() {
+      return 0.;
+    }
+    let s = self.size();
+    2. * (s.x * s.y + s.y * s.z + s.z * s.x)
+  }
+}
+
+impl Aabb {
+  // 模型所有顶点的包围盒
+  pub fn from_model<M: Model>(model: &M) -> Aabb {
+    let mut aabb = Aabb::empty();
+    for face in 0..model.face_count() {
+      for i in 0..3 {
+        aabb.extend(model.vert(face, i));
+      }
+    }
+    aabb
+  }
+  pub fn from_points<'a, I: IntoIterator<Item = &'a Vec3<f32>>>(points: I) -> Aabb {
+    let mut aabb = Aabb::empty();
+    points.into_iter().for_each(|p| aabb.extend(*p));
+    aabb
+  }
+  pub fn corners(&self) -> [Vec3<f32>; 8] {
+    let (a, b) = (self.min, self.max);
+    [
+      Vec3::new(a.x, a.y, a.z),
+      Vec3::new(b.x, a.y, a.z),
+      Vec3::new(a.x, b.y, a.z),
+      Vec3::new(b.x, b.y, a.z),
+      Vec3::new(a.x, a.y, b.z),
+      Vec3::new(b.x, a.y, b.z),
+      Vec3::new(a.x, b.y, b.z),
+      Vec3::new(b.x, b.y, b.z),
+    ]
+  }
+  // 变换后的包围盒，包含变换后的 8 个角点
+  pub fn transform(&self, m: &Mat4) -> Aabb {
+    if self.is_empty() {
+      return *self;
+    }
+    Aabb::from_points(self.corners().map(|p| m * &p).iter())
+  }
+}
+
+// 包围球
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingSphere {
+  pub center: Vec3<f32>,
+  pub radius: f32,
+}
+impl BoundingSphere {
+  pub fn new(center: Vec3<f32>, radius: f32) -> BoundingSphere {
+    BoundingSphere { center, radius }
+  }
+  // 以包围盒的中心为球心，不是最小包围球，但计算简单
+  pub fn from_model<M: Model>(model: &M) -> BoundingSphere {
+    let center = Aabb::from_model(model).center();
+    let mut radius: f32 = 0.;
+    for face in 0..model.face_count() {
+      for i in 0..3 {
+        radius = radius.max((model.vert(face, i) - center).norm());
+      }
+    }
+    BoundingSphere { center, radius }
+  }
+  // 按最大的缩放系数放大半径，适用于仿射变换
+  pub fn transform(&self, m: &Mat4) -> BoundingSphere {
+    let scale = (0..3)
+      .map(|i| m.col(i).to_3d_vector().norm())
+      .fold(0., f32::max);
+    BoundingSphere {
+      center: m * &self.center,
+      radius: self.radius * scale,
+    }
+  }
+}
+
+// 平面 normal·p + d = 0，normal 为单位向量，指向视锥体内侧
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+  pub normal: Vec3<f32>,
+  pub d: f32,
+}
+impl Plane {
+  // 点到平面的有向距离
+  pub fn distance(&self, p: Vec3<f32>) -> f32 {
+    self.normal * p + self.d
+  }
+}
+
+// 视锥体，由 6 个平面围成
+pub struct Frustum {
+  planes: Vec<Plane>,
+}
+impl Frustum {
+  // 从投影和视图矩阵的乘积中提取裁剪平面(Gribb-Hartmann)。
+  // 乘上模型矩阵后得到的是模型空间中的视锥体。
+  // 按标准立方体 [-1,1] 提取，对于反向 Z 的投影也是保守的
+  pub fn new(view_projection: &Mat4) -> Frustum {
+    let m = view_projection;
+    let w = m.row(3);
+    let inside = &m.invert() * &Vec3::new(0., 0., 0.5);
+    let planes = (0..3)
+      .flat_map(|i| [w + m.row(i), w - m.row(i)])
+      .filter_map(|p| {
+        let normal = Vec3::new(p.x, p.y, p.z);
+        let len = normal.norm();
+        // 无穷远的远平面
+        if len < 1e-12 {
+          return None;
+        }
+        let plane = Plane {
+          normal: normal * (1. / len),
+          d: p.w / len,
+        };
+        // 这里的投影矩阵 w 可以是负数，此时平面的方向相反
+        if plane.distance(inside) < 0. {
+          Some(Plane {
+            normal: plane.normal * -1.,
+            d: -plane.d,
+          })
+        } else {
+          Some(plane)
+        }
+      })
+      .collect();
+    Frustum { planes }
+  }
+  pub fn planes(&self) -> &[Plane] {
+    &self.planes
+  }
+  pub fn contains_point(&self, p: Vec3<f32>) -> bool {
+    self.planes.iter().all(|plane| plane.distance(p) >= 0.)
+  }
+  // 保守的测试：返回 false 时一定在视锥体之外
+  pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+    self
+      .planes
+      .iter()
+      .all(|plane| plane.distance(sphere.center) >= -sphere.radius)
+  }
+  // 保守的测试：返回 false 时一定在视锥体之外
+  pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+    if aabb.is_empty() {
+      return false;
+    }
+    self.planes.iter().all(|plane| {
+      // 沿法向量方向最远的角点
+      let n = plane.normal;
+      let p = Vec3::new(
+        if n.x >= 0. { aabb.max.x } else { aabb.min.x },
+        if n.y >= 0. { aabb.max.y } else { aabb.min.y },
+        if n.z >= 0. { aabb.max.z } else { aabb.min.z },
+      );
+      plane.distance(p) >= 0.
+    })
+  }
+}
+
+#[test]
+fn test_frustum_culling() {
+  let view = crate::transform::camera(
+    Vec3::new(0., 1., 0.),
+    Vec3::new(0., 0., 3.),
+    Vec3::new(0., 0., 0.),
+  );
+  let projection = crate::transform::perspective(45., 1., -1., -10.);
+  let frustum = Frustum::new(&(&projection * &view));
+  let unit = Aabb::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5));
+  assert!(frustum.intersects_aabb(&unit));
+  // 相机后面、远平面之外和视野侧面
+  let behind = crate::transform::translate(0., 0., 5.);
+  assert!(!frustum.intersects_aabb(&unit.transform(&behind)));
+  let far = crate::transform::translate(0., 0., -20.);
+  assert!(!frustum.intersects_aabb(&unit.transform(&far)));
+  let side = BoundingSphere::new(Vec3::new(5., 0., 0.), 1.);
+  assert!(!frustum.intersects_sphere(&side));
+  assert!(frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(5., 0., 0.), 4.)));
+  let scaled = side.transform(&crate::transform::scale(2., 2., 2.));
+  assert_eq!(scaled.radius, 2.);
+}
@@ -0,0 +1,278 @@
+// 三角形网格的层次包围盒(BVH)，按表面积启发式(SAH)划分，用于光线追踪、拾取和 AO 烘焙等
+// 需要大量射线求交的场合。
+use crate::{
+  bounds::Aabb,
+  geometry::Vec3,
+  model::Model,
+  ray::{intersect_triangle, slab, Ray},
+  util::barycentric_interpolate,
+};
+
+// 叶子节点最多包含的三角形数
+const MAX_LEAF_SIZE: usize = 4;
+// 划分时使用的桶数
+const SAH_BUCKETS: usize = 12;
+// 遍历一个节点相对于求交一个三角形的代价
+const TRAVERSAL_COST: f32 = 1.;
+
+struct Triangle {
+  verts: [Vec3<f32>; 3],
+  normals: [Vec3<f32>; 3],
+  uvs: [Vec3<f32>; 3],
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+  bounds: Aabb,
+  // 叶子节点：第一个三角形的序号；内部节点：右子节点的序号，左子节点紧跟在自身后面
+  offset: usize,
+  // 为 0 时是内部节点
+  count: usize,
+  // 内部节点的划分轴，遍历时先访问射线方向上较近的子节点
+  axis: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BvhHit {
+  pub t: f32,
+  // 在模型中的面的序号
+  pub face: usize,
+  // 重心坐标
+  pub barycentric: Vec3<f32>,
+  pub pos: Vec3<f32>,
+  // 插值后的法向量
+  pub normal: Vec3<f32>,
+  pub uv: Vec3<f32>,
+}
+
+pub struct Bvh {
+  // 按叶子节点的顺序重新排列的三角形
+  triangles: Vec<Triangle>,
+  // triangles 中每个三角形在模型中的面的序号
+  faces: Vec<usize>,
+  nodes: Vec<Node>,
+}
+
+struct BuildItem {
+  face: usize,
+  bounds: Aabb,
+  centroid: Vec3<f32>,
+}
+
+impl Bvh {
+  pub fn new<M: Model>(model: &M) -> Bvh {
+    let has_uv = model.has_texture_coord();
+    let mut items: Vec<BuildItem> = (0..model.face_count())
+      .map(|face| {
+        let mut bounds = Aabb::empty();
+        for i in 0..3 {
+          bounds.extend(model.vert(face, i));
+        }
+        BuildItem {
+          face,
+          bounds,
+          centroid: bounds.center(),
+        }
+      })
+      .collect();
+    let mut nodes = vec![];
+    if !items.is_empty() {
+      build(&mut items, 0, &mut nodes);
+    }
+    let triangles = items
+      .iter()
+      .map(|item| {
+        let face = item.face;
+        Triangle {
+          verts: [0, 1, 2].map(|i| model.vert(face, i)),
+          normals: [0, 1, 2].map(|i| model.normal(face, i)),
+          uvs: [0, 1, 2].map(|i| {
+            if has_uv {
+              model.texture_coord(face, i)
+            } else {
+              Vec3::default()
+            }
+          }),
+        }
+      })
+      .collect();
+    Bvh {
+      triangles,
+      faces: items.iter().map(|item| item.face).collect(),
+      nodes,
+    }
+  }
+  pub fn bounds(&self) -> Aabb {
+    self.nodes.first().map_or(Aabb::empty(), |node| node.bounds)
+  }
+  pub fn face_count(&self) -> usize {
+    self.triangles.len()
+  }
+  // 最近的交点，t 在 (t_min, t_max) 内
+  pub fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<BvhHit> {
+    let mut closest = None;
+    self.traverse(ray, t_max, |idx, t_limit| {
+      if let Some((t, u, v)) = intersect_triangle(ray, &self.triangles[idx].verts, t_min) {
+        if t < *t_limit {
+          *t_limit = t;
+          closest = Some((idx, t, u, v));
+        }
+      }
+      false
+    });
+    closest.map(|(idx, t, u, v)| {
+      let tri = &self.triangles[idx];
+      let barycentric = Vec3::new(1. - u - v, u, v);
+      BvhHit {
+        t,
+        face: self.faces[idx],
+        barycentric,
+        pos: ray.at(t),
+        normal: barycentric_interpolate(&tri.normals, barycentric).normalize(),
+        uv: barycentric_interpolate(&tri.uvs, barycentric),
+      }
+    })
+  }
+  // 射线在 (t_min, t_max) 内是否与任意三角形相交，用于阴影和遮蔽
+  pub fn occluded(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+    let mut hit = false;
+    self.traverse(ray, t_max, |idx, t_limit| {
+      hit = matches!(
+        intersect_triangle(ray, &self.triangles[idx].verts, t_min),
+        Some((t, _, _)) if t < *t_limit
+      );
+      hit
+    });
+    hit
+  }
+  // 按由近到远的顺序访问与射线相交的叶子中的三角形，f 可以缩小 t_max，返回 true 时停止遍历
+  fn traverse<F: FnMut(usize, &mut f32) -> bool>(&self, ray: &Ray, t_max: f32, mut f: F) {
+    if self.nodes.is_empty() {
+      return;
+    }
+    let inv_dir = Vec3::new(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+    let mut t_max = t_max;
+    let mut stack = vec![0];
+    while let Some(idx) = stack.pop() {
+      let node = &self.nodes[idx];
+      if slab(ray.origin, inv_dir, &node.bounds, t_max).is_none() {
+        continue;
+      }
+      if node.count > 0 {
+        for i in node.offset..node.offset + node.count {
+          if f(i, &mut t_max) {
+            return;
+          }
+        }
+      } else if ray.dir[node.axis] < 0. {
+        stack.push(idx + 1);
+        stack.push(node.offset);
+      } else {
+        stack.push(node.offset);
+        stack.push(idx + 1);
+      }
+    }
+  }
+}
+
+fn build(items: &mut [BuildItem], start: usize, nodes: &mut Vec<Node>) -> usize {
+  let idx = nodes.len();
+  let bounds = items
+    .iter()
+    .fold(Aabb::empty(), |b, item| b.union(&item.bounds));
+  nodes.push(Node {
+    bounds,
+    offset: start,
+    count: items.len(),
+    axis: 0,
+  });
+  if items.len() <= 1 {
+    return idx;
+  }
+  let mut centroid_bounds = Aabb::empty();
+  items
+    .iter()
+    .for_each(|item| centroid_bounds.extend(item.centroid));
+  let extent = centroid_bounds.size();
+  let axis = if extent.x >= extent.y && extent.x >= extent.z {
+    0
+  } else if extent.y >= extent.z {
+    1
+  } else {
+    2
+  };
+  let (min, len) = (centroid_bounds.min[axis], extent[axis]);
+  if len <= 0. {
+    // 所有三角形的中心重合，无法划分
+    return idx;
+  }
+  let bucket_of = |item: &BuildItem| {
+    (((item.centroid[axis] - min) / len * SAH_BUCKETS as f32) as usize).min(SAH_BUCKETS - 1)
+  };
+  let mut buckets = [(0usize, Aabb::empty()); SAH_BUCKETS];
+  for item in items.iter() {
+    let b = &mut buckets[bucket_of(item)];
+    b.0 += 1;
+    b.1 = b.1.union(&item.bounds);
+  }
+  // 在第 i 个桶之后划分的代价
+  let area = bounds.surface_area();
+  let (split, cost) = (0..SAH_BUCKETS - 1)
+    .map(|i| {
+      let (n0, b0) = buckets[..=i]
+        .iter()
+        .fold((0, Aabb::empty()), |(n, b), (c, cb)| (n + c, b.union(cb)));
+      let (n1, b1) = buckets[i + 1..]
+        .iter()
+        .fold((0, Aabb::empty()), |(n, b), (c, cb)| (n + c, b.union(cb)));
+      let cost =
+        TRAVERSAL_COST + (n0 as f32 * b0.surface_area() + n1 as f32 * b1.surface_area()) / area;
+      (i, cost)
+    })
+    .fold((0, f32::INFINITY), |a, b| if b.1 < a.1 { b } else { a });
+  if items.len() <= MAX_LEAF_SIZE && cost >= items.len() as f32 {
+    return idx;
+  }
+  // 把划分位置之前的三角形移到前面
+  let mut mid = 0;
+  for i in 0..items.len() {
+    if bucket_of(&items[i]) <= split {
+      items.swap(i, mid);
+      mid += 1;
+    }
+  }
+  if mid == 0 || mid == items.len() {
+    mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+  }
+  let (left, right) = items.split_at_mut(mid);
+  build(left, start, nodes);
+  let right_idx = build(right, start + mid, nodes);
+  nodes[idx].offset = right_idx;
+  nodes[idx].count = 0;
+  nodes[idx].axis = axis;
+  idx
+}
+
+#[test]
+fn test_bvh_matches_brute_force() {
+  let model = crate::model::Object::from_file("models/spot/spot_triangulated.obj").unwrap();
+  let bvh = Bvh::new(&model);
+  let mut hits = 0;
+  for i in 0..64 {
+    let a = i as f32 * 0.7;
+    let origin = Vec3::new(a.cos() * 3., (i % 8) as f32 * 0.2 - 0.7, a.sin() * 3.);
+    let ray = Ray::new(origin, (Vec3::new(0., 0., 0.) - origin).normalize());
+    let brute = (0..model.face_count())
+      .filter_map(|face| {
+        let verts = [0, 1, 2].map(|i| model.vert(face, i));
+        intersect_triangle(&ray, &verts, 0.).map(|(t, _, _)| (face, t))
+      })
+      .min_by(|a, b| a.1.total_cmp(&b.1));
+    let hit = bvh.intersect(&ray, 0., f32::INFINITY);
+    assert_eq!(hit.map(|h| h.face), brute.map(|b| b.0));
+    assert_eq!(bvh.occluded(&ray, 0., f32::INFINITY), brute.is_some());
+    hits += hit.is_some() as u32;
+  }
+  assert!(hits > 0);
+}
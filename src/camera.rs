@@ -0,0 +1,258 @@
+// 交互式相机控制器：绕目标旋转的轨道相机和 WASD + 鼠标视角的第一人称相机。
+// 控制器接收 input::InputEvent，view() 返回与 transform::camera 相同形式的视图矩阵。
+use std::{collections::HashSet, f32::consts::FRAC_PI_2, time::Duration};
+
+use crate::{
+  geometry::Vec3,
+  input::{InputEvent, Key, MouseButton},
+  mat::Mat4,
+  transform,
+};
+
+// 俯仰角不能到达 ±90°，否则视线方向与 up 平行
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+// 轨道相机：左键拖动绕目标旋转，右键或中键拖动平移目标，滚轮缩放距离
+pub struct OrbitCamera {
+  pub target: Vec3<f32>,
+  pub distance: f32,
+  // 绕 y 轴的角度，为 0 时相机在目标的 +z 方向
+  pub yaw: f32,
+  pub pitch: f32,
+  // 每像素旋转的弧度
+  pub rotate_speed: f32,
+  // 每格滚轮距离缩放的比例
+  pub zoom_speed: f32,
+  pub min_distance: f32,
+  pub max_distance: f32,
+  dragging: Option<MouseButton>,
+}
+impl OrbitCamera {
+  pub fn new(target: Vec3<f32>, distance: f32) -> OrbitCamera {
+    OrbitCamera {
+      target,
+      distance,
+      yaw: 0.,
+      pitch: 0.,
+      rotate_speed: 0.01,
+      zoom_speed: 0.9,
+      min_distance: 0.01,
+      max_distance: f32::INFINITY,
+      dragging: None,
+    }
+  }
+  pub fn angles(mut self, yaw: f32, pitch: f32) -> OrbitCamera {
+    self.yaw = yaw;
+    self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    self
+  }
+  pub fn distance_range(mut self, min: f32, max: f32) -> OrbitCamera {
+    self.min_distance = min;
+    self.max_distance = max;
+    self.distance = self.distance.clamp(min, max);
+    self
+  }
+  pub fn handle(&mut self, event: &InputEvent) {
+    match *event {
+      InputEvent::MouseDown { button, .. } if self.dragging.is_none() => {
+        self.dragging = Some(button)
+      }
+      InputEvent::MouseUp { button, .. } if self.dragging == Some(button) => self.dragging = None,
+      InputEvent::MouseMotion { dx, dy, .. } => match self.dragging {
+        Some(MouseButton::Left) => self.rotate(dx as f32, dy as f32),
+        Some(MouseButton::Right | MouseButton::Middle) => self.pan(dx as f32, dy as f32),
+        _ => {}
+      },
+      InputEvent::MouseWheel(delta) => self.zoom(delta),
+      _ => {}
+    }
+  }
+  // 鼠标向右拖动时相机向左转，看到物体的右侧
+  pub fn rotate(&mut self, dx: f32, dy: f32) {
+    self.yaw -= dx * self.rotate_speed;
+    self.pitch = (self.pitch + dy * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+  }
+  // 在视平面内移动目标，移动量与距离成正比
+  pub fn pan(&mut self, dx: f32, dy: f32) {
+    let forward = (self.target - self.position()).normalize();
+    let right = forward.cross_product(Vec3::new(0., 1., 0.)).normalize();
+    let up = right.cross_product(forward);
+    let s = self.distance * self.rotate_speed * 0.1;
+    self.target = self.target - right * (dx * s) + up * (dy * s);
+  }
+  pub fn zoom(&mut self, delta: f32) {
+    self.distance =
+      (self.distance * self.zoom_speed.powf(delta)).clamp(self.min_distance, self.max_distance);
+  }
+  pub fn position(&self) -> Vec3<f32> {
+    let (sy, cy) = self.yaw.sin_cos();
+    let (sp, cp) = self.pitch.sin_cos();
+    self.target + Vec3::new(cp * sy, sp, cp * cy) * self.distance
+  }
+  pub fn view(&self) -> Mat4 {
+    transform::camera(Vec3::new(0., 1., 0.), self.position(), self.target)
+  }
+}
+
+// 第一人称相机：WASD 前后左右移动，空格/Ctrl 上升下降，按住 Shift 加速，鼠标控制视角
+pub struct FpsCamera {
+  pub pos: Vec3<f32>,
+  // 为 0 时朝向 -z，为正时向右转
+  pub yaw: f32,
+  pub pitch: f32,
+  // 每秒移动的距离
+  pub speed: f32,
+  // 每像素旋转的弧度
+  pub sensitivity: f32,
+  // 按住这个键时才转动视角，为 None 时鼠标移动总是转动视角(适合相对鼠标模式)
+  pub look_button: Option<MouseButton>,
+  pressed: HashSet<Key>,
+  looking: bool,
+}
+impl FpsCamera {
+  pub fn new(pos: Vec3<f32>) -> FpsCamera {
+    FpsCamera {
+      pos,
+      yaw: 0.,
+      pitch: 0.,
+      speed: 2.,
+      sensitivity: 0.003,
+      look_button: Some(MouseButton::Left),
+      pressed: HashSet::new(),
+      looking: false,
+    }
+  }
+  pub fn angles(mut self, yaw: f32, pitch: f32) -> FpsCamera {
+    self.yaw = yaw;
+    self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    self
+  }
+  pub fn speed(mut self, speed: f32) -> FpsCamera {
+    self.speed = speed;
+    self
+  }
+  pub fn look_button(mut self, button: Option<MouseButton>) -> FpsCamera {
+    self.look_button = button;
+    self
+  }
+  pub fn handle(&mut self, event: &InputEvent) {
+    match *event {
+      InputEvent::KeyDown(key) => {
+        self.pressed.insert(key);
+      }
+      InputEvent::KeyUp(key) => {
+        self.pressed.remove(&key);
+      }
+      InputEvent::MouseDown { button, .. } if Some(button) == self.look_button => {
+        self.looking = true
+      }
+      InputEvent::MouseUp { button, .. } if Some(button) == self.look_button => {
+        self.looking = false
+      }
+      InputEvent::MouseMotion { dx, dy, .. } if self.looking || self.look_button.is_none() => {
+        self.look(dx as f32, dy as f32)
+      }
+      _ => {}
+    }
+  }
+  pub fn look(&mut self, dx: f32, dy: f32) {
+    self.yaw += dx * self.sensitivity;
+    self.pitch = (self.pitch - dy * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+  }
+  pub fn forward(&self) -> Vec3<f32> {
+    let (sy, cy) = self.yaw.sin_cos();
+    let (sp, cp) = self.pitch.sin_cos();
+    Vec3::new(cp * sy, sp, -cp * cy)
+  }
+  // 根据按下的键移动，每帧调用一次
+  pub fn update(&mut self, delta: Duration) {
+    let pressed = |ch| self.pressed.contains(&Key::Char(ch));
+    let forward = self.forward();
+    let right = forward.cross_product(Vec3::new(0., 1., 0.)).normalize();
+    let mut dir = Vec3::new(0., 0., 0.);
+    if pressed('w') {
+      dir = dir + forward;
+    }
+    if pressed('s') {
+      dir = dir - forward;
+    }
+    if pressed('d') {
+      dir = dir + right;
+    }
+    if pressed('a') {
+      dir = dir - right;
+    }
+    if self.pressed.contains(&Key::Space) {
+      dir.y += 1.;
+    }
+    if self.pressed.contains(&Key::Ctrl) {
+      dir.y -= 1.;
+    }
+    if dir.norm() == 0. {
+      return;
+    }
+    let speed = if self.pressed.contains(&Key::Shift) {
+      self.speed * 3.
+    } else {
+      self.speed
+    };
+    self.pos = self.pos + dir.normalize() * (speed * delta.as_secs_f32());
+  }
+  pub fn view(&self) -> Mat4 {
+    transform::camera(Vec3::new(0., 1., 0.), self.pos, self.pos + self.forward())
+  }
+}
+
+#[test]
+fn test_camera_controllers() {
+  let near = |a: Vec3<f32>, b: Vec3<f32>| (a - b).norm() < 1e-4;
+  let mut orbit = OrbitCamera::new(Vec3::new(0., 1., 0.), 5.);
+  assert!(near(orbit.position(), Vec3::new(0., 1., 5.)));
+  assert!(
+    orbit.view()
+      == transform::camera(
+        Vec3::new(0., 1., 0.),
+        Vec3::new(0., 1., 5.),
+        Vec3::new(0., 1., 0.)
+      )
+  );
+  orbit.rotate_speed = FRAC_PI_2 / 100.;
+  orbit.handle(&InputEvent::MouseDown {
+    button: MouseButton::Left,
+    x: 0,
+    y: 0,
+  });
+  orbit.handle(&InputEvent::MouseMotion {
+    x: 0,
+    y: 0,
+    dx: -100,
+    dy: 0,
+  });
+  // 向左拖动 90°，相机转到目标的 +x 方向，目标仍在视线正前方
+  assert!(near(orbit.position(), Vec3::new(5., 1., 0.)));
+  assert!(near(&orbit.view() * &orbit.target, Vec3::new(0., 0., -5.)));
+  orbit.handle(&InputEvent::MouseWheel(-1.));
+  assert!((orbit.distance - 5. / 0.9).abs() < 1e-4);
+
+  let mut fps = FpsCamera::new(Vec3::new(0., 0., 0.)).speed(2.);
+  fps.handle(&InputEvent::KeyDown(Key::Char('w')));
+  fps.update(Duration::from_millis(500));
+  assert!(near(fps.pos, Vec3::new(0., 0., -1.)));
+  fps.handle(&InputEvent::KeyUp(Key::Char('w')));
+  // 没有按下鼠标时不转动视角
+  fps.handle(&InputEvent::MouseMotion {
+    x: 0,
+    y: 0,
+    dx: 100,
+    dy: 0,
+  });
+  assert_eq!(fps.yaw, 0.);
+  fps.look(FRAC_PI_2 / fps.sensitivity, 0.);
+  fps.handle(&InputEvent::KeyDown(Key::Char('w')));
+  fps.update(Duration::from_millis(500));
+  assert!(near(fps.pos, Vec3::new(1., 0., -1.)));
+  assert!(near(
+    &fps.view() * &Vec3::new(2., 0., -1.),
+    Vec3::new(0., 0., -1.)
+  ));
+}
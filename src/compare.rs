@@ -0,0 +1,133 @@
+use crate::{
+  geometry::Vec3,
+  image::{Image, PixImage},
+};
+
+// 两张图片的差异统计，只比较 RGB 通道。
+// 大小不同时只统计重叠的部分，重叠部分以外的像素都算作超过容差
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDiff {
+  // 大小不同时 within 总是返回 false
+  pub same_size: bool,
+  // 单个通道的最大差值
+  pub max_diff: u8,
+  // 差值超过容差的像素数
+  pub mismatched: usize,
+  // 峰值信噪比，单位 dB，两图完全相同时为无穷大
+  pub psnr: f32,
+}
+
+// 比较时允许的误差
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+  // 单个通道允许的差值
+  pub per_channel: u8,
+  // 允许超过 per_channel 的像素数
+  pub max_mismatched: usize,
+  pub min_psnr: f32,
+}
+impl Default for Tolerance {
+  fn default() -> Self {
+    Tolerance {
+      per_channel: 2,
+      max_mismatched: 0,
+      min_psnr: 40.,
+    }
+  }
+}
+
+impl ImageDiff {
+  pub fn within(&self, tolerance: &Tolerance) -> bool {
+    self.same_size && self.mismatched <= tolerance.max_mismatched && self.psnr >= tolerance.min_psnr
+  }
+}
+
+pub fn compare<A: Image, B: Image>(a: &A, b: &B, tolerance: &Tolerance) -> ImageDiff {
+  let (w, h) = (a.width().min(b.width()), a.height().min(b.height()));
+  let area = |w: u32, h: u32| w as usize * h as usize;
+  let same_size = a.width() == b.width() && a.height() == b.height();
+  let mut max_diff = 0;
+  // 只在一张图片中的像素
+  let mut mismatched = area(a.width(), a.height()) + area(b.width(), b.height()) - 2 * area(w, h);
+  let mut squared_error = 0.;
+  for y in 0..h {
+    for x in 0..w {
+      let ca = a.get(x, y);
+      let cb = b.get(x, y);
+      let mut pix_diff = 0;
+      for i in 0..3 {
+        let d = ca[i].abs_diff(cb[i]);
+        pix_diff = pix_diff.max(d);
+        squared_error += (d as f64) * (d as f64);
+      }
+      max_diff = max_diff.max(pix_diff);
+      if pix_diff > tolerance.per_channel {
+        mismatched += 1;
+      }
+    }
+  }
+  let mse = squared_error / (w as f64 * h as f64 * 3.);
+  ImageDiff {
+    same_size,
+    max_diff,
+    mismatched,
+    psnr: psnr_of_mse(mse),
+  }
+}
+
+fn psnr_of_mse(mse: f64) -> f32 {
+  if mse == 0. {
+    f32::INFINITY
+  } else {
+    (10. * (255. * 255. / mse).log10()) as f32
+  }
+}
+
+pub fn psnr<A: Image, B: Image>(a: &A, b: &B) -> f32 {
+  compare(a, b, &Tolerance::default()).psnr
+}
+
+// 差异图：相同的像素显示为变暗的原图，不同的像素按差值显示为红色。
+// 大小不同时差异图包含两张图片，只在一张图片中的像素显示为红色
+pub fn diff_image<A: Image, B: Image>(a: &A, b: &B) -> PixImage {
+  let (w, h) = (a.width().max(b.width()), a.height().max(b.height()));
+  let mut img = PixImage::new(w, h);
+  for y in 0..h {
+    for x in 0..w {
+      let inside = |img: &dyn Image| x < img.width() && y < img.height();
+      if !inside(a) || !inside(b) {
+        img.set_rgb(x, y, Vec3::new(1., 0., 0.));
+        continue;
+      }
+      let ca = a.get_rgbf(x, y);
+      let cb = b.get_rgbf(x, y);
+      let d = ca - cb;
+      let d = d.x.abs().max(d.y.abs()).max(d.z.abs());
+      if d > 0. {
+        img.set_rgb(x, y, Vec3::new(0.5 + d / 2., 0., 0.));
+      } else {
+        img.set_rgb(x, y, ca * 0.3);
+      }
+    }
+  }
+  img
+}
+
+#[test]
+fn test_compare() {
+  let mut a = PixImage::new(4, 4);
+  let b = PixImage::new(4, 4);
+  assert!(compare(&a, &b, &Tolerance::default()).psnr.is_infinite());
+  a.set_rgb24(1, 1, Vec3::new(10, 0, 0));
+  let diff = compare(&a, &b, &Tolerance::default());
+  assert_eq!((diff.max_diff, diff.mismatched), (10, 1));
+  assert!(!diff.within(&Tolerance::default()));
+
+  // 大小不同时不 panic，重叠部分以外的像素都不同
+  let c = PixImage::new(4, 3);
+  let diff = compare(&b, &c, &Tolerance::default());
+  assert_eq!((diff.same_size, diff.mismatched), (false, 4));
+  assert!(!diff.within(&Tolerance::default()));
+  let img = diff_image(&c, &b);
+  assert_eq!((img.width, img.height), (4, 4));
+}
@@ -0,0 +1,170 @@
+use crate::image::{Image, PixImage};
+
+// 深度缓冲的存储格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthFormat {
+  F32,
+  // 16 位定点数（unorm），深度范围线性映射到 [0, 2^16-1]
+  Unorm16,
+  // 24 位定点数（unorm），深度范围线性映射到 [0, 2^24-1]
+  Unorm24,
+}
+impl DepthFormat {
+  fn max_value(&self) -> u32 {
+    match self {
+      DepthFormat::F32 => u32::MAX,
+      DepthFormat::Unorm16 => (1 << 16) - 1,
+      DepthFormat::Unorm24 => (1 << 24) - 1,
+    }
+  }
+}
+
+// 深度测试函数，new 为片元深度，stored 为缓冲中的深度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthCompare {
+  // 默认：越大越近
+  Greater,
+  GreaterEqual,
+  Less,
+  LessEqual,
+  Always,
+  Never,
+}
+impl DepthCompare {
+  pub fn test<T: PartialOrd>(&self, new: T, stored: T) -> bool {
+    match self {
+      DepthCompare::Greater => new > stored,
+      DepthCompare::GreaterEqual => new >= stored,
+      DepthCompare::Less => new < stored,
+      DepthCompare::LessEqual => new <= stored,
+      DepthCompare::Always => true,
+      DepthCompare::Never => false,
+    }
+  }
+}
+
+// 光栅化时使用的深度缓冲，index 为 y * width + x
+pub trait DepthTarget {
+  // 深度测试是否通过
+  fn test_depth(&self, index: usize, z: f32, compare: DepthCompare) -> bool;
+  fn set_depth(&mut self, index: usize, z: f32);
+  // 缓冲中存储的深度
+  fn depth(&self, index: usize) -> f32;
+}
+
+impl DepthTarget for [f32] {
+  fn test_depth(&self, index: usize, z: f32, compare: DepthCompare) -> bool {
+    compare.test(z, self[index])
+  }
+  fn set_depth(&mut self, index: usize, z: f32) {
+    self[index] = z;
+  }
+  fn depth(&self, index: usize) -> f32 {
+    self[index]
+  }
+}
+impl DepthTarget for Vec<f32> {
+  fn test_depth(&self, index: usize, z: f32, compare: DepthCompare) -> bool {
+    self.as_slice().test_depth(index, z, compare)
+  }
+  fn set_depth(&mut self, index: usize, z: f32) {
+    self.as_mut_slice().set_depth(index, z)
+  }
+  fn depth(&self, index: usize) -> f32 {
+    self.as_slice().depth(index)
+  }
+}
+
+// 可选择存储格式的深度缓冲，用来对比不同精度下的深度冲突(z-fighting)
+pub struct DepthBuffer {
+  pub width: u32,
+  pub height: u32,
+  format: DepthFormat,
+  // 定点数格式表示的深度范围，应与视口的深度范围一致
+  range: (f32, f32),
+  // F32 格式存储 f32 的二进制表示
+  data: Vec<u32>,
+}
+impl DepthBuffer {
+  pub fn new(width: u32, height: u32, format: DepthFormat) -> DepthBuffer {
+    DepthBuffer {
+      width,
+      height,
+      format,
+      range: (-1., 1.),
+      data: vec![0; (width * height) as usize],
+    }
+  }
+  pub fn range(mut self, near: f32, far: f32) -> DepthBuffer {
+    self.range = (near, far);
+    self
+  }
+  pub fn format(&self) -> DepthFormat {
+    self.format
+  }
+  fn encode(&self, z: f32) -> u32 {
+    match self.format {
+      DepthFormat::F32 => z.to_bits(),
+      _ => {
+        let (near, far) = self.range;
+        let t = ((z - near) / (far - near)).clamp(0., 1.);
+        (t as f64 * self.format.max_value() as f64).round() as u32
+      }
+    }
+  }
+  fn decode(&self, v: u32) -> f32 {
+    match self.format {
+      DepthFormat::F32 => f32::from_bits(v),
+      _ => {
+        let (near, far) = self.range;
+        let t = v as f64 / self.format.max_value() as f64;
+        (near as f64 + t * (far - near) as f64) as f32
+      }
+    }
+  }
+  pub fn clear(&mut self, z: f32) {
+    let v = self.encode(z);
+    self.data.fill(v);
+  }
+  // 按存储格式量化后的深度
+  pub fn quantize(&self, z: f32) -> f32 {
+    self.decode(self.encode(z))
+  }
+  // 把深度可视化为灰度图，[near,far] 映射到 [黑,白]
+  pub fn to_image(&self, near: f32, far: f32) -> PixImage {
+    let mut img = PixImage::new(self.width, self.height);
+    for y in 0..self.height {
+      for x in 0..self.width {
+        let z = self.depth((y * self.width + x) as usize);
+        let c = (z - near) / (far - near);
+        img.set_rgb(x, y, crate::geometry::Vec3::new(c, c, c));
+      }
+    }
+    img
+  }
+}
+impl DepthTarget for DepthBuffer {
+  fn test_depth(&self, index: usize, z: f32, compare: DepthCompare) -> bool {
+    match self.format {
+      DepthFormat::F32 => compare.test(z, f32::from_bits(self.data[index])),
+      _ => compare.test(self.encode(z), self.data[index]),
+    }
+  }
+  fn set_depth(&mut self, index: usize, z: f32) {
+    self.data[index] = self.encode(z);
+  }
+  fn depth(&self, index: usize) -> f32 {
+    self.decode(self.data[index])
+  }
+}
+
+#[test]
+fn test_unorm_quantize() {
+  let buf = DepthBuffer::new(1, 1, DepthFormat::Unorm16).range(0., 1.);
+  assert_eq!(buf.quantize(1.), 1.);
+  assert_eq!(buf.quantize(-1.), 0.);
+  // 两个相距很近的深度在 16 位下无法区分，在 24 位下可以
+  assert_eq!(buf.encode(0.5), buf.encode(0.500001));
+  let buf = DepthBuffer::new(1, 1, DepthFormat::Unorm24).range(0., 1.);
+  assert!(buf.encode(0.5) < buf.encode(0.500001));
+}
@@ -0,0 +1,102 @@
+// 读取图片和模型时的错误：IO 错误、数据格式错误和还不支持的特性。
+// 从文件读取时带上文件路径，文本格式还带上出错的行号
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ErrorKind {
+  Io(std::io::Error),
+  // 数据不符合格式或不完整
+  Format(String),
+  // 数据符合格式，但用到了还不支持的特性
+  Unsupported(String),
+}
+
+#[derive(Debug)]
+pub struct Error {
+  pub kind: ErrorKind,
+  pub path: Option<PathBuf>,
+  // 从 1 开始
+  pub line: Option<usize>,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+  pub fn new(kind: ErrorKind) -> Error {
+    Error {
+      kind,
+      path: None,
+      line: None,
+    }
+  }
+  pub fn format<S: Into<String>>(msg: S) -> Error {
+    Error::new(ErrorKind::Format(msg.into()))
+  }
+  pub fn unsupported<S: Into<String>>(msg: S) -> Error {
+    Error::new(ErrorKind::Unsupported(msg.into()))
+  }
+  // 已经有路径时保留原来的路径
+  pub fn with_path<P: AsRef<Path>>(mut self, path: P) -> Error {
+    self.path.get_or_insert_with(|| path.as_ref().to_path_buf());
+    self
+  }
+  pub fn at_line(mut self, line: usize) -> Error {
+    self.line.get_or_insert(line);
+    self
+  }
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(path) = &self.path {
+      write!(f, "{}:", path.display())?;
+      if let Some(line) = self.line {
+        write!(f, "{}:", line)?;
+      }
+      write!(f, " ")?;
+    } else if let Some(line) = self.line {
+      write!(f, "line {}: ", line)?;
+    }
+    match &self.kind {
+      ErrorKind::Io(e) => write!(f, "{}", e),
+      ErrorKind::Format(msg) => write!(f, "invalid data: {}", msg),
+      ErrorKind::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match &self.kind {
+      ErrorKind::Io(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for Error {
+  fn from(e: std::io::Error) -> Error {
+    Error::new(ErrorKind::Io(e))
+  }
+}
+
+impl From<crate::zlib::InflateError> for Error {
+  fn from(e: crate::zlib::InflateError) -> Error {
+    Error::format(e.to_string())
+  }
+}
+
+#[test]
+fn test_error_display() {
+  let e = Error::format("bad face").at_line(3).with_path("a.obj");
+  assert_eq!(e.to_string(), "a.obj:3: invalid data: bad face");
+  // 内层已经设置的路径和行号不被覆盖
+  let e = e.at_line(5).with_path("b.obj");
+  assert_eq!(
+    (e.line, e.path.as_deref()),
+    (Some(3), Some(Path::new("a.obj")))
+  );
+  let e = Error::from(std::io::Error::new(std::io::ErrorKind::NotFound, "missing"));
+  assert!(matches!(e.kind, ErrorKind::Io(_)));
+  assert_eq!(Error::unsupported("RLE4").to_string(), "unsupported: RLE4");
+}
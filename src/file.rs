@@ -11,3 +11,9 @@ pub fn save_image<P: AsRef<Path>, E: Encoder, I: crate::image::Image>(
   std::fs::write(path, encoded)
 }
 
+// 按扩展名在 format 模块的注册表中选择编码器。Encoder::encode 看不到路径，也不能失败，
+// 而按扩展名选择时可能没有对应的编码器，或者图片超出格式的大小上限，所以不能作为 save_image 的一个编码器，
+// 返回值也是 error::Result 而不是 io::Result
+pub fn save_image_auto<P: AsRef<Path>, I: Image>(path: P, img: &I) -> crate::error::Result<()> {
+  crate::format::save(path, img)
+}
@@ -0,0 +1,321 @@
+// 图片格式注册表：解码时先按文件头的魔数识别格式，识别不出再按扩展名；编码时按扩展名。
+// 内置 PNG、BMP、TGA、PPM/PGM/PFM、Radiance HDR 和 GIF(只能编码)，运行时可以用 register 添加新的格式，
+// 后注册的格式优先，所以也可以覆盖内置格式。
+use std::{
+  path::Path,
+  sync::{OnceLock, RwLock},
+};
+
+use crate::{
+  error::{Error, Result},
+  geometry::{Vec3, Vec4},
+  image::{Image, ImageOriginPos, PixImage},
+  image_decoder::{Decoder, BMP, HDR, PFM, PNG, TGA},
+  image_encoder::{Encoder, RawPGM, RawPPM, GIF},
+  pixel::PixelFormat,
+};
+
+type Magic = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+type EncodeFn = Box<dyn Fn(&dyn Image) -> Vec<u8> + Send + Sync>;
+
+pub struct ImageFormat {
+  pub name: String,
+  // 小写，不带点
+  pub extensions: Vec<String>,
+  magic: Option<Magic>,
+  decoder: Option<Box<dyn Decoder + Send + Sync>>,
+  encoder: Option<EncodeFn>,
+  max_size: Option<u32>,
+}
+impl ImageFormat {
+  pub fn new(name: &str) -> ImageFormat {
+    ImageFormat {
+      name: name.to_string(),
+      extensions: vec![],
+      magic: None,
+      decoder: None,
+      encoder: None,
+      max_size: None,
+    }
+  }
+  pub fn extensions(mut self, extensions: &[&str]) -> ImageFormat {
+    self.extensions = extensions.iter().map(|e| e.to_ascii_lowercase()).collect();
+    self
+  }
+  // 根据文件开头的数据判断是不是这种格式
+  pub fn magic<F: Fn(&[u8]) -> bool + Send + Sync + 'static>(mut self, magic: F) -> ImageFormat {
+    self.magic = Some(Box::new(magic));
+    self
+  }
+  pub fn decoder<D: Decoder + Send + Sync + 'static>(mut self, decoder: D) -> ImageFormat {
+    self.decoder = Some(Box::new(decoder));
+    self
+  }
+  pub fn encoder<E: Encoder + Send + Sync + 'static>(mut self, encoder: E) -> ImageFormat {
+    self.encoder = Some(Box::new(move |img| encoder.encode(&ImageRef(img))));
+    self
+  }
+  // 宽高的上限，编码空图片或超出上限的图片时返回错误
+  pub fn max_size(mut self, max_size: u32) -> ImageFormat {
+    self.max_size = Some(max_size);
+    self
+  }
+  pub fn can_decode(&self) -> bool {
+    self.decoder.is_some()
+  }
+  pub fn can_encode(&self) -> bool {
+    self.encoder.is_some()
+  }
+  fn matches_magic(&self, data: &[u8]) -> bool {
+    self.magic.as_ref().is_some_and(|magic| magic(data))
+  }
+  fn matches_extension(&self, path: &Path) -> bool {
+    path
+      .extension()
+      .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+      .is_some_and(|ext| self.extensions.contains(&ext))
+  }
+}
+
+// Encoder::encode 的参数必须是 Sized，用它把 &dyn Image 交给编码器。
+// 读取浮点颜色的方法也要转发，否则 HDR、PFM 编码器只能读到截断到 [0,1] 的 8 位颜色
+struct ImageRef<'a>(&'a dyn Image);
+impl Image for ImageRef<'_> {
+  fn width(&self) -> u32 {
+    self.0.width()
+  }
+  fn height(&self) -> u32 {
+    self.0.height()
+  }
+  fn image_origin(&self) -> ImageOriginPos {
+    self.0.image_origin()
+  }
+  fn pixel_format(&self) -> PixelFormat {
+    self.0.pixel_format()
+  }
+  fn get_rgba(&self, x: u32, y: u32) -> Vec4<u8> {
+    self.0.get_rgba(x, y)
+  }
+  fn get_rgbaf(&self, x: u32, y: u32) -> Vec4<f32> {
+    self.0.get_rgbaf(x, y)
+  }
+  fn get_rgbf(&self, x: u32, y: u32) -> Vec3<f32> {
+    self.0.get_rgbf(x, y)
+  }
+  // 只读，编码器不会写入像素
+  fn set_rgba32(&mut self, _x: u32, _y: u32, _color: Vec4<u8>) {}
+}
+
+fn netpbm_magic(kinds: &'static [u8]) -> impl Fn(&[u8]) -> bool {
+  |data| {
+    data.len() > 2 && data[0] == b'P' && kinds.contains(&data[1]) && data[2].is_ascii_whitespace()
+  }
+}
+
+pub struct Registry {
+  formats: Vec<ImageFormat>,
+}
+impl Registry {
+  // 不包含任何格式
+  pub fn new() -> Registry {
+    Registry { formats: vec![] }
+  }
+  pub fn builtin() -> Registry {
+    let mut registry = Registry::new();
+    // TGA 2.0 的文件末尾有签名，更早的文件只能靠扩展名识别
+    registry.register(
+      ImageFormat::new("TGA")
+        .extensions(&["tga", "icb", "vda", "vst"])
+        .magic(|data| data.ends_with(b"TRUEVISION-XFILE.\0"))
+        .decoder(TGA::new())
+        .encoder(TGA::new()),
+    );
+    registry.register(
+      ImageFormat::new("PNG")
+        .extensions(&["png"])
+        .magic(|data| data.starts_with(b"\x89PNG\r\n\x1a\n"))
+        .decoder(PNG::new())
+        .encoder(PNG::new()),
+    );
+    registry.register(
+      ImageFormat::new("BMP")
+        .extensions(&["bmp", "dib"])
+        .magic(|data| data.starts_with(b"BM"))
+        .decoder(BMP::new())
+        .encoder(BMP::new()),
+    );
+    registry.register(
+      ImageFormat::new("PPM")
+        .extensions(&["ppm", "pnm"])
+        .magic(netpbm_magic(b"36"))
+        .decoder(RawPPM)
+        .encoder(RawPPM),
+    );
+    registry.register(
+      ImageFormat::new("PGM")
+        .extensions(&["pgm"])
+        .magic(netpbm_magic(b"25"))
+        .decoder(RawPGM)
+        .encoder(RawPGM),
+    );
+    registry.register(
+      ImageFormat::new("PFM")
+        .extensions(&["pfm"])
+        .magic(netpbm_magic(b"Ff"))
+        .decoder(PFM)
+        .encoder(PFM),
+    );
+    registry.register(
+      ImageFormat::new("HDR")
+        .extensions(&["hdr", "rgbe"])
+        .magic(|data| data.starts_with(b"#?RADIANCE\n") || data.starts_with(b"#?RGBE\n"))
+        .decoder(HDR::new())
+        .encoder(HDR::new()),
+    );
+    registry.register(
+      ImageFormat::new("GIF")
+        .extensions(&["gif"])
+        .max_size(u16::MAX as u32)
+        .encoder(GIF::new()),
+    );
+    registry
+  }
+  pub fn register(&mut self, format: ImageFormat) {
+    self.formats.push(format);
+  }
+  pub fn formats(&self) -> impl Iterator<Item = &ImageFormat> {
+    self.formats.iter().rev()
+  }
+  // path 为 None 时只按魔数识别
+  pub fn detect(&self, path: Option<&Path>, data: &[u8]) -> Option<&ImageFormat> {
+    let decoders = || self.formats().filter(|f| f.can_decode());
+    decoders().find(|f| f.matches_magic(data)).or_else(|| {
+      let path = path?;
+      decoders().find(|f| f.matches_extension(path))
+    })
+  }
+  pub fn decode(&self, path: Option<&Path>, data: Vec<u8>) -> Result<PixImage> {
+    let format = self
+      .detect(path, &data)
+      .ok_or_else(|| Error::unsupported("unknown image format"))?;
+    format.decoder.as_ref().unwrap().decode(data)
+  }
+  pub fn encode<I: Image>(&self, path: &Path, img: &I) -> Result<Vec<u8>> {
+    let format = self
+      .formats()
+      .find(|f| f.can_encode() && f.matches_extension(path))
+      .ok_or_else(|| {
+        Error::unsupported(format!(
+          "no image encoder for extension {:?}",
+          path.extension().unwrap_or_default()
+        ))
+      })?;
+    let (w, h) = (img.width(), img.height());
+    if let Some(max) = format.max_size {
+      if w == 0 || h == 0 || w > max || h > max {
+        return Err(Error::unsupported(format!(
+          "{} image size must be in 1..={}, got {}x{}",
+          format.name, max, w, h
+        )));
+      }
+    }
+    Ok(format.encoder.as_ref().unwrap()(img))
+  }
+  pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<PixImage> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|e| Error::from(e).with_path(path))?;
+    self.decode(Some(path), data).map_err(|e| e.with_path(path))
+  }
+  pub fn save<P: AsRef<Path>, I: Image>(&self, path: P, img: &I) -> Result<()> {
+    let path = path.as_ref();
+    let data = self.encode(path, img).map_err(|e| e.with_path(path))?;
+    std::fs::write(path, data).map_err(|e| Error::from(e).with_path(path))
+  }
+}
+impl Default for Registry {
+  fn default() -> Registry {
+    Registry::builtin()
+  }
+}
+
+// util::load_image 和 file::save_image_auto 使用的全局注册表
+fn registry() -> &'static RwLock<Registry> {
+  static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+  REGISTRY.get_or_init(|| RwLock::new(Registry::builtin()))
+}
+pub fn register(format: ImageFormat) {
+  registry()
+    .write()
+    .unwrap_or_else(|e| e.into_inner())
+    .register(format)
+}
+pub fn load<P: AsRef<Path>>(path: P) -> Result<PixImage> {
+  registry()
+    .read()
+    .unwrap_or_else(|e| e.into_inner())
+    .load(path)
+}
+pub fn save<P: AsRef<Path>, I: Image>(path: P, img: &I) -> Result<()> {
+  registry()
+    .read()
+    .unwrap_or_else(|e| e.into_inner())
+    .save(path, img)
+}
+
+#[test]
+fn test_registry() {
+  let mut img = PixImage::new(2, 1);
+  img.set_rgba32(1, 0, Vec4::new(10, 20, 30, 255));
+  let registry = Registry::builtin();
+  let name = |path: Option<&str>, data: &[u8]| {
+    registry
+      .detect(path.map(Path::new), data)
+      .map(|f| f.name.as_str())
+  };
+  // 魔数优先于扩展名
+  let png = registry.encode(Path::new("a.PNG"), &img).unwrap();
+  assert_eq!(name(Some("a.tga"), &png), Some("PNG"));
+  let ppm = registry.encode(Path::new("a.ppm"), &img).unwrap();
+  assert_eq!(name(None, &ppm), Some("PPM"));
+  let tga = registry.encode(Path::new("a.tga"), &img).unwrap();
+  assert_eq!(name(Some("a.tga"), &tga), Some("TGA"));
+  assert_eq!(name(Some("a.dat"), &tga), None);
+  let decoded = registry.decode(Some(Path::new("a.tga")), tga).unwrap();
+  assert_eq!(decoded.get_rgba(1, 0).x, 10);
+  // GIF 只能编码
+  let gif = registry.encode(Path::new("a.gif"), &img).unwrap();
+  assert!(registry.decode(Some(Path::new("a.gif")), gif).is_err());
+  assert!(registry.encode(Path::new("a"), &img).is_err());
+  // 浮点图片经过注册表编码时保留超出 [0,1] 的值
+  let float = crate::pixel::ImageF32::from_data(vec![[0., 0., 0., 1.], [4., 0.5, 0.25, 1.]], 2, 1);
+  let hdr = registry.encode(Path::new("a.hdr"), &float).unwrap();
+  assert_eq!(HDR::new().decode_float(&hdr).unwrap().data, float.data);
+  let pfm = registry.encode(Path::new("a.pfm"), &float).unwrap();
+  assert_eq!(PFM.decode_float(&pfm).unwrap().data, float.data);
+  assert!(registry
+    .encode(Path::new("a.gif"), &PixImage::new(0, 1))
+    .is_err());
+
+  // 后注册的格式优先
+  struct Blank;
+  impl Decoder for Blank {
+    fn decode(&self, data: Vec<u8>) -> Result<PixImage> {
+      Ok(PixImage::new(data.len() as u32, 1))
+    }
+  }
+  let mut registry = Registry::builtin();
+  registry.register(
+    ImageFormat::new("Blank")
+      .extensions(&["tga"])
+      .magic(|d| d.starts_with(b"BLANK"))
+      .decoder(Blank),
+  );
+  assert_eq!(registry.decode(None, b"BLANK!".to_vec()).unwrap().width, 6);
+  assert_eq!(
+    registry
+      .detect(Some(Path::new("b.tga")), &[0; 18])
+      .unwrap()
+      .name,
+    "Blank"
+  );
+}
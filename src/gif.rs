@@ -0,0 +1,541 @@
+// GIF89a 编码器：中位切分(median cut)把颜色量化为最多 256 色，可选 Floyd–Steinberg 抖动，
+// 用 LZW 压缩。GifWriter 可以写多帧动画，也可以作为 record::FrameSink 录制 sdl::frame。
+// GIF 不支持半透明，alpha 通道被忽略。
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{BufWriter, Write},
+  path::Path,
+  time::Duration,
+};
+
+use crate::{
+  image::{Image, PixImage},
+  image_encoder::Encoder,
+  record::FrameSink,
+};
+
+const MAX_COLORS: usize = 256;
+// LZW 码的最大位数
+const MAX_CODE_BITS: u8 = 12;
+// 与 giflib 一样不使用码 4095，码表满时发出清除码
+const MAX_CODE: u16 = (1 << MAX_CODE_BITS) - 1;
+
+// 单帧 GIF
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GIF {
+  pub dither: bool,
+}
+impl GIF {
+  pub fn new() -> GIF {
+    GIF::default()
+  }
+  pub fn dither(mut self, dither: bool) -> GIF {
+    self.dither = dither;
+    self
+  }
+}
+impl Encoder for GIF {
+  // GIF 的宽高最大为 65535，更大的图片只保留左上角；空图片没有可以编码的数据，返回空数组
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    let max = u16::MAX as u32;
+    let (w, h) = (img.width().min(max), img.height().min(max));
+    if w == 0 || h == 0 {
+      return vec![];
+    }
+    let mut writer = GifWriter::new(vec![], w, h)
+      .dither(self.dither)
+      .repeat(Repeat::Once);
+    // 大小已经在范围内，写入 Vec 不会失败
+    match writer
+      .add_frame(img, Duration::ZERO)
+      .and_then(|()| writer.finish())
+    {
+      Ok(()) => writer.into_inner(),
+      Err(_) => vec![],
+    }
+  }
+}
+
+// 动画的循环次数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+  // 只播放一次
+  Once,
+  // 播放 1 + n 次
+  Times(u16),
+  Infinite,
+}
+
+// 多帧 GIF，所有帧使用各自的局部调色板
+pub struct GifWriter<W: Write> {
+  out: W,
+  width: u32,
+  height: u32,
+  dither: bool,
+  repeat: Repeat,
+  // 作为 FrameSink 使用时每帧的延迟
+  delay: Duration,
+  started: bool,
+  finished: bool,
+}
+impl GifWriter<BufWriter<File>> {
+  pub fn create<P: AsRef<Path>>(
+    path: P,
+    width: u32,
+    height: u32,
+  ) -> std::io::Result<GifWriter<BufWriter<File>>> {
+    Ok(GifWriter::new(
+      BufWriter::new(File::create(path)?),
+      width,
+      height,
+    ))
+  }
+}
+impl<W: Write> GifWriter<W> {
+  // width, height 为画布大小
+  pub fn new(out: W, width: u32, height: u32) -> GifWriter<W> {
+    GifWriter {
+      out,
+      width,
+      height,
+      dither: false,
+      repeat: Repeat::Infinite,
+      delay: Duration::from_millis(40),
+      started: false,
+      finished: false,
+    }
+  }
+  pub fn dither(mut self, dither: bool) -> GifWriter<W> {
+    self.dither = dither;
+    self
+  }
+  pub fn repeat(mut self, repeat: Repeat) -> GifWriter<W> {
+    self.repeat = repeat;
+    self
+  }
+  pub fn delay(mut self, delay: Duration) -> GifWriter<W> {
+    self.delay = delay;
+    self
+  }
+  fn write_header(&mut self) -> std::io::Result<()> {
+    let (w, h) = (self.width, self.height);
+    if w == 0 || h == 0 || w > u16::MAX as u32 || h > u16::MAX as u32 {
+      return Err(invalid_input("GIF canvas size must be in 1..=65535"));
+    }
+    self.out.write_all(b"GIF89a")?;
+    // 逻辑屏幕描述符，没有全局调色板
+    self.out.write_all(&(w as u16).to_le_bytes())?;
+    self.out.write_all(&(h as u16).to_le_bytes())?;
+    self.out.write_all(&[0, 0, 0])?;
+    let times = match self.repeat {
+      Repeat::Once => return Ok(()),
+      Repeat::Times(n) => n,
+      Repeat::Infinite => 0,
+    };
+    // NETSCAPE2.0 应用扩展，指定循环次数
+    self.out.write_all(&[0x21, 0xff, 11])?;
+    self.out.write_all(b"NETSCAPE2.0")?;
+    self.out.write_all(&[3, 1])?;
+    self.out.write_all(&times.to_le_bytes())?;
+    self.out.write_all(&[0])
+  }
+  // delay 以 1/100 秒为单位保存，很多查看器把小于 0.02 秒的延迟当作 0.1 秒
+  pub fn add_frame<I: Image + ?Sized>(&mut self, img: &I, delay: Duration) -> std::io::Result<()> {
+    if self.finished {
+      return Err(invalid_input("GIF has been finished"));
+    }
+    // 超出画布的部分被裁掉，窗口改变大小时仍然可以继续录制
+    let (w, h) = (img.width().min(self.width), img.height().min(self.height));
+    if w == 0 || h == 0 {
+      return Err(invalid_input("GIF frame is empty"));
+    }
+    if !self.started {
+      self.write_header()?;
+      self.started = true;
+    }
+    // 从最上面一行开始
+    let top = img.height() - 1;
+    let pixels: Vec<[u8; 3]> = (0..h)
+      .flat_map(|row| (0..w).map(move |col| (col, top - row)))
+      .map(|(col, row)| {
+        let c = img.get(col, row);
+        [c.x, c.y, c.z]
+      })
+      .collect();
+    let palette = median_cut(&histogram(&pixels), MAX_COLORS);
+    let indices = if self.dither {
+      dither(&pixels, w as usize, &palette)
+    } else {
+      let mut nearest = Nearest::new(&palette);
+      pixels.iter().map(|&c| nearest.get(c)).collect()
+    };
+    // 调色板的大小必须是 2 的幂
+    let bits = (palette.len().max(2) as u32)
+      .next_power_of_two()
+      .trailing_zeros() as u8;
+
+    // 图形控制扩展：延迟，处置方式为保留
+    let delay = (delay.as_secs_f64() * 100.).round().min(u16::MAX as f64) as u16;
+    self.out.write_all(&[0x21, 0xf9, 4, 1 << 2])?;
+    self.out.write_all(&delay.to_le_bytes())?;
+    self.out.write_all(&[0, 0])?;
+    // 图像描述符，带局部调色板
+    self.out.write_all(&[0x2c, 0, 0, 0, 0])?;
+    self.out.write_all(&(w as u16).to_le_bytes())?;
+    self.out.write_all(&(h as u16).to_le_bytes())?;
+    self.out.write_all(&[0x80 | (bits - 1)])?;
+    for i in 0..1 << bits {
+      self.out.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+    }
+    let min_code_size = bits.max(2);
+    self.out.write_all(&[min_code_size])?;
+    for block in lzw_encode(&indices, min_code_size).chunks(255) {
+      self.out.write_all(&[block.len() as u8])?;
+      self.out.write_all(block)?;
+    }
+    self.out.write_all(&[0])
+  }
+  // 写入结尾，可以多次调用
+  pub fn finish(&mut self) -> std::io::Result<()> {
+    if self.finished {
+      return Ok(());
+    }
+    if !self.started {
+      return Err(invalid_input("GIF has no frames"));
+    }
+    self.finished = true;
+    self.out.write_all(&[0x3b])?;
+    self.out.flush()
+  }
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+}
+impl<W: Write> FrameSink for GifWriter<W> {
+  fn write_frame(&mut self, _index: u64, img: &PixImage) -> std::io::Result<()> {
+    self.add_frame(img, self.delay)
+  }
+  fn finish(&mut self) -> std::io::Result<()> {
+    GifWriter::finish(self)
+  }
+}
+
+fn invalid_input(msg: &str) -> std::io::Error {
+  std::io::Error::new(std::io::ErrorKind::InvalidInput, msg)
+}
+
+fn histogram(pixels: &[[u8; 3]]) -> Vec<([u8; 3], u32)> {
+  let mut counts = HashMap::new();
+  for &c in pixels {
+    *counts.entry(c).or_insert(0) += 1;
+  }
+  let mut colors: Vec<_> = counts.into_iter().collect();
+  // 使结果与 HashMap 的遍历顺序无关
+  colors.sort_unstable();
+  colors
+}
+
+// 中位切分：反复把颜色范围最大的盒子沿最长的轴在加权中位数处分成两半，
+// 每个盒子中颜色的加权平均值就是调色板中的一种颜色
+fn median_cut(colors: &[([u8; 3], u32)], max_colors: usize) -> Vec<[u8; 3]> {
+  if colors.len() <= max_colors {
+    return colors.iter().map(|&(c, _)| c).collect();
+  }
+  let mut colors = colors.to_vec();
+  // 盒子为 colors 中的区间，以及最长的轴和颜色在这个轴上的范围
+  let mut boxes = vec![(0, colors.len(), longest_axis(&colors))];
+  while boxes.len() < max_colors {
+    let Some(i) = (0..boxes.len())
+      .filter(|&i| boxes[i].1 - boxes[i].0 > 1)
+      .max_by_key(|&i| {
+        let (_, _, (_, range)) = boxes[i];
+        (range, std::cmp::Reverse(i))
+      })
+    else {
+      break;
+    };
+    let (start, end, (axis, _)) = boxes[i];
+    let slice = &mut colors[start..end];
+    slice.sort_unstable_by_key(|&(c, _)| c[axis]);
+    let total: u64 = slice.iter().map(|&(_, n)| n as u64).sum();
+    let mut sum = 0;
+    let mut mid = slice.len() - 1;
+    for (j, &(_, n)) in slice.iter().enumerate() {
+      sum += n as u64;
+      if sum * 2 >= total {
+        mid = j + 1;
+        break;
+      }
+    }
+    let mid = start + mid.clamp(1, slice.len() - 1);
+    boxes[i] = (start, mid, longest_axis(&colors[start..mid]));
+    boxes.push((mid, end, longest_axis(&colors[mid..end])));
+  }
+  boxes
+    .iter()
+    .map(|&(start, end, _)| {
+      let mut sum = [0u64; 3];
+      let mut total = 0u64;
+      for &(c, n) in &colors[start..end] {
+        for k in 0..3 {
+          sum[k] += c[k] as u64 * n as u64;
+        }
+        total += n as u64;
+      }
+      sum.map(|s| ((s + total / 2) / total) as u8)
+    })
+    .collect()
+}
+
+fn longest_axis(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+  (0..3)
+    .map(|axis| {
+      let min = colors.iter().map(|(c, _)| c[axis]).min().unwrap();
+      let max = colors.iter().map(|(c, _)| c[axis]).max().unwrap();
+      (axis, max - min)
+    })
+    .max_by_key(|&(axis, range)| (range, std::cmp::Reverse(axis)))
+    .unwrap()
+}
+
+// 查找调色板中最接近的颜色，结果按颜色缓存
+struct Nearest<'a> {
+  palette: &'a [[u8; 3]],
+  cache: HashMap<[u8; 3], u8>,
+}
+impl<'a> Nearest<'a> {
+  fn new(palette: &'a [[u8; 3]]) -> Nearest<'a> {
+    Nearest {
+      palette,
+      cache: HashMap::new(),
+    }
+  }
+  fn get(&mut self, c: [u8; 3]) -> u8 {
+    let palette = self.palette;
+    *self.cache.entry(c).or_insert_with(|| {
+      let dist = |p: &[u8; 3]| {
+        (0..3)
+          .map(|k| (p[k] as i32 - c[k] as i32).pow(2))
+          .sum::<i32>()
+      };
+      (0..palette.len())
+        .min_by_key(|&i| dist(&palette[i]))
+        .unwrap_or(0) as u8
+    })
+  }
+}
+
+// Floyd–Steinberg 抖动：把每个像素的量化误差按 7/16、3/16、5/16、1/16 扩散到右边和下一行
+fn dither(pixels: &[[u8; 3]], width: usize, palette: &[[u8; 3]]) -> Vec<u8> {
+  let mut nearest = Nearest::new(palette);
+  let mut indices = Vec::with_capacity(pixels.len());
+  // 当前行和下一行累积的误差，两边各多一个像素
+  let mut err = vec![[0f32; 3]; width + 2];
+  let mut next_err = vec![[0f32; 3]; width + 2];
+  for row in pixels.chunks(width) {
+    for (x, &c) in row.iter().enumerate() {
+      let want: [f32; 3] = std::array::from_fn(|k| c[k] as f32 + err[x + 1][k]);
+      let idx = nearest.get(want.map(|v| v.round().clamp(0., 255.) as u8));
+      let got = palette[idx as usize];
+      for k in 0..3 {
+        let e = want[k] - got[k] as f32;
+        err[x + 2][k] += e * 7. / 16.;
+        next_err[x][k] += e * 3. / 16.;
+        next_err[x + 1][k] += e * 5. / 16.;
+        next_err[x + 2][k] += e * 1. / 16.;
+      }
+      indices.push(idx);
+    }
+    std::mem::swap(&mut err, &mut next_err);
+    next_err.fill([0.; 3]);
+  }
+  indices
+}
+
+// 按 GIF 的约定从低位开始把码写入字节流
+struct BitWriter {
+  bytes: Vec<u8>,
+  acc: u32,
+  n: u8,
+}
+impl BitWriter {
+  fn write(&mut self, code: u16, bits: u8) {
+    self.acc |= (code as u32) << self.n;
+    self.n += bits;
+    while self.n >= 8 {
+      self.bytes.push(self.acc as u8);
+      self.acc >>= 8;
+      self.n -= 8;
+    }
+  }
+  fn finish(mut self) -> Vec<u8> {
+    if self.n > 0 {
+      self.bytes.push(self.acc as u8);
+    }
+    self.bytes
+  }
+}
+
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+  let clear = 1u16 << min_code_size;
+  let end = clear + 1;
+  let mut out = BitWriter {
+    bytes: vec![],
+    acc: 0,
+    n: 0,
+  };
+  // (前缀的码 << 8 | 下一个颜色) -> 码
+  let mut table: HashMap<u32, u16> = HashMap::new();
+  let mut bits = min_code_size + 1;
+  let mut next_code = end + 1;
+  out.write(clear, bits);
+  let Some((&first, rest)) = indices.split_first() else {
+    out.write(end, bits);
+    return out.finish();
+  };
+  let mut prefix = first as u16;
+  for &k in rest {
+    let key = (prefix as u32) << 8 | k as u32;
+    if let Some(&code) = table.get(&key) {
+      prefix = code;
+      continue;
+    }
+    out.write(prefix, bits);
+    if next_code < MAX_CODE {
+      table.insert(key, next_code);
+      next_code += 1;
+      // 解码器读到下一个码之前才会加入这一项，此时它可能需要更多的位
+      if next_code > 1 << bits && bits < MAX_CODE_BITS {
+        bits += 1;
+      }
+    } else {
+      out.write(clear, bits);
+      table.clear();
+      bits = min_code_size + 1;
+      next_code = end + 1;
+    }
+    prefix = k as u16;
+  }
+  out.write(prefix, bits);
+  // 解码器读到最后一个码后同样会加入一项
+  if next_code + 1 > 1 << bits && bits < MAX_CODE_BITS {
+    bits += 1;
+  }
+  out.write(end, bits);
+  out.finish()
+}
+
+#[test]
+fn test_gif_encode() {
+  use crate::geometry::Vec3;
+  // 解码 LZW 数据，返回颜色的序号
+  fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let (mut pos, mut bits) = (0, min_code_size + 1);
+    let mut table: Vec<Vec<u8>> = vec![];
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = vec![];
+    loop {
+      let code = (0..bits as usize).fold(0, |code, i| {
+        let bit = (data[(pos + i) / 8] >> ((pos + i) % 8)) & 1;
+        code | (bit as usize) << i
+      });
+      pos += bits as usize;
+      if code == clear {
+        table = (0..clear)
+          .map(|i| vec![i as u8])
+          .chain([vec![], vec![]])
+          .collect();
+        bits = min_code_size + 1;
+        prev = None;
+        continue;
+      }
+      if code == clear + 1 {
+        return out;
+      }
+      let entry = match (&prev, table.get(code)) {
+        (_, Some(entry)) => entry.clone(),
+        (Some(prev), None) => [&prev[..], &prev[..1]].concat(),
+        (None, None) => panic!("invalid code {}", code),
+      };
+      if let Some(prev) = prev {
+        if table.len() < 4096 {
+          table.push([&prev[..], &entry[..1]].concat());
+        }
+      }
+      if table.len() == 1 << bits && bits < 12 {
+        bits += 1;
+      }
+      out.extend_from_slice(&entry);
+      prev = Some(entry);
+    }
+  }
+
+  // 长而重复的序列使码表多次填满并清除
+  let indices: Vec<u8> = (0..40000u32)
+    .map(|i| ((i * 7 + i / 13) % 5 + (i / 1000) % 3) as u8)
+    .collect();
+  assert_eq!(lzw_decode(&lzw_encode(&indices, 3), 3), indices);
+  let noise: Vec<u8> = (0..20000u32)
+    .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+    .collect();
+  assert_eq!(lzw_decode(&lzw_encode(&noise, 8), 8), noise);
+  assert_eq!(lzw_decode(&lzw_encode(&[], 2), 2), vec![]);
+
+  // 颜色少于 256 种时量化是无损的
+  let mut img = PixImage::new(16, 8);
+  for y in 0..8 {
+    for x in 0..16 {
+      img.set_rgb24(x, y, Vec3::new(x as u8 * 16, y as u8 * 32, 128));
+    }
+  }
+  assert!(GIF::new().encode(&PixImage::new(0, 8)).is_empty());
+  let data = GIF::new().encode(&img);
+  assert_eq!(&data[..6], b"GIF89a");
+  assert_eq!(data.last(), Some(&0x3b));
+  // 头部 13 字节，图形控制扩展 8 字节，图像描述符 10 字节，128 色调色板
+  let palette = &data[31..31 + 128 * 3];
+  let min_code_size = data[31 + 128 * 3];
+  let mut lzw = vec![];
+  let mut pos = 31 + 128 * 3 + 1;
+  while data[pos] != 0 {
+    lzw.extend_from_slice(&data[pos + 1..pos + 1 + data[pos] as usize]);
+    pos += 1 + data[pos] as usize;
+  }
+  let indices = lzw_decode(&lzw, min_code_size);
+  assert_eq!(indices.len(), 16 * 8);
+  for (i, &idx) in indices.iter().enumerate() {
+    let (x, y) = (i as u32 % 16, 7 - i as u32 / 16);
+    let c = &palette[idx as usize * 3..idx as usize * 3 + 3];
+    let expected = img.get(x, y);
+    assert_eq!(c, [expected.x, expected.y, expected.z]);
+  }
+
+  // 渐变超过 256 色，抖动后的平均颜色仍接近原图
+  let mut gradient = PixImage::new(64, 64);
+  for y in 0..64 {
+    for x in 0..64 {
+      gradient.set_rgb24(x, y, Vec3::new(x as u8 * 4, y as u8 * 4, (x + y) as u8 * 2));
+    }
+  }
+  let pixels: Vec<[u8; 3]> = (0..64 * 64)
+    .map(|i| {
+      let c = gradient.get(i % 64, i / 64);
+      [c.x, c.y, c.z]
+    })
+    .collect();
+  let palette = median_cut(&histogram(&pixels), 16);
+  assert_eq!(palette.len(), 16);
+  let indices = dither(&pixels, 64, &palette);
+  let mean = |colors: &mut dyn Iterator<Item = [u8; 3]>| {
+    colors.fold([0f32; 3], |s, c| {
+      std::array::from_fn(|k| s[k] + c[k] as f32 / 4096.)
+    })
+  };
+  let want = mean(&mut pixels.iter().copied());
+  let got = mean(&mut indices.iter().map(|&i| palette[i as usize]));
+  for k in 0..3 {
+    assert!((want[k] - got[k]).abs() < 2., "{:?} {:?}", want, got);
+  }
+}
@@ -0,0 +1,359 @@
+// Radiance HDR(RGBE) 编解码，用于环境贴图等高动态范围图片。
+// 每个像素是共享指数的 RGB：(r, g, b) * 2^(e-136)。
+// 解码：支持新式 RLE(每个分量分别游程编码)、旧式 RLE(1,1,1,n 重复前一个像素)和不压缩的扫描线，
+// 以及文件头中所有的 8 种扫描方向。忽略 EXPOSURE，不支持 XYZE。
+use crate::{
+  error::{Error, Result},
+  image::{Image, PixImage},
+  image_decoder::Decoder,
+  image_encoder::Encoder,
+  pixel::{ImageF32, Pixel, TypedImage},
+};
+
+// 新式 RLE 只能用于这个范围内的扫描线长度
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+// 比这短的重复按字面值写更省空间
+const MIN_RUN: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HDR {
+  pub rle: bool,
+}
+impl HDR {
+  pub fn new() -> HDR {
+    HDR::default()
+  }
+  // 是否使用新式 RLE 压缩，默认使用
+  pub fn rle(mut self, rle: bool) -> HDR {
+    self.rle = rle;
+    self
+  }
+}
+impl Default for HDR {
+  fn default() -> HDR {
+    HDR { rle: true }
+  }
+}
+
+fn to_rgbe(rgb: [f32; 3]) -> [u8; 4] {
+  let v = rgb[0].max(rgb[1]).max(rgb[2]);
+  if v.is_nan() || v <= 1e-32 {
+    return [0; 4];
+  }
+  if v >= 2f32.powi(127) {
+    return [255; 4];
+  }
+  // v = m * 2^e，m ∈ [0.5, 1)
+  let e = ((v.to_bits() >> 23) & 0xff) as i32 - 126;
+  let scale = 256. / 2f32.powi(e);
+  let [r, g, b] = rgb.map(|c| (c.max(0.) * scale) as u8);
+  [r, g, b, (e + 128) as u8]
+}
+
+fn from_rgbe([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+  if e == 0 {
+    return [0.; 3];
+  }
+  let f = 2f32.powi(e as i32 - 136);
+  [r as f32 * f, g as f32 * f, b as f32 * f]
+}
+
+fn rle_component(out: &mut Vec<u8>, data: &[u8]) {
+  let run_len = |i: usize| {
+    data[i..]
+      .iter()
+      .take(127)
+      .take_while(|&&v| v == data[i])
+      .count()
+  };
+  let mut i = 0;
+  while i < data.len() {
+    let run = run_len(i);
+    if run >= MIN_RUN {
+      out.extend([128 + run as u8, data[i]]);
+      i += run;
+      continue;
+    }
+    let start = i;
+    while i < data.len() && i - start < 128 && run_len(i) < MIN_RUN {
+      i += 1;
+    }
+    out.push((i - start) as u8);
+    out.extend(&data[start..i]);
+  }
+}
+
+impl HDR {
+  // 忽略透明度，单通道的图片写为灰色
+  pub fn encode_float<P: Pixel>(&self, img: &TypedImage<P>) -> Vec<u8> {
+    let (w, h) = (img.width, img.height);
+    let mut out = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", h, w).into_bytes();
+    let rle = self.rle && (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&(w as usize));
+    let mut scanline = Vec::with_capacity(w as usize * 4);
+    // 从最上面一行开始
+    for y in (0..h).rev() {
+      scanline.clear();
+      for x in 0..w {
+        let c = img.get_rgbf(x, y);
+        scanline.extend(to_rgbe([c.x, c.y, c.z]));
+      }
+      if rle {
+        out.extend([2, 2, (w >> 8) as u8, w as u8]);
+        for c in 0..4 {
+          let component: Vec<u8> = scanline.iter().skip(c).step_by(4).copied().collect();
+          rle_component(&mut out, &component);
+        }
+      } else {
+        out.extend(&scanline);
+      }
+    }
+    out
+  }
+  pub fn decode_float(&self, data: &[u8]) -> Result<ImageF32> {
+    decode_hdr(data)
+  }
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+impl<'a> Reader<'a> {
+  fn line(&mut self) -> Result<&'a str> {
+    let rest = &self.data[self.pos.min(self.data.len())..];
+    let len = rest
+      .iter()
+      .position(|&c| c == b'\n')
+      .ok_or_else(|| Error::format("unexpected end of header"))?;
+    self.pos += len + 1;
+    std::str::from_utf8(&rest[..len]).map_err(|_| Error::format("header is not valid text"))
+  }
+  fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+    let bytes = self
+      .data
+      .get(self.pos..self.pos + n)
+      .ok_or_else(|| Error::format("image data is too short"))?;
+    self.pos += n;
+    Ok(bytes)
+  }
+  fn byte(&mut self) -> Result<u8> {
+    Ok(self.bytes(1)?[0])
+  }
+}
+
+// 读取一条扫描线的 RGBE 数据到 out
+fn read_scanline(reader: &mut Reader, out: &mut [[u8; 4]]) -> Result<()> {
+  let width = out.len();
+  let start = reader.pos;
+  if (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width) {
+    let head = reader.bytes(4)?;
+    if head[..2] == [2, 2] && head[2] & 0x80 == 0 {
+      let len = (head[2] as usize) << 8 | head[3] as usize;
+      if len != width {
+        return Err(Error::format(format!(
+          "scanline length {} does not match width {}",
+          len, width
+        )));
+      }
+      // 新式 RLE：4 个分量依次编码，每段是一个游程(>128)或一串字面值
+      for c in 0..4 {
+        let mut x = 0;
+        while x < width {
+          let count = reader.byte()? as usize;
+          let (n, run) = if count > 128 {
+            (count - 128, true)
+          } else {
+            (count, false)
+          };
+          if n == 0 || x + n > width {
+            return Err(Error::format("invalid RLE run length"));
+          }
+          if run {
+            let v = reader.byte()?;
+            out[x..x + n].iter_mut().for_each(|p| p[c] = v);
+          } else {
+            let values = reader.bytes(n)?;
+            out[x..x + n]
+              .iter_mut()
+              .zip(values)
+              .for_each(|(p, &v)| p[c] = v);
+          }
+          x += n;
+        }
+      }
+      return Ok(());
+    }
+    reader.pos = start;
+  }
+  // 旧式 RLE 或不压缩：(1,1,1,n) 表示重复前一个像素 n 次，连续的重复标记依次为更高的字节
+  let mut x = 0;
+  let mut shift = 0;
+  while x < width {
+    let p: [u8; 4] = reader.bytes(4)?.try_into().unwrap();
+    if p[..3] == [1, 1, 1] {
+      if x == 0 || shift > 16 {
+        return Err(Error::format("invalid RLE repeat"));
+      }
+      let n = (p[3] as usize) << shift;
+      if x + n > width {
+        return Err(Error::format("RLE repeat overruns scanline"));
+      }
+      let prev = out[x - 1];
+      out[x..x + n].fill(prev);
+      x += n;
+      shift += 8;
+    } else {
+      out[x] = p;
+      x += 1;
+      shift = 0;
+    }
+  }
+  Ok(())
+}
+
+fn decode_hdr(data: &[u8]) -> Result<ImageF32> {
+  let mut reader = Reader { data, pos: 0 };
+  if !reader.line()?.starts_with("#?") {
+    return Err(Error::format("missing #? signature"));
+  }
+  // 文件头以空行结束
+  loop {
+    let line = reader.line()?;
+    if line.is_empty() {
+      break;
+    }
+    if let Some(format) = line.strip_prefix("FORMAT=") {
+      if format.trim() != "32-bit_rle_rgbe" {
+        return Err(Error::unsupported(format!(
+          "pixel format {}",
+          format.trim()
+        )));
+      }
+    }
+  }
+  // 分辨率：先是扫描线的方向和数量，再是扫描线内的方向和长度，如 "-Y 512 +X 768"
+  let resolution = reader.line()?;
+  let parts: Vec<&str> = resolution.split_whitespace().collect();
+  let invalid = || Error::format(format!("invalid resolution {:?}", resolution));
+  let [major, major_len, minor, minor_len] = parts[..] else {
+    return Err(invalid());
+  };
+  let major_len: u32 = major_len.parse().map_err(|_| invalid())?;
+  let minor_len: u32 = minor_len.parse().map_err(|_| invalid())?;
+  let axis = |s: &str| match s {
+    "+X" | "-X" | "+Y" | "-Y" => Some((s.as_bytes()[1], s.starts_with('-'))),
+    _ => None,
+  };
+  let ((major_axis, major_neg), (minor_axis, minor_neg)) =
+    axis(major).zip(axis(minor)).ok_or_else(invalid)?;
+  if major_axis == minor_axis {
+    return Err(invalid());
+  }
+  let (width, height) = if major_axis == b'Y' {
+    (minor_len, major_len)
+  } else {
+    (major_len, minor_len)
+  };
+  // 图片的 y 轴向上，-Y 表示从最上面一行开始
+  let coord = |i: u32, axis: u8, neg: bool| match (axis, neg) {
+    (b'X', false) | (b'Y', false) => i,
+    (b'X', true) => width - 1 - i,
+    _ => height - 1 - i,
+  };
+
+  // 宽高来自文件，先确认剩下的数据足够再分配。每条扫描线至少有一个像素的字面值，
+  // 旧式 RLE 的一个重复标记最多表示 255 << 16 个像素
+  let too_large = || Error::format("image is too large");
+  let repeats = (minor_len as usize).saturating_sub(1).div_ceil(255 << 16);
+  let size = (major_len as usize)
+    .checked_mul(4 * (1 + repeats))
+    .ok_or_else(too_large)?;
+  if data.len().saturating_sub(reader.pos) < size {
+    return Err(Error::format("image data is too short"));
+  }
+  (width as usize)
+    .checked_mul(height as usize)
+    .and_then(|n| n.checked_mul(4))
+    .ok_or_else(too_large)?;
+  let mut img = ImageF32::new(width, height);
+  let mut scanline = vec![[0u8; 4]; minor_len as usize];
+  for i in 0..major_len {
+    read_scanline(&mut reader, &mut scanline)?;
+    for (j, &rgbe) in scanline.iter().enumerate() {
+      let (a, b) = (
+        coord(i, major_axis, major_neg),
+        coord(j as u32, minor_axis, minor_neg),
+      );
+      let (x, y) = if major_axis == b'Y' { (b, a) } else { (a, b) };
+      let [r, g, b] = from_rgbe(rgbe);
+      img.set_pixel(x, y, [r, g, b, 1.]);
+    }
+  }
+  Ok(img)
+}
+
+impl Encoder for HDR {
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    self.encode_float(&ImageF32::from_image(img))
+  }
+}
+
+// 超出 [0,1] 的值被截断
+impl Decoder for HDR {
+  fn decode(&self, data: Vec<u8>) -> Result<PixImage> {
+    Ok(decode_hdr(&data)?.to_pix_image())
+  }
+}
+
+#[test]
+fn test_hdr() {
+  // 新式 RLE 和不压缩的往返，这些值都可以用 8 位尾数精确表示
+  let mut img = ImageF32::new(9, 2);
+  for y in 0..2 {
+    for x in 0..9 {
+      let v = if x < 5 { 1. } else { (x * 8 + y * 64) as f32 };
+      img.set_pixel(x, y, [v, v * 0.5, 0., 1.]);
+    }
+  }
+  for hdr in [HDR::new(), HDR::new().rle(false)] {
+    let data = hdr.encode_float(&img);
+    assert!(data.starts_with(b"#?RADIANCE\n"));
+    let decoded = HDR::new().decode_float(&data).unwrap();
+    assert_eq!((decoded.width, decoded.height), (9, 2));
+    assert_eq!(decoded.data, img.data);
+  }
+  // 第一条扫描线是最上面一行，每条扫描线的 R、G、E 分量都是一个游程加 4 个字面值
+  let data = HDR::new().encode_float(&img);
+  assert_eq!(data.len(), 45 + 2 * (4 + 3 * 7 + 2));
+  assert_eq!(data[45..54], [2, 2, 0, 9, 128 + 5, 128, 4, 208, 224]);
+
+  // 旧式 RLE，+Y 表示第一条扫描线是最下面一行
+  let mut data = b"#?RGBE\n# comment\n\n+Y 2 +X 3\n".to_vec();
+  data.extend([128, 64, 0, 129, 1, 1, 1, 2]);
+  data.extend([0, 0, 0, 0, 1, 1, 1, 1, 255, 255, 255, 128]);
+  let img = HDR::new().decode_float(&data).unwrap();
+  assert_eq!(img.pixel(2, 0), [1., 0.5, 0., 1.]);
+  assert_eq!(img.pixel(1, 1), [0., 0., 0., 1.]);
+  assert!((img.pixel(2, 1)[0] - 0.99609375).abs() < 1e-6);
+  // 列优先的扫描方向
+  let mut data = b"#?RADIANCE\n\n+X 2 -Y 1\n".to_vec();
+  data.extend([0, 0, 0, 0, 128, 0, 0, 129]);
+  assert_eq!(
+    HDR::new().decode_float(&data).unwrap().pixel(1, 0),
+    [1., 0., 0., 1.]
+  );
+
+  assert!(HDR::new()
+    .decode_float(b"#?RADIANCE\n\n-Y 1 +X 2\n\x01\x01\x01\x01")
+    .is_err());
+  // 分辨率与数据不符时返回错误，不按分辨率分配内存
+  assert!(HDR::new()
+    .decode_float(b"#?RADIANCE\n\n-Y 4000000000 +X 4000000000\n\x01\x01\x01\x01")
+    .is_err());
+  let xyze = b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0";
+  assert!(matches!(
+    HDR::new().decode_float(xyze).unwrap_err().kind,
+    crate::error::ErrorKind::Unsupported(_)
+  ));
+}
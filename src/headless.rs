@@ -0,0 +1,179 @@
+// 不打开窗口的 sdl::frame/sdl::one_frame 实现，可以在 CI 或服务器上运行动画示例。
+// 关闭 sdl feature 时 crate::sdl 就是这个模块。
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+  gif::GifWriter,
+  image::{ImageOriginPos, PixImage},
+  input::FrameContext,
+  record::{FrameSink, ImageSequence, Recorder},
+};
+
+// 渲染结果的去向
+pub enum Output {
+  // 保存在内存中，由 Headless::run 返回
+  Memory,
+  // 保存为编号的图片文件：<dir>/<name>-0000.ppm
+  Files(PathBuf),
+}
+
+pub struct Headless {
+  name: String,
+  width: u32,
+  height: u32,
+  frames: u32,
+  // 决定传给回调的帧间隔，固定的帧率使动画的每一帧都可以重现
+  fps: f32,
+  output: Output,
+  recorder: Option<Recorder>,
+}
+impl Headless {
+  pub fn new(name: &str, width: u32, height: u32) -> Headless {
+    Headless {
+      name: name.to_string(),
+      width,
+      height,
+      frames: 1,
+      fps: 60.,
+      output: Output::Memory,
+      recorder: None,
+    }
+  }
+  pub fn frames(mut self, frames: u32) -> Headless {
+    self.frames = frames;
+    self
+  }
+  // 忽略不是正数的帧率
+  pub fn fps(mut self, fps: f32) -> Headless {
+    if fps.is_finite() && fps > 0. {
+      self.fps = fps;
+    }
+    self
+  }
+  pub fn output(mut self, output: Output) -> Headless {
+    self.output = output;
+    self
+  }
+  // 除了 output 之外，再把帧交给 recorder，例如编码为动画
+  pub fn record(mut self, recorder: Recorder) -> Headless {
+    self.recorder = Some(recorder);
+    self
+  }
+  // 用环境变量 YATSR_FRAMES、YATSR_FPS、YATSR_OUTPUT_DIR 覆盖设置，
+  // 设置 YATSR_RECORD_GIF 时另外把所有帧保存为 GIF 动画，不能创建 GIF 文件时返回错误
+  pub fn from_env(mut self) -> std::io::Result<Headless> {
+    self.apply_env()?;
+    Ok(self)
+  }
+  // 返回错误时除了录制之外的设置都已经生效
+  fn apply_env(&mut self) -> std::io::Result<()> {
+    if let Some(frames) = std::env::var("YATSR_FRAMES").ok().and_then(|v| v.parse().ok()) {
+      self.frames = frames;
+    }
+    if let Some(fps) = std::env::var("YATSR_FPS").ok().and_then(|v| v.parse::<f32>().ok()) {
+      if fps.is_finite() && fps > 0. {
+        self.fps = fps;
+      } else {
+        eprintln!("Ignoring YATSR_FPS={}", fps);
+      }
+    }
+    if let Ok(dir) = std::env::var("YATSR_OUTPUT_DIR") {
+      self.output = Output::Files(PathBuf::from(dir));
+    }
+    if let Ok(path) = std::env::var("YATSR_RECORD_GIF") {
+      let gif = GifWriter::create(path, self.width, self.height)?
+        .delay(Duration::from_secs_f32(1. / self.fps));
+      self.recorder = Some(Recorder::new(gif));
+    }
+    Ok(())
+  }
+  // 输出到文件时返回空数组
+  // 回调收到的 FrameContext 没有输入事件，第一帧之后 delta 为 1/fps
+  pub fn run<F: FnMut((&mut [u8], u32, u32), &FrameContext)>(
+    &mut self,
+    mut draw: F,
+  ) -> std::io::Result<Vec<PixImage>> {
+    let (w, h) = (self.width, self.height);
+    let mut data = vec![0; (w * h * 4) as usize];
+    let mut frames = vec![];
+    let mut ctx = FrameContext::new(w, h);
+    for n in 0..self.frames {
+      ctx.begin(if n == 0 {
+        Duration::ZERO
+      } else {
+        Duration::from_secs_f32(1. / self.fps)
+      });
+      data.fill(0);
+      draw((&mut data, w, h), &ctx);
+      if let Some(recorder) = &mut self.recorder {
+        recorder.capture(&ctx, &data)?;
+      }
+      // 与 (&mut [u8], u32, u32) 一样，数据的第一行是图片的最上面一行
+      let img = PixImage::from_data(data.clone(), w, h, ImageOriginPos::LeftBottom);
+      match &self.output {
+        Output::Memory => frames.push(img),
+        Output::Files(dir) => ImageSequence::new(dir, &self.name).write_frame(n as u64, &img)?,
+      }
+      ctx.end();
+    }
+    if let Some(recorder) = &mut self.recorder {
+      recorder.finish()?;
+    }
+    Ok(frames)
+  }
+}
+
+// 与窗口一样，不能录制或保存时输出错误，不 panic
+fn headless_from_env(title: &str, w: u32, h: u32, frames: Option<u32>) -> Headless {
+  let mut headless = Headless::new(title, w, h).output(Output::Files(PathBuf::from("frames")));
+  if let Err(e) = headless.apply_env() {
+    eprintln!("Recording disabled: {}", e);
+  }
+  if let Some(frames) = frames {
+    headless.frames = frames;
+  }
+  headless
+}
+fn report(result: std::io::Result<Vec<PixImage>>) {
+  if let Err(e) = result {
+    eprintln!("Failed to save frames: {}", e);
+  }
+}
+
+// 默认只渲染一帧，保存到 frames 目录
+pub fn frame<F: FnMut((&mut [u8], u32, u32), &FrameContext)>(title: &str, w: u32, h: u32, draw: F) {
+  report(headless_from_env(title, w, h, None).run(draw));
+}
+// 与 frame 相同，帧的大小不会改变
+pub fn resizable_frame<F: FnMut((&mut [u8], u32, u32), &FrameContext)>(
+  title: &str,
+  w: u32,
+  h: u32,
+  draw: F,
+) {
+  frame(title, w, h, draw)
+}
+pub fn one_frame<F: FnMut((&mut [u8], u32, u32))>(title: &str, w: u32, h: u32, mut draw: F) {
+  report(headless_from_env(title, w, h, Some(1)).run(|img, _| draw(img)));
+}
+
+#[test]
+fn test_headless_frames() {
+  use crate::image::Image;
+  let mut n = 0;
+  let frames = Headless::new("test", 4, 2)
+    .frames(3)
+    .fps(4.)
+    // 不是正数的帧率被忽略
+    .fps(0.)
+    .fps(f32::NAN)
+    .run(|mut img, ctx| {
+      assert_eq!(ctx.index, n as u64);
+      assert_eq!(ctx.elapsed.as_secs_f32(), n as f32 * 0.25);
+      n += 1;
+      img.set_rgb24(0, 0, crate::geometry::Vec3::new(n, 0, 0));
+    })
+    .unwrap();
+  assert_eq!(frames.len(), 3);
+  assert_eq!(frames[2].get(0, 0).x, 3);
+}
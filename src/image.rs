@@ -1,10 +1,15 @@
 use crate::geometry::{Vec3, Vec4};
+use crate::pixel::PixelFormat;
 pub trait Image {
   fn width(&self) -> u32;
   fn height(&self) -> u32;
   fn image_origin(&self) -> ImageOriginPos {
     ImageOriginPos::LeftBottom
   }
+  // 保存数据的格式，set_rgba/get_rgbaf 默认经过 RGBA8
+  fn pixel_format(&self) -> PixelFormat {
+    PixelFormat::Rgba8
+  }
   /**
    * 约定图片原点在左下角
    * */
@@ -48,12 +53,8 @@ pub trait Image {
     Vec3::new(rgba.x, rgba.y, rgba.z)
   }
   fn get_rgbf(&self, x: u32, y: u32) -> Vec3<f32> {
-    let rgba = self.get_rgba(x, y);
-    Vec3::new(
-      rgba.x as f32 / 255.,
-      rgba.y as f32 / 255.,
-      rgba.z as f32 / 255.,
-    )
+    let rgba = self.get_rgbaf(x, y);
+    Vec3::new(rgba.x, rgba.y, rgba.z)
   }
   fn get_rgba(&self, x: u32, y: u32) -> Vec4<u8>;
   fn get_rgbaf(&self, x: u32, y: u32) -> Vec4<f32> {
@@ -183,3 +184,8 @@ impl Image for (&mut [u8], u32, u32) {
     Vec4::new(r, g, b, a)
   }
 }
+
+// 按 BT.601 的权重计算亮度，用于灰度格式的编码
+pub(crate) fn luma(c: Vec4<u8>) -> u8 {
+  ((299 * c.x as u32 + 587 * c.y as u32 + 114 * c.z as u32 + 500) / 1000) as u8
+}
@@ -1,5 +1,11 @@
 use crate::image::Image;
 use std::io::Write;
+pub use crate::bmp::BMP;
+pub use crate::gif::GIF;
+pub use crate::hdr::HDR;
+pub use crate::tga::{ScreenDestination, TGAFormat, TGA};
+pub use crate::netpbm::{RawPGM, RawPPM, PFM};
+pub use crate::png::PNG;
 pub trait Encoder {
   fn encode<I: crate::image::Image>(&self, img: &I) -> Vec<u8>;
 }
@@ -21,4 +27,3 @@ impl Encoder for PPM {
     ret
   }
 }
-
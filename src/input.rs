@@ -0,0 +1,273 @@
+// 与窗口后端无关的输入事件。开启 sdl feature 时可以由 sdl2 的事件转换得到，
+// headless 模式下也可以手动构造，用来驱动相机控制器等交互逻辑。
+use std::{collections::HashSet, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+  // 字母和数字键，字母为小写
+  Char(char),
+  Up,
+  Down,
+  Left,
+  Right,
+  Space,
+  Shift,
+  Ctrl,
+  Escape,
+  // 功能键 F1~F12
+  F(u8),
+  Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+  Left,
+  Middle,
+  Right,
+  Other,
+}
+
+// 坐标以窗口左上角为原点，y 轴向下
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+  KeyDown(Key),
+  KeyUp(Key),
+  MouseDown { button: MouseButton, x: i32, y: i32 },
+  MouseUp { button: MouseButton, x: i32, y: i32 },
+  // dx,dy 为相对上一次的移动量
+  MouseMotion { x: i32, y: i32, dx: i32, dy: i32 },
+  // 向上滚动为正
+  MouseWheel(f32),
+  Resized { width: u32, height: u32 },
+}
+
+#[cfg(feature = "sdl")]
+impl Key {
+  pub fn from_sdl(keycode: sdl2::keyboard::Keycode) -> Key {
+    use sdl2::keyboard::Keycode;
+    match keycode {
+      Keycode::Up => Key::Up,
+      Keycode::Down => Key::Down,
+      Keycode::Left => Key::Left,
+      Keycode::Right => Key::Right,
+      Keycode::Space => Key::Space,
+      Keycode::LShift | Keycode::RShift => Key::Shift,
+      Keycode::LCtrl | Keycode::RCtrl => Key::Ctrl,
+      Keycode::Escape => Key::Escape,
+      Keycode::F1 => Key::F(1),
+      Keycode::F2 => Key::F(2),
+      Keycode::F3 => Key::F(3),
+      Keycode::F4 => Key::F(4),
+      Keycode::F5 => Key::F(5),
+      Keycode::F6 => Key::F(6),
+      Keycode::F7 => Key::F(7),
+      Keycode::F8 => Key::F(8),
+      Keycode::F9 => Key::F(9),
+      Keycode::F10 => Key::F(10),
+      Keycode::F11 => Key::F(11),
+      Keycode::F12 => Key::F(12),
+      _ => {
+        let name = keycode.name();
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+          (Some(ch), None) if ch.is_ascii_alphanumeric() => Key::Char(ch.to_ascii_lowercase()),
+          _ => Key::Other,
+        }
+      }
+    }
+  }
+}
+
+#[cfg(feature = "sdl")]
+impl MouseButton {
+  pub fn from_sdl(button: sdl2::mouse::MouseButton) -> MouseButton {
+    match button {
+      sdl2::mouse::MouseButton::Left => MouseButton::Left,
+      sdl2::mouse::MouseButton::Middle => MouseButton::Middle,
+      sdl2::mouse::MouseButton::Right => MouseButton::Right,
+      _ => MouseButton::Other,
+    }
+  }
+}
+
+#[cfg(feature = "sdl")]
+impl InputEvent {
+  // 不关心的事件(包括按键的自动重复)返回 None
+  pub fn from_sdl(event: &sdl2::event::Event) -> Option<InputEvent> {
+    use sdl2::event::{Event, WindowEvent};
+    Some(match *event {
+      Event::KeyDown {
+        keycode: Some(keycode),
+        repeat: false,
+        ..
+      } => InputEvent::KeyDown(Key::from_sdl(keycode)),
+      Event::KeyUp {
+        keycode: Some(keycode),
+        ..
+      } => InputEvent::KeyUp(Key::from_sdl(keycode)),
+      Event::MouseButtonDown {
+        mouse_btn, x, y, ..
+      } => InputEvent::MouseDown {
+        button: MouseButton::from_sdl(mouse_btn),
+        x,
+        y,
+      },
+      Event::MouseButtonUp {
+        mouse_btn, x, y, ..
+      } => InputEvent::MouseUp {
+        button: MouseButton::from_sdl(mouse_btn),
+        x,
+        y,
+      },
+      Event::MouseMotion {
+        x, y, xrel, yrel, ..
+      } => InputEvent::MouseMotion {
+        x,
+        y,
+        dx: xrel,
+        dy: yrel,
+      },
+      Event::MouseWheel {
+        precise_y,
+        direction,
+        ..
+      } => InputEvent::MouseWheel(match direction {
+        sdl2::mouse::MouseWheelDirection::Flipped => -precise_y,
+        _ => precise_y,
+      }),
+      Event::Window {
+        win_event: WindowEvent::SizeChanged(width, height),
+        ..
+      } => InputEvent::Resized {
+        width: width.max(1) as u32,
+        height: height.max(1) as u32,
+      },
+      _ => return None,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MouseState {
+  pub x: i32,
+  pub y: i32,
+  pub left: bool,
+  pub middle: bool,
+  pub right: bool,
+}
+impl MouseState {
+  pub fn is_down(&self, button: MouseButton) -> bool {
+    match button {
+      MouseButton::Left => self.left,
+      MouseButton::Middle => self.middle,
+      MouseButton::Right => self.right,
+      MouseButton::Other => false,
+    }
+  }
+}
+
+// 传给 sdl::frame 回调的每帧信息
+#[derive(Debug, Clone, Default)]
+pub struct FrameContext {
+  // 这一帧之前收到的事件
+  pub events: Vec<InputEvent>,
+  // 当前按下的键
+  pub keys: HashSet<Key>,
+  pub mouse: MouseState,
+  // 与上一帧的时间间隔，第一帧为 0
+  pub delta: Duration,
+  // 从第一帧开始经过的时间
+  pub elapsed: Duration,
+  // 帧的序号，从 0 开始
+  pub index: u64,
+  pub width: u32,
+  pub height: u32,
+  // 窗口大小在这一帧改变了，回调需要重新分配深度缓冲等与大小有关的资源
+  pub resized: bool,
+}
+impl FrameContext {
+  pub fn new(width: u32, height: u32) -> FrameContext {
+    FrameContext {
+      width,
+      height,
+      ..Default::default()
+    }
+  }
+  // 开始新的一帧：清空上一帧的事件，累加时间
+  pub fn begin(&mut self, delta: Duration) {
+    self.events.clear();
+    self.resized = false;
+    self.delta = delta;
+    self.elapsed += delta;
+  }
+  // 回调返回后调用
+  pub fn end(&mut self) {
+    self.index += 1;
+  }
+  // 记录事件并更新键盘、鼠标状态和窗口大小
+  pub fn push(&mut self, event: InputEvent) {
+    match event {
+      InputEvent::KeyDown(key) => {
+        self.keys.insert(key);
+      }
+      InputEvent::KeyUp(key) => {
+        self.keys.remove(&key);
+      }
+      InputEvent::MouseDown { button, x, y } | InputEvent::MouseUp { button, x, y } => {
+        let down = matches!(event, InputEvent::MouseDown { .. });
+        match button {
+          MouseButton::Left => self.mouse.left = down,
+          MouseButton::Middle => self.mouse.middle = down,
+          MouseButton::Right => self.mouse.right = down,
+          MouseButton::Other => {}
+        }
+        (self.mouse.x, self.mouse.y) = (x, y);
+      }
+      InputEvent::MouseMotion { x, y, .. } => (self.mouse.x, self.mouse.y) = (x, y),
+      InputEvent::Resized { width, height } => {
+        self.resized = (width, height) != (self.width, self.height);
+        (self.width, self.height) = (width, height);
+      }
+      InputEvent::MouseWheel(_) => {}
+    }
+    self.events.push(event);
+  }
+  pub fn is_key_down(&self, key: Key) -> bool {
+    self.keys.contains(&key)
+  }
+  // 由 delta 计算的瞬时帧率，delta 为 0 时返回 0
+  pub fn fps(&self) -> f32 {
+    let secs = self.delta.as_secs_f32();
+    if secs > 0. {
+      1. / secs
+    } else {
+      0.
+    }
+  }
+}
+
+#[test]
+fn test_frame_context() {
+  let mut ctx = FrameContext::new(4, 4);
+  ctx.begin(Duration::ZERO);
+  assert_eq!(ctx.fps(), 0.);
+  ctx.push(InputEvent::KeyDown(Key::Char('w')));
+  ctx.push(InputEvent::MouseDown {
+    button: MouseButton::Right,
+    x: 1,
+    y: 2,
+  });
+  ctx.push(InputEvent::Resized {
+    width: 8,
+    height: 6,
+  });
+  assert!(ctx.resized && (ctx.width, ctx.height) == (8, 6));
+  assert!(ctx.mouse.is_down(MouseButton::Right) && (ctx.mouse.x, ctx.mouse.y) == (1, 2));
+  ctx.end();
+  ctx.begin(Duration::from_millis(20));
+  ctx.push(InputEvent::KeyUp(Key::Char('w')));
+  assert_eq!(ctx.index, 1);
+  assert!(!ctx.resized && !ctx.is_key_down(Key::Char('w')));
+  assert_eq!(ctx.events.len(), 1);
+  assert!((ctx.fps() - 50.).abs() < 1e-3);
+}
@@ -1,19 +1,45 @@
+pub mod bmp;
+pub mod bounds;
+pub mod bvh;
+pub mod camera;
+pub mod compare;
+pub mod depth;
+pub mod error;
 pub mod file;
+pub mod format;
 pub mod geometry;
+pub mod gif;
+pub mod hdr;
 pub mod image;
 pub mod image_decoder;
 pub mod image_encoder;
+pub mod input;
+pub mod lod;
 pub mod mat;
 pub mod model;
+pub mod netpbm;
+pub mod picking;
+pub mod pixel;
+pub mod png;
 pub mod pipeline;
 pub mod pipeline2;
+pub mod ray;
+pub mod raytracer;
+pub mod record;
+pub mod tga;
 pub mod transform;
 pub mod font;
+pub mod headless;
+#[cfg(feature = "sdl")]
 pub mod sdl;
+#[cfg(not(feature = "sdl"))]
+pub use headless as sdl;
 pub mod util;
+pub mod zlib;
 pub mod texture;
 pub mod prelude {
-  pub use crate::file::save_image;
+  pub use crate::depth::{DepthBuffer, DepthCompare, DepthFormat};
+  pub use crate::file::{save_image, save_image_auto};
   pub use crate::geometry::{Vec3, Vec4};
   pub use crate::image::{Image, PixImage};
   pub use crate::image_encoder::{Encoder, PPM};
@@ -34,4 +60,6 @@ pub mod prelude {
   pub use crate::texture::*;
 }
 pub mod shape;
+pub mod scene_graph;
 pub mod shaders;
+pub mod simplify;
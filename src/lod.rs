@@ -0,0 +1,99 @@
+// 细节层次(LOD)：预先生成逐级简化的模型，绘制时按模型投影到屏幕上的大小选择一级。
+use crate::{
+  bounds::BoundingSphere,
+  geometry::Vec4,
+  mat::Mat4,
+  model::{IndexedMesh, Model},
+};
+
+// 少于这个三角形数时不再生成更粗的层次
+const MIN_FACES: usize = 16;
+
+pub struct Lod {
+  // 第 0 级为原始模型，之后每级的三角形数减半
+  levels: Vec<IndexedMesh>,
+  sphere: BoundingSphere,
+  // 每个三角形平均覆盖的像素数，越小选择的层次越精细
+  pub pixels_per_triangle: f32,
+}
+impl Lod {
+  pub fn new<M: Model>(model: &M, max_levels: usize) -> Lod {
+    let mut levels = vec![IndexedMesh::from_model(model)];
+    while levels.len() < max_levels {
+      let last = levels.last().unwrap();
+      if last.face_count() / 2 < MIN_FACES {
+        break;
+      }
+      let next = last.simplify(last.face_count() / 2);
+      // 无法继续简化
+      if next.face_count() >= last.face_count() {
+        break;
+      }
+      levels.push(next);
+    }
+    Lod {
+      levels,
+      sphere: BoundingSphere::from_model(model),
+      pixels_per_triangle: 8.,
+    }
+  }
+  pub fn pixels_per_triangle(mut self, pixels: f32) -> Lod {
+    self.pixels_per_triangle = pixels;
+    self
+  }
+  pub fn level_count(&self) -> usize {
+    self.levels.len()
+  }
+  pub fn level(&self, i: usize) -> &IndexedMesh {
+    &self.levels[i.min(self.levels.len() - 1)]
+  }
+  pub fn bounding_sphere(&self) -> BoundingSphere {
+    self.sphere
+  }
+  // 三角形数不超过覆盖面积所需的最精细的一级
+  pub fn select_level(&self, model_view: &Mat4, projection: &Mat4, height: u32) -> usize {
+    let r = projected_radius(&self.sphere, model_view, projection, height);
+    let budget = std::f32::consts::PI * r * r / self.pixels_per_triangle;
+    self
+      .levels
+      .iter()
+      .position(|m| m.face_count() as f32 <= budget)
+      .unwrap_or(self.levels.len() - 1)
+  }
+  pub fn select(&self, model_view: &Mat4, projection: &Mat4, height: u32) -> &IndexedMesh {
+    self.level(self.select_level(model_view, projection, height))
+  }
+}
+
+// 包围球投影到屏幕上的半径，单位为像素，height 为视口的高度
+pub fn projected_radius(
+  sphere: &BoundingSphere,
+  model_view: &Mat4,
+  projection: &Mat4,
+  height: u32,
+) -> f32 {
+  let sphere = sphere.transform(model_view);
+  let clip = projection * Vec4::from_point(&sphere.center);
+  if clip.w.abs() < f32::EPSILON {
+    return f32::INFINITY;
+  }
+  sphere.radius * projection.get(1, 1).abs() / clip.w.abs() * height as f32 / 2.
+}
+
+#[test]
+fn test_lod_select() {
+  use crate::transform;
+  let model = crate::model::Object::from_file("models/spot/spot_triangulated.obj").unwrap();
+  let lod = Lod::new(&model, 4);
+  assert_eq!(lod.level_count(), 4);
+  assert!(lod.level(3).face_count() <= model.face_count() / 8);
+  let projection = transform::perspective(60., 1., -0.1, -100.);
+  let near = transform::translate(0., 0., -2.);
+  let far = transform::translate(0., 0., -60.);
+  assert_eq!(lod.select_level(&near, &projection, 512), 0);
+  assert_eq!(lod.select_level(&far, &projection, 512), 3);
+  // 正交投影下大小与距离无关
+  let ortho = transform::orthographic(-1., 1., -1., 1., -100., -0.1);
+  let r = projected_radius(&lod.bounding_sphere(), &far, &ortho, 512);
+  assert!((r - lod.bounding_sphere().radius * 256.).abs() < 1e-3);
+}
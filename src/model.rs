@@ -1,8 +1,10 @@
 use std::collections::HashMap;
-use std::io::*;
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 
 //https://en.wikipedia.org/wiki/Wavefront_.obj_file
+use crate::bounds::Aabb;
+use crate::error::{Error, Result};
 use crate::geometry::Vec3;
 
 pub trait Model {
@@ -12,6 +14,10 @@ pub trait Model {
   fn normal(&self, face: usize, nth_vert: usize) -> Vec3<f32>;
   fn normal_of_face(&self, face: usize) -> Vec3<f32>;
   fn texture_coord(&self, face: usize, nth_vert: usize) -> Vec3<f32>;
+  // 没有纹理坐标时不能调用 texture_coord
+  fn has_texture_coord(&self) -> bool {
+    true
+  }
 }
 
 pub struct Object {
@@ -38,41 +44,25 @@ impl Object {
   pub fn has_normal_vector(&self) -> bool {
     self.vert_normals.len() > 1
   }
+  // 包含所有顶点的包围盒，不包括占位的 verts[0]
+  pub fn bounds(&self) -> Aabb {
+    Aabb::from_points(self.verts[1..].iter())
+  }
   pub fn normalize_verts(&mut self) {
-    let first = self.verts[0];
-    let mut min_x = first.x;
-    let mut max_x = first.x;
-    let mut min_y = first.y;
-    let mut max_y = first.y;
-    let mut min_z = first.z;
-    let mut max_z = first.z;
-    self.verts.iter().for_each(|v| {
-      min_x = min_x.min(v.x);
-      max_x = max_x.max(v.x);
-      min_y = min_y.min(v.y);
-      max_y = max_y.max(v.y);
-      min_z = min_z.min(v.z);
-      max_z = max_z.max(v.z);
-    });
-    let x = max_x - min_x;
-    let y = max_y - min_y;
-    let z = max_z - min_z;
-    if max_x <= 1. && min_x >= -1. && max_y <= 1. && max_y >= -1. && max_z <= 1. && max_z >= -1. {
+    let Aabb { min, max } = self.bounds();
+    let size = max - min;
+    let inside = |i: u8| min[i] >= -1. && max[i] <= 1.;
+    if (0..3).all(inside) {
       return;
     }
 
-    self.verts.iter_mut().for_each(|v| {
-      if max_x > 1. || min_x < -1. {
-        v.x = (v.x - min_x) / x * 2. - 1.0;
-      }
-      if max_y > 1. || min_y < -1. {
-        v.y = (v.y - min_y) / y * 2. - 1.0;
-      }
-      if max_z > 1. || min_z < -1. {
-        v.z = (v.z - min_z) / z * 2. - 1.0;
+    self.verts[1..].iter_mut().for_each(|v| {
+      for i in 0..3 {
+        if !inside(i) {
+          v[i] = (v[i] - min[i]) / size[i] * 2. - 1.0;
+        }
       }
     });
-    //
   }
   fn get_vertex_index(&self, v_idx: i32) -> usize {
     if v_idx >= 0 {
@@ -142,7 +132,13 @@ impl Object {
       self.computed_vert_normals[face * 3 + nth_vert]
     }
   }
-  pub fn from_file<P: AsRef<Path>>(file: P) -> std::io::Result<Object> {
+  pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Object> {
+    let path = file.as_ref();
+    let file = std::fs::File::open(path).map_err(|e| Error::from(e).with_path(path))?;
+    Object::from_reader(BufReader::new(file)).map_err(|e| e.with_path(path))
+  }
+  // 出错时返回的错误带有行号
+  pub fn from_reader<R: BufRead>(reader: R) -> Result<Object> {
     let mut verts = vec![Vec3::new(0., 0., 0.)];
     let mut texture_coords = vec![Vec3::new(0., 0., 0.)];
     let mut vert_normals = vec![Vec3::new(0., 0., 0.)];
@@ -151,37 +147,31 @@ impl Object {
     let mut face_texture_idx = Vec::new();
     let mut vert_normal_idx = Vec::new();
     let mut faces_of_vert = HashMap::new();
-    let file = std::fs::File::open(file)?;
-    BufReader::new(file).lines().for_each(|line| {
-      if let Ok(line) = line {
-        if line.starts_with("v ") {
-          verts.push(parse_vert_or_vn(line));
-        } else if line.starts_with("vt") {
-          texture_coords.push(parse_vt(line));
-        } else if line.starts_with("vn") {
-          vert_normals.push(parse_vert_or_vn(line));
-        } else if line.starts_with("f") {
-          let (verts, vts, vns) = parse_face(line);
+    for (n, line) in reader.lines().enumerate() {
+      let at_line = |e: Error| e.at_line(n + 1);
+      let line = line.map_err(|e| at_line(e.into()))?;
+      let mut items = line.split_whitespace();
+      match items.next() {
+        Some("v") => verts.push(parse_vert_or_vn(items).map_err(at_line)?),
+        Some("vt") => texture_coords.push(parse_vt(items).map_err(at_line)?),
+        Some("vn") => vert_normals.push(parse_vert_or_vn(items).map_err(at_line)?),
+        Some("f") => {
+          let counts = (verts.len(), texture_coords.len(), vert_normals.len());
+          let (vs, vts, vns) = parse_face(items, counts).map_err(at_line)?;
           let face_idx = face_vert_idx.len();
-          for vertex_idx in &verts {
-            // 为了简单起见，假设所有顶点已经读取到了verts里面
-            let vertex_idx = if *vertex_idx > 0 {
-              *vertex_idx as usize
-            } else {
-              let last = (verts.len() - 1) as i32;
-              (last + (*vertex_idx as i32)) as usize
-            };
-            let faces = faces_of_vert.entry(vertex_idx).or_insert(Vec::new());
+          for vertex_idx in &vs {
+            let faces = faces_of_vert
+              .entry(*vertex_idx as usize)
+              .or_insert(Vec::new());
             faces.push(face_idx)
           }
-          face_vert_idx.push(verts);
+          face_vert_idx.push(vs);
           face_texture_idx.push(vts);
           vert_normal_idx.push(vns);
         }
-      } else if let Err(e) = line {
-        eprintln!("Readline error:{:?}", e);
+        _ => {}
       }
-    });
+    }
 
     let mut m = Object {
       verts,
@@ -225,13 +215,11 @@ impl Object {
           } else if vertex_idx == self.get_vertex_index(vertex_index[2]) {
             2
           } else {
-            panic!("")
+            // 多边形面只用到前三个顶点
+            continue;
           };
           computed_vert_normals[face * 3 + nth_vert] = normal;
         }
-      } else {
-        println!("{}", vertex_idx);
-        panic!("No faces found, this is a bug")
       }
     }
     self.computed_vert_normals = computed_vert_normals;
@@ -277,6 +265,10 @@ impl Model for Object {
     self.texture_coords[v_idx]
   }
 
+  fn has_texture_coord(&self) -> bool {
+    self.texture_coords.len() > 1
+  }
+
   fn normal_of_face(&self, face: usize) -> Vec3<f32> {
     let verts = self.verts_of_face(face);
     return (verts[1] - verts[0])
@@ -285,81 +277,170 @@ impl Model for Object {
   }
 }
 
-fn parse_vt(line: String) -> Vec3<f32> {
-  let vs = line
-    .trim_matches(|ch| ch == 'v' || ch == ' ' || ch == 't' || ch == 'n')
-    .split(" ")
-    .map(|i| {
-      i.parse::<f32>()
-        .expect(format!("Expect float number,got {:?}", i).as_str())
-    })
-    .collect::<Vec<_>>();
-  Vec3::new(vs[0], vs[1], 0.)
+// 索引三角形网格，每个顶点是一组 (位置, 法向量, 纹理坐标)。
+// 纹理坐标或法向量不连续(接缝)处同一位置有多个顶点
+#[derive(Debug, Clone, Default)]
+pub struct IndexedMesh {
+  pub positions: Vec<Vec3<f32>>,
+  pub normals: Vec<Vec3<f32>>,
+  // 模型没有纹理坐标时为空
+  pub uvs: Vec<Vec3<f32>>,
+  pub indices: Vec<[u32; 3]>,
 }
-fn parse_vert_or_vn(line: String) -> Vec3<f32> {
-  let vs = line
-    .trim_matches(|ch| ch == 'v' || ch == ' ' || ch == 't' || ch == 'n')
-    .split(" ")
+impl IndexedMesh {
+  // 合并属性完全相同的顶点
+  pub fn from_model<M: Model>(model: &M) -> IndexedMesh {
+    let has_uv = model.has_texture_coord();
+    let mut mesh = IndexedMesh::default();
+    let mut index_of = HashMap::new();
+    let bits = |v: Vec3<f32>| [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()];
+    for face in 0..model.face_count() {
+      let mut tri = [0; 3];
+      for (i, idx) in tri.iter_mut().enumerate() {
+        let p = model.vert(face, i);
+        let n = model.normal(face, i);
+        let uv = if has_uv {
+          model.texture_coord(face, i)
+        } else {
+          Vec3::default()
+        };
+        *idx = *index_of.entry((bits(p), bits(n), bits(uv))).or_insert_with(|| {
+          mesh.positions.push(p);
+          mesh.normals.push(n);
+          if has_uv {
+            mesh.uvs.push(uv);
+          }
+          (mesh.positions.len() - 1) as u32
+        });
+      }
+      mesh.indices.push(tri);
+    }
+    mesh
+  }
+}
+impl Model for IndexedMesh {
+  fn vert_count(&self) -> usize {
+    self.positions.len()
+  }
+  fn face_count(&self) -> usize {
+    self.indices.len()
+  }
+  fn vert(&self, face: usize, nth_vert: usize) -> Vec3<f32> {
+    self.positions[self.indices[face][nth_vert] as usize]
+  }
+  fn normal(&self, face: usize, nth_vert: usize) -> Vec3<f32> {
+    self.normals[self.indices[face][nth_vert] as usize]
+  }
+  fn normal_of_face(&self, face: usize) -> Vec3<f32> {
+    let [a, b, c] = self.indices[face].map(|i| self.positions[i as usize]);
+    (b - a).cross_product(c - a).normalize()
+  }
+  fn texture_coord(&self, face: usize, nth_vert: usize) -> Vec3<f32> {
+    self.uvs[self.indices[face][nth_vert] as usize]
+  }
+  fn has_texture_coord(&self) -> bool {
+    !self.uvs.is_empty()
+  }
+}
+
+fn parse_floats<'a, I: Iterator<Item = &'a str>>(items: I, min: usize) -> Result<Vec<f32>> {
+  let vs = items
     .map(|i| {
       i.parse::<f32>()
-        .expect(format!("Expect float number,got {:?}", i).as_str())
+        .map_err(|_| Error::format(format!("expect float number, got {:?}", i)))
     })
-    .collect::<Vec<_>>();
-  if vs.len() < 3 {
-    println!("line:{}", line);
+    .collect::<Result<Vec<_>>>()?;
+  if vs.len() < min {
+    return Err(Error::format(format!(
+      "expect at least {} numbers, got {}",
+      min,
+      vs.len()
+    )));
   }
-  Vec3::new(vs[0], vs[1], vs[2])
+  Ok(vs)
 }
-fn parse_face(line: String) -> (Vec<i32>, Vec<i32>, Vec<i32>) {
-  let parts = line
-    // f v v v
-    // f v/vt/vn xx/xx/xx xx/xx/xx
-    // f v/vt v/vt v/vt
-    // f v//vn v//vn v//vn
-    .trim_matches(|ch| ch == 'f' || ch == ' ')
-    .split(" ");
+fn parse_vt<'a, I: Iterator<Item = &'a str>>(items: I) -> Result<Vec3<f32>> {
+  // v 可以省略，默认为 0
+  let vs = parse_floats(items, 1)?;
+  Ok(Vec3::new(vs[0], vs.get(1).copied().unwrap_or(0.), 0.))
+}
+fn parse_vert_or_vn<'a, I: Iterator<Item = &'a str>>(items: I) -> Result<Vec3<f32>> {
+  let vs = parse_floats(items, 3)?;
+  Ok(Vec3::new(vs[0], vs[1], vs[2]))
+}
+// 把索引转换为从 1 起的绝对索引，负数索引相对于已经读取的最后一个元素。
+// len 包含下标为 0 的占位元素
+fn parse_index(item: &str, len: usize, what: &str) -> Result<i32> {
+  let idx = item
+    .parse::<i32>()
+    .map_err(|_| Error::format(format!("expect {} index, got {:?}", what, item)))?;
+  let abs = if idx < 0 { len as i32 + idx } else { idx };
+  if abs < 1 || abs as usize >= len {
+    return Err(Error::format(format!(
+      "{} index {} out of range",
+      what, idx
+    )));
+  }
+  Ok(abs)
+}
+// f v v v
+// f v/vt/vn xx/xx/xx xx/xx/xx
+// f v/vt v/vt v/vt
+// f v//vn v//vn v//vn
+fn parse_face<'a, I: Iterator<Item = &'a str>>(
+  items: I,
+  (vert_len, vt_len, vn_len): (usize, usize, usize),
+) -> Result<(Vec<i32>, Vec<i32>, Vec<i32>)> {
   let mut verts = vec![];
   let mut vts = vec![];
   let mut vert_normals = vec![];
-  parts.for_each(|item| {
+  for item in items {
     let parts = item.split('/').collect::<Vec<_>>();
-    match parts.len() {
-      1 => {
-        let v_idx = parts[0];
-        verts.push(v_idx.parse::<i32>().expect("Expect i32 as a vert index"));
-      }
-      2 => {
-        let v_idx = parts[0];
-        let vt_idx = parts[1];
-        verts.push(v_idx.parse::<i32>().expect("Expect i32 as a vert index"));
-        vts.push(
-          vt_idx
-            .parse::<i32>()
-            .expect("Expect i32 as a texture index"),
-        );
-      }
-      3 => {
-        let v_idx = parts[0];
-        let vt_idx = parts[1];
-        let vn_idx = parts[2];
-        verts.push(v_idx.parse::<i32>().expect("Expect i32 as a vert index"));
-        if !vt_idx.is_empty() {
-          vts.push(
-            vt_idx
-              .parse::<i32>()
-              .expect("Expect i32 as a texture index"),
-          );
-        }
-        vert_normals.push(
-          vn_idx
-            .parse::<i32>()
-            .expect("Expect i32 as vert normal vector index"),
-        );
-      }
-      _ => {
-        panic!("Invalid model file:{} ", line)
-      }
+    if parts.len() > 3 {
+      return Err(Error::format(format!("invalid face vertex {:?}", item)));
     }
-  });
-  (verts, vts, vert_normals)
+    verts.push(parse_index(parts[0], vert_len, "vertex")?);
+    if let Some(vt_idx) = parts.get(1).filter(|i| !i.is_empty()) {
+      vts.push(parse_index(vt_idx, vt_len, "texture")?);
+    }
+    if let Some(vn_idx) = parts.get(2) {
+      vert_normals.push(parse_index(vn_idx, vn_len, "normal")?);
+    }
+  }
+  if verts.len() < 3 {
+    return Err(Error::format(format!(
+      "face has {} vertices, expect at least 3",
+      verts.len()
+    )));
+  }
+  Ok((verts, vts, vert_normals))
+}
+
+#[test]
+fn test_obj_parse_error() {
+  let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5\nf -3 2 3/1\n";
+  let m = Object::from_reader(obj.as_bytes()).unwrap();
+  assert_eq!((m.vert_count(), m.face_count()), (3, 1));
+  assert_eq!(m.vert(0, 0).x, 0.);
+  let m = Object::from_reader("v 2 3 4\nv 5 3 4\nv 2 6 8\nf 1 2 3\n".as_bytes()).unwrap();
+  let Aabb { min, max } = m.bounds();
+  assert_eq!((min.x, min.y, min.z, max.x, max.y, max.z), (2., 3., 4., 5., 6., 8.));
+  let err = |obj: &str| {
+    Object::from_reader(obj.as_bytes())
+      .err()
+      .unwrap()
+      .to_string()
+  };
+  assert_eq!(
+    err("v 0 0 0\nv 1 x 0\n"),
+    "line 2: invalid data: expect float number, got \"x\""
+  );
+  assert_eq!(
+    err("v 0 0 0\n\nf 1 1 2\n"),
+    "line 3: invalid data: vertex index 2 out of range"
+  );
+  assert_eq!(
+    err("v 0 0 0\nf 1 1\n"),
+    "line 2: invalid data: face has 2 vertices, expect at least 3"
+  );
 }
@@ -0,0 +1,299 @@
+// Netpbm 格式：二进制的 PPM(P6)、PGM(P5) 和浮点的 PFM(PF/Pf)，文本格式的 PPM(P3) 见 image_encoder::PPM。
+// 文件头说明了具体格式，所以这些格式的解码器是同一个，可以读取 P2、P3、P5、P6、PF 和 Pf。
+use crate::{
+  error::{Error, Result},
+  image::{luma, Image, ImageOriginPos, PixImage},
+  image_decoder::Decoder,
+  image_encoder::{Encoder, PPM},
+  pixel::{ImageF32, Pixel, TypedImage},
+};
+
+// 二进制的 RGB，每个通道 8 位
+pub struct RawPPM;
+// 二进制的灰度，每个像素 8 位
+pub struct RawPGM;
+// 32 位浮点数的 RGB 或灰度，可以无损保存深度缓冲和 HDR 数据
+pub struct PFM;
+
+impl Encoder for RawPPM {
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    let (w, h) = (img.width(), img.height());
+    let mut out = format!("P6\n{} {}\n255\n", w, h).into_bytes();
+    out.reserve((w * h * 3) as usize);
+    // 从最上面一行开始
+    for y in (0..h).rev() {
+      for x in 0..w {
+        let c = img.get(x, y);
+        out.extend([c.x, c.y, c.z]);
+      }
+    }
+    out
+  }
+}
+
+impl Encoder for RawPGM {
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    let (w, h) = (img.width(), img.height());
+    let mut out = format!("P5\n{} {}\n255\n", w, h).into_bytes();
+    out.reserve((w * h) as usize);
+    for y in (0..h).rev() {
+      for x in 0..w {
+        out.push(luma(img.get_rgba(x, y)));
+      }
+    }
+    out
+  }
+}
+
+impl PFM {
+  // 单通道的图片写为 Pf，否则写为 PF，忽略透明度
+  pub fn encode_float<P: Pixel>(&self, img: &TypedImage<P>) -> Vec<u8> {
+    let channels = if P::FORMAT.channels() == 1 { 1 } else { 3 };
+    let magic = if channels == 1 { "Pf" } else { "PF" };
+    // 比例为负表示小端
+    let mut out = format!("{}\n{} {}\n-1.0\n", magic, img.width, img.height).into_bytes();
+    out.reserve(img.data.len() * channels * 4);
+    // PFM 的第一行是图片的最下面一行，与 TypedImage 一致
+    for p in &img.data {
+      let c = p.to_rgba();
+      for v in &[c.x, c.y, c.z][..channels] {
+        out.extend(v.to_le_bytes());
+      }
+    }
+    out
+  }
+  // Pf 的灰度读取为 r、g、b 相同的颜色，可以用 convert 转换为 R32F
+  pub fn decode_float(&self, data: &[u8]) -> Result<ImageF32> {
+    let mut tokens = Tokens { data, pos: 0 };
+    decode_pfm(&mut tokens)
+  }
+}
+
+impl Encoder for PFM {
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    self.encode_float(&ImageF32::from_image(img))
+  }
+}
+
+// 文件头中以空白分隔的记号，# 到行尾是注释
+struct Tokens<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+impl<'a> Tokens<'a> {
+  fn next(&mut self) -> Option<&'a str> {
+    loop {
+      match self.data.get(self.pos)? {
+        b'#' => {
+          while self.data.get(self.pos).is_some_and(|&c| c != b'\n') {
+            self.pos += 1;
+          }
+        }
+        c if c.is_ascii_whitespace() => self.pos += 1,
+        _ => break,
+      }
+    }
+    let start = self.pos;
+    while self
+      .data
+      .get(self.pos)
+      .is_some_and(|c| !c.is_ascii_whitespace())
+    {
+      self.pos += 1;
+    }
+    std::str::from_utf8(&self.data[start..self.pos]).ok()
+  }
+  fn number<T: std::str::FromStr>(&mut self) -> Result<T> {
+    let token = self
+      .next()
+      .ok_or_else(|| Error::format("unexpected end of file"))?;
+    token
+      .parse()
+      .map_err(|_| Error::format(format!("invalid number {:?}", token)))
+  }
+  // 二进制数据从最后一个记号后的一个空白字符之后开始
+  fn binary(&self) -> &'a [u8] {
+    self.data.get(self.pos + 1..).unwrap_or(&[])
+  }
+}
+
+// 宽高来自文件，先用这个数量检查数据的长度再分配
+fn sample_count(width: u32, height: u32, channels: usize) -> Result<usize> {
+  (width as usize)
+    .checked_mul(height as usize)
+    .and_then(|n| n.checked_mul(channels))
+    .ok_or_else(|| Error::format("image is too large"))
+}
+
+fn decode_pfm(tokens: &mut Tokens) -> Result<ImageF32> {
+  let channels = match tokens.next() {
+    Some("Pf") => 1,
+    Some("PF") => 3,
+    magic => return Err(Error::format(format!("unknown PFM magic {:?}", magic))),
+  };
+  let width: u32 = tokens.number()?;
+  let height: u32 = tokens.number()?;
+  // 比例的绝对值一般为 1，这里只用它的符号判断字节序
+  let scale: f32 = tokens.number()?;
+  let len = sample_count(width, height, channels)?;
+  let data = tokens.binary();
+  if data.len() / 4 < len {
+    return Err(Error::format("image data is too short"));
+  }
+  let samples: Vec<f32> = data[..len * 4]
+    .chunks_exact(4)
+    .map(|b| {
+      let b = b.try_into().unwrap();
+      if scale < 0. {
+        f32::from_le_bytes(b)
+      } else {
+        f32::from_be_bytes(b)
+      }
+    })
+    .collect();
+  let pixels = samples
+    .chunks_exact(channels)
+    .map(|p| match *p {
+      [v] => [v, v, v, 1.],
+      _ => [p[0], p[1], p[2], 1.],
+    })
+    .collect();
+  Ok(TypedImage::from_data(pixels, width, height))
+}
+
+fn decode_netpbm(data: &[u8]) -> Result<PixImage> {
+  let mut tokens = Tokens { data, pos: 0 };
+  let magic = tokens
+    .next()
+    .ok_or_else(|| Error::format("missing magic number"))?;
+  let channels = match magic {
+    "P2" | "P5" => 1,
+    "P3" | "P6" => 3,
+    "PF" | "Pf" => {
+      return Ok(decode_pfm(&mut Tokens { data, pos: 0 })?.to_pix_image());
+    }
+    _ => return Err(Error::unsupported(format!("netpbm format {:?}", magic))),
+  };
+  let width: u32 = tokens.number()?;
+  let height: u32 = tokens.number()?;
+  let max_value: u32 = tokens.number()?;
+  if !(1..=65535).contains(&max_value) {
+    return Err(Error::format(format!(
+      "invalid maximum value {}",
+      max_value
+    )));
+  }
+  let len = sample_count(width, height, channels)?;
+  let samples: Vec<u32> = if magic == "P2" || magic == "P3" {
+    // 每个采样至少有一个数字
+    if data.len().saturating_sub(tokens.pos) < len {
+      return Err(Error::format("image data is too short"));
+    }
+    (0..len).map(|_| tokens.number()).collect::<Result<_>>()?
+  } else {
+    // 最大值超过 255 时每个采样 2 字节，大端
+    let size = if max_value > 255 { 2 } else { 1 };
+    let data = tokens.binary();
+    if data.len() / size < len {
+      return Err(Error::format("image data is too short"));
+    }
+    data[..len * size]
+      .chunks_exact(size)
+      .map(|b| b.iter().fold(0, |v, &byte| v << 8 | byte as u32))
+      .collect()
+  };
+  let mut ret = Vec::with_capacity(len / channels * 4);
+  for pixel in samples.chunks_exact(channels) {
+    let scaled = |v: u32| ((v.min(max_value) * 255 + max_value / 2) / max_value) as u8;
+    match *pixel {
+      [v] => ret.extend([scaled(v), scaled(v), scaled(v), 255]),
+      _ => ret.extend([scaled(pixel[0]), scaled(pixel[1]), scaled(pixel[2]), 255]),
+    }
+  }
+  // Netpbm 的第一行是图片的最上面一行(PFM 除外)
+  Ok(PixImage::from_data(
+    ret,
+    width,
+    height,
+    ImageOriginPos::LeftBottom,
+  ))
+}
+
+macro_rules! netpbm_decoder {
+  ($($format:ty),*) => {
+    $(impl Decoder for $format {
+      fn decode(&self, data: Vec<u8>) -> Result<PixImage> {
+        decode_netpbm(&data)
+      }
+    })*
+  };
+}
+netpbm_decoder!(PPM, RawPPM, RawPGM, PFM);
+
+#[test]
+fn test_netpbm() {
+  use crate::{geometry::Vec4, pixel::R32F};
+  let rgba = |c: Vec4<u8>| [c.x, c.y, c.z, c.w];
+  let mut img = PixImage::new(3, 2);
+  for y in 0..2 {
+    for x in 0..3 {
+      img.set_rgba32(x, y, Vec4::new(x as u8 * 100, y as u8 * 200, 7, 255));
+    }
+  }
+  let ppm = RawPPM.encode(&img);
+  assert_eq!(ppm[..11], *b"P6\n3 2\n255\n");
+  // 第一个像素是左上角
+  assert_eq!(ppm[11..14], [0, 200, 7]);
+  for decoded in [
+    RawPPM.decode(ppm).unwrap(),
+    PPM.decode(PPM.encode(&img)).unwrap(),
+  ] {
+    for y in 0..2 {
+      for x in 0..3 {
+        assert_eq!(rgba(decoded.get_rgba(x, y)), rgba(img.get_rgba(x, y)));
+      }
+    }
+  }
+  let pgm = RawPGM.decode(RawPGM.encode(&img)).unwrap();
+  assert_eq!(rgba(pgm.get_rgba(2, 1)), [178, 178, 178, 255]);
+
+  // 注释、16 位的采样和文本格式的灰度
+  let p5 = RawPGM
+    .decode(b"P5 # comment\n2 1\n# max\n1000\n\x03\xe8\x01\xf4".to_vec())
+    .unwrap();
+  assert_eq!(rgba(p5.get_rgba(0, 0)), [255; 4]);
+  assert_eq!(rgba(p5.get_rgba(1, 0)), [128, 128, 128, 255]);
+  let p2 = PPM.decode(b"P2\n1 2\n4\n1\n4\n".to_vec()).unwrap();
+  assert_eq!(rgba(p2.get_rgba(0, 1)), [64, 64, 64, 255]);
+
+  // 浮点数无损往返，超出 [0,1] 的值也保留
+  let depth = R32F::from_data(vec![-1.5, 0., 0.25, 1e10], 2, 2);
+  let pfm = PFM.encode_float(&depth);
+  assert_eq!(pfm[..12], *b"Pf\n2 2\n-1.0\n");
+  let decoded = PFM.decode_float(&pfm).unwrap();
+  assert_eq!((decoded.width, decoded.height), (2, 2));
+  assert_eq!(decoded.pixel(1, 1), [1e10, 1e10, 1e10, 1.]);
+  assert_eq!(decoded.convert::<f32>().data, depth.data);
+  // 大端，第一行是最下面一行
+  let mut be = b"PF\n1 2\n1\n".to_vec();
+  for v in [0.5f32, 1., 2., 0., 0., 0.] {
+    be.extend(v.to_be_bytes());
+  }
+  assert_eq!(
+    PFM.decode_float(&be).unwrap().pixel(0, 0),
+    [0.5, 1., 2., 1.]
+  );
+  assert_eq!(
+    rgba(PFM.decode(be).unwrap().get_rgba(0, 0)),
+    [128, 255, 255, 255]
+  );
+
+  // 头中的大小与数据不符时返回错误，不按头分配内存
+  for data in [
+    &b"P6\n4294967295 4294967295\n255\n\0\0\0"[..],
+    b"P3\n100000 100000\n255\n1 2 3\n",
+    b"Pf\n100000 100000\n-1.0\n\0\0\0\0",
+  ] {
+    assert!(PPM.decode(data.to_vec()).is_err());
+  }
+}
@@ -0,0 +1,114 @@
+// 物体拾取：光栅化时把每个像素上可见的 (draw id, 面序号) 写入 ID 缓冲，
+// 之后可以查询鼠标下的模型和面，并由深度重建世界坐标。
+use crate::{depth::DepthTarget, geometry::Vec3, mat::Mat4, pipeline2::Viewport};
+
+// 图元的 ID：draw 由 RenderOptions::draw_id 指定，face 是面在模型中的序号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrimitiveId {
+  pub draw: u32,
+  pub face: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pick {
+  pub id: PrimitiveId,
+  // 深度缓冲中的深度
+  pub depth: f32,
+  // 世界坐标
+  pub pos: Vec3<f32>,
+}
+
+// 与深度缓冲一起使用的 ID 附件，index 为 y * width + x
+pub struct IdBuffer {
+  pub width: u32,
+  pub height: u32,
+  data: Vec<Option<PrimitiveId>>,
+}
+impl IdBuffer {
+  pub fn new(width: u32, height: u32) -> IdBuffer {
+    IdBuffer {
+      width,
+      height,
+      data: vec![None; (width * height) as usize],
+    }
+  }
+  pub fn clear(&mut self) {
+    self.data.fill(None);
+  }
+  pub(crate) fn set(&mut self, index: usize, id: PrimitiveId) {
+    self.data[index] = Some(id);
+  }
+  // (x,y) 与光栅化的像素坐标相同，y 轴向上
+  pub fn get(&self, x: u32, y: u32) -> Option<PrimitiveId> {
+    if x >= self.width || y >= self.height {
+      return None;
+    }
+    self.data[(y * self.width + x) as usize]
+  }
+  // 查询像素上的 ID、深度和世界坐标。
+  // viewport 为渲染时使用的视口，inv_view_projection 为投影和视图矩阵乘积的逆
+  pub fn pick<D: DepthTarget + ?Sized>(
+    &self,
+    depth_buff: &D,
+    viewport: &Viewport,
+    inv_view_projection: &Mat4,
+    x: u32,
+    y: u32,
+  ) -> Option<Pick> {
+    let id = self.get(x, y)?;
+    let depth = depth_buff.depth((y * self.width + x) as usize);
+    let ndc = &viewport.matrix().invert() * &Vec3::new(x as f32, y as f32, depth);
+    Some(Pick {
+      id,
+      depth,
+      pos: inv_view_projection * &ndc,
+    })
+  }
+}
+
+#[test]
+fn test_pick() {
+  use crate::{
+    geometry::Vec4,
+    image::PixImage,
+    model::Model,
+    pipeline::Fragment,
+    pipeline2::{render_with_ids, FragmentInfo, RenderOptions, Shader},
+    shape::Plane,
+  };
+  struct FlatShader;
+  impl Shader<Plane> for FlatShader {
+    fn vertext(&mut self, model: &Plane, face: usize, nth_vert: usize) -> Vec4<f32> {
+      Vec4::from_point(&(model.vert(face, nth_vert) * 0.5))
+    }
+    fn fragment(&self, info: FragmentInfo) -> Fragment {
+      Fragment::Color(Vec3::new(info.primitive_id.face as f32, 0., 0.))
+    }
+  }
+  let mut img = PixImage::new(16, 16);
+  let mut depth = vec![f32::MIN; 16 * 16];
+  let mut ids = IdBuffer::new(16, 16);
+  let options = RenderOptions::new().draw_id(7);
+  render_with_ids(
+    &mut img,
+    &mut depth,
+    &mut ids,
+    &mut FlatShader,
+    &Plane::new(),
+    &options,
+  );
+  assert_eq!(ids.get(0, 0), None);
+  // 左上和右下两个三角形
+  assert_eq!(ids.get(5, 10), Some(PrimitiveId { draw: 7, face: 0 }));
+  assert_eq!(ids.get(10, 5), Some(PrimitiveId { draw: 7, face: 1 }));
+  let pick = ids
+    .pick(
+      &depth,
+      &Viewport::new(0, 0, 16, 16),
+      &Mat4::identity(),
+      10,
+      8,
+    )
+    .unwrap();
+  assert!((pick.pos.x - 0.25).abs() < 1e-5 && pick.pos.y.abs() < 1e-5);
+}
@@ -1,19 +1,22 @@
 use crate::{
+  bounds::{Aabb, Frustum},
+  depth::{DepthCompare, DepthTarget},
   geometry::{Vec3, Vec4},
   image::Image,
-  model::Object,
+  mat::Mat4,
+  picking::{IdBuffer, PrimitiveId},
   pipeline::Fragment,
 };
 
 pub struct FragmentInfo {
-  width: f32,
-  height: f32,
+  viewport: Viewport,
   pub z: [f32; 3],
   pub vertices: [Vec3<f32>; 3],
   // 屏幕空间内片元坐标
   pub pos: Vec3<f32>,
   // 屏幕空间质心坐标
   pub bar: Vec3<f32>,
+  pub primitive_id: PrimitiveId,
 }
 impl FragmentInfo {
   pub fn barycentric_interpolate(&self, props: &[Vec3<f32>; 3]) -> Vec3<f32> {
@@ -36,15 +39,181 @@ impl FragmentInfo {
     let y = self.pos.y;
     self.barycentric(x, y)
   }
+  // 片元在标准立方体中的坐标，深度范围为空(near == far)时 z 为 0
   pub fn coordinate(&self) -> Vec3<f32> {
+    let vp = &self.viewport;
+    let depth = vp.far - vp.near;
     Vec3::new(
-      self.pos.x / self.width * 2. - 1.,
-      self.pos.y / self.height * 2. - 1.,
-      self.pos.z,
+      (self.pos.x - vp.x as f32) / vp.width as f32 * 2. - 1.,
+      (self.pos.y - vp.y as f32) / vp.height as f32 * 2. - 1.,
+      if depth == 0. {
+        0.
+      } else {
+        (self.pos.z - vp.near) / depth * 2. - 1.
+      },
     )
   }
 }
 
+// 图像上的矩形区域，单位为像素，(x,y) 为左下角
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+impl Rect {
+  pub fn new(x: u32, y: u32, width: u32, height: u32) -> Rect {
+    Rect {
+      x,
+      y,
+      width,
+      height,
+    }
+  }
+  pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+    let x = self.x.max(other.x);
+    let y = self.y.max(other.y);
+    let right = self
+      .x
+      .saturating_add(self.width)
+      .min(other.x.saturating_add(other.width));
+    let top = self
+      .y
+      .saturating_add(self.height)
+      .min(other.y.saturating_add(other.height));
+    if x < right && y < top {
+      Some(Rect::new(x, y, right - x, top - y))
+    } else {
+      None
+    }
+  }
+}
+
+#[test]
+fn test_rect_intersect() {
+  let a = Rect::new(0, 0, 100, 100);
+  assert_eq!(
+    a.intersect(&Rect::new(50, 60, 100, 100)),
+    Some(Rect::new(50, 60, 50, 40))
+  );
+  assert_eq!(a.intersect(&Rect::new(100, 0, 10, 10)), None);
+  // 右上角超出 u32 时截断，不溢出
+  let huge = Rect::new(10, 10, u32::MAX, u32::MAX);
+  assert_eq!(a.intersect(&huge), Some(Rect::new(10, 10, 90, 90)));
+  assert_eq!(
+    huge.intersect(&huge),
+    Some(Rect::new(10, 10, u32::MAX - 10, u32::MAX - 10))
+  );
+}
+
+#[test]
+fn test_fragment_coordinate() {
+  let info = |viewport: Viewport| FragmentInfo {
+    viewport,
+    z: [1.; 3],
+    vertices: [Vec3::default(); 3],
+    pos: Vec3::new(30., 10., 0.5),
+    bar: Vec3::default(),
+    primitive_id: PrimitiveId::default(),
+  };
+  let vp = Viewport::new(20, 0, 20, 40).depth_range(0., 1.);
+  let coord = info(vp).coordinate();
+  assert_eq!((coord.x, coord.y, coord.z), (0., -0.5, 0.));
+  assert_eq!(info(vp.depth_range(0.5, 0.5)).coordinate().z, 0.);
+}
+
+// 视口：标准立方体映射到图像上的区域，以及深度范围
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+  pub near: f32,
+  pub far: f32,
+}
+impl Viewport {
+  // 默认深度范围为 [-1,1]，即不改变 z
+  pub fn new(x: u32, y: u32, width: u32, height: u32) -> Viewport {
+    Viewport {
+      x,
+      y,
+      width,
+      height,
+      near: -1.,
+      far: 1.,
+    }
+  }
+  pub fn depth_range(mut self, near: f32, far: f32) -> Viewport {
+    self.near = near;
+    self.far = far;
+    self
+  }
+  pub fn rect(&self) -> Rect {
+    Rect::new(self.x, self.y, self.width, self.height)
+  }
+  pub fn matrix(&self) -> Mat4 {
+    crate::transform::viewport_rect(
+      self.x as f32,
+      self.y as f32,
+      self.width as f32,
+      self.height as f32,
+      self.near,
+      self.far,
+    )
+  }
+}
+
+pub struct RenderOptions {
+  pub super_sampling: u32,
+  // 为 None 时使用整张图像
+  pub viewport: Option<Viewport>,
+  // 裁剪矩形，矩形外的像素不会被光栅化
+  pub scissor: Option<Rect>,
+  // 默认越大越近
+  pub depth_compare: DepthCompare,
+  // 写入 ID 缓冲的 draw id，用来区分同一帧中的不同模型
+  pub draw_id: u32,
+}
+impl Default for RenderOptions {
+  fn default() -> Self {
+    RenderOptions::new()
+  }
+}
+impl RenderOptions {
+  pub fn new() -> RenderOptions {
+    RenderOptions {
+      super_sampling: 0,
+      viewport: None,
+      scissor: None,
+      depth_compare: DepthCompare::Greater,
+      draw_id: 0,
+    }
+  }
+  pub fn super_sampling(mut self, m: u32) -> RenderOptions {
+    self.super_sampling = m;
+    self
+  }
+  pub fn viewport(mut self, viewport: Viewport) -> RenderOptions {
+    self.viewport = Some(viewport);
+    self
+  }
+  pub fn scissor(mut self, scissor: Rect) -> RenderOptions {
+    self.scissor = Some(scissor);
+    self
+  }
+  pub fn depth_compare(mut self, compare: DepthCompare) -> RenderOptions {
+    self.depth_compare = compare;
+    self
+  }
+  pub fn draw_id(mut self, id: u32) -> RenderOptions {
+    self.draw_id = id;
+    self
+  }
+}
+
 pub trait Shader<M: crate::model::Model> {
   // 计算顶点在屏幕（渲染结果图像）上的位置
   fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32>;
@@ -84,51 +253,215 @@ fn test_supper_sample_offsets() {
   println!("{:?}", offsets)
 }
 
-fn draw_triangle<M: crate::model::Model, S: Shader<M>, I: Image>(
+// 子像素精度：屏幕坐标吸附到 1/256 像素的网格上
+const SUB_PIXEL_BITS: u32 = 8;
+const SUB_PIXEL: i64 = 1 << SUB_PIXEL_BITS;
+// 限制定点坐标的范围(约 ±2^21 像素)：坐标差不超过 2^30，边函数中两个乘积都不超过 2^60，
+// 它们的差不会溢出 i64。靠近或在相机后面的顶点没有裁剪，会被截断到这个范围
+const FIXED_LIMIT: f64 = (1i64 << 29) as f64;
+
+fn to_fixed(v: f32) -> i64 {
+  (v as f64 * SUB_PIXEL as f64)
+    .round()
+    .clamp(-FIXED_LIMIT, FIXED_LIMIT) as i64
+}
+
+// 定点数表示的边函数，值大于等于 0 表示点在边的内侧
+#[derive(Debug, Clone, Copy)]
+struct EdgeFn {
+  x0: i64,
+  y0: i64,
+  dx: i64,
+  dy: i64,
+  // 左上填充规则：不是上边或左边的边不包含边上的点
+  bias: i64,
+}
+impl EdgeFn {
+  fn new(from: (i64, i64), to: (i64, i64)) -> EdgeFn {
+    let dx = to.0 - from.0;
+    let dy = to.1 - from.1;
+    // y 轴向上、三角形逆时针时，上边从右往左，左边从上往下
+    let top_left = (dy == 0 && dx < 0) || dy < 0;
+    EdgeFn {
+      x0: from.0,
+      y0: from.1,
+      dx,
+      dy,
+      bias: if top_left { 0 } else { -1 },
+    }
+  }
+  fn eval(&self, x: i64, y: i64) -> i64 {
+    self.dx * (y - self.y0) - self.dy * (x - self.x0)
+  }
+}
+
+// 三角形的定点数设置，顶点吸附到子像素网格后用整数计算覆盖
+struct TriangleSetup {
+  verts: [(i64, i64); 3],
+  // edges[i] 是顶点 i 的对边，在顶点 i 处的值等于 area
+  edges: [EdgeFn; 3],
+  // 两倍的有向面积，总是为正
+  area: i64,
+}
+impl TriangleSetup {
+  fn new(a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>) -> Option<TriangleSetup> {
+    let pa = (to_fixed(a.x), to_fixed(a.y));
+    let pb = (to_fixed(b.x), to_fixed(b.y));
+    let pc = (to_fixed(c.x), to_fixed(c.y));
+    let area = EdgeFn::new(pa, pb).eval(pc.0, pc.1);
+    if area == 0 {
+      return None;
+    }
+    // 顺时针的三角形反转边的方向，使内侧总是为正
+    let edges = if area > 0 {
+      [EdgeFn::new(pb, pc), EdgeFn::new(pc, pa), EdgeFn::new(pa, pb)]
+    } else {
+      [EdgeFn::new(pc, pb), EdgeFn::new(pa, pc), EdgeFn::new(pb, pa)]
+    };
+    Some(TriangleSetup {
+      verts: [pa, pb, pc],
+      edges,
+      area: area.abs(),
+    })
+  }
+  // 包围盒覆盖的像素范围 (min_x, min_y, max_x, max_y)，像素采样点在整数坐标上
+  fn bounds(&self) -> (i64, i64, i64, i64) {
+    let [a, b, c] = self.verts;
+    let min_x = a.0.min(b.0).min(c.0);
+    let max_x = a.0.max(b.0).max(c.0);
+    let min_y = a.1.min(b.1).min(c.1);
+    let max_y = a.1.max(b.1).max(c.1);
+    (
+      (min_x + SUB_PIXEL - 1).div_euclid(SUB_PIXEL),
+      (min_y + SUB_PIXEL - 1).div_euclid(SUB_PIXEL),
+      max_x.div_euclid(SUB_PIXEL),
+      max_y.div_euclid(SUB_PIXEL),
+    )
+  }
+  // 屏幕空间重心坐标，点可以在三角形外
+  fn barycentric(&self, x: i64, y: i64) -> (f32, f32, f32) {
+    let area = self.area as f64;
+    let [e0, e1, e2] = self.edges;
+    (
+      (e0.eval(x, y) as f64 / area) as f32,
+      (e1.eval(x, y) as f64 / area) as f32,
+      (e2.eval(x, y) as f64 / area) as f32,
+    )
+  }
+  // 点在三角形内时返回重心坐标
+  fn cover(&self, x: i64, y: i64) -> Option<(f32, f32, f32)> {
+    if self.edges.iter().any(|e| e.eval(x, y) + e.bias < 0) {
+      return None;
+    }
+    Some(self.barycentric(x, y))
+  }
+}
+
+#[test]
+fn test_fill_rule() {
+  // 两个共享对角线的三角形，对角线上的像素只被其中一个覆盖，
+  // 底边 y=0 不是上边或左边，两个三角形都不包含
+  let a = Vec3::new(0., 0., 0.);
+  let b = Vec3::new(4., 0., 0.);
+  let c = Vec3::new(4., 4., 0.);
+  let d = Vec3::new(0., 4., 0.);
+  let t1 = TriangleSetup::new(a, b, c).unwrap();
+  let t2 = TriangleSetup::new(a, c, d).unwrap();
+  assert!(t1.cover(SUB_PIXEL, 0).is_none());
+  for y in 1..4 {
+    for x in 0..4 {
+      let (x, y) = (x * SUB_PIXEL, y * SUB_PIXEL);
+      assert!(t1.cover(x, y).is_some() != t2.cover(x, y).is_some());
+    }
+  }
+  // 负坐标的包围盒
+  let t = TriangleSetup::new(Vec3::new(-10.5, -3., 0.), b, c).unwrap();
+  assert_eq!(t.bounds(), (-10, -3, 4, 4));
+  // 被截断的巨大坐标不会使边函数溢出
+  let far = Vec3::new(f32::MAX, -1e30, 0.);
+  let t = TriangleSetup::new(far, Vec3::new(-1e30, 1e30, 0.), c).unwrap();
+  let (min_x, min_y, ..) = t.bounds();
+  t.cover(min_x * SUB_PIXEL, min_y * SUB_PIXEL);
+  t.cover(0, 0);
+}
+
+// 一次 render 调用中对所有三角形都相同的状态
+struct RasterState {
+  viewport: Viewport,
+  vp: Mat4,
+  // 图像、视口和裁剪矩形的交集
+  clip: Rect,
+  super_sampling: Option<Vec<(f32, f32)>>,
+  depth_compare: DepthCompare,
+  draw_id: u32,
+}
+
+fn draw_triangle<M: crate::model::Model, S: Shader<M>, I: Image + ?Sized, D: DepthTarget + ?Sized>(
   img: &mut I,
-  depth_buff: &mut Vec<f32>,
+  depth_buff: &mut D,
+  mut ids: Option<&mut IdBuffer>,
   // 三角形的三个顶点，假设坐标在[-1,1]
-  a: Vec4<f32>,
-  b: Vec4<f32>,
-  c: Vec4<f32>,
+  [a, b, c]: [Vec4<f32>; 3],
+  face: usize,
   shader: &mut S,
-  super_sampling: &Option<Vec<(f32, f32)>>,
+  state: &RasterState,
 ) {
   let wa = a.w;
   let wb = b.w;
   let wc = c.w;
-  let vp = crate::transform::viewport(img.width() as f32, img.height() as f32);
-  let a = &vp * &a.to_3d_point();
-  let b = &vp * &b.to_3d_point();
-  let c = &vp * &c.to_3d_point();
   // 映射标准立方体到屏幕空间
+  let a = &state.vp * &a.to_3d_point();
+  let b = &state.vp * &b.to_3d_point();
+  let c = &state.vp * &c.to_3d_point();
+  let viewport = state.viewport;
+  let clip = state.clip;
+  let compare = state.depth_compare;
+  let primitive_id = PrimitiveId {
+    draw: state.draw_id,
+    face: face as u32,
+  };
 
-  let min_x = a.x.min(b.x).min(c.x) as u32;
-  let max_x = a.x.max(b.x).max(c.x).min((img.width() - 1) as f32) as u32;
-  let min_y = a.y.min(b.y).min(c.y) as u32;
-  let max_y = a.y.max(b.y).max(c.y).min((img.height() - 1) as f32) as u32;
-  let width = img.width() as f32;
-  let height = img.height() as f32;
+  let Some(tri) = TriangleSetup::new(a, b, c) else {
+    // 退化三角形
+    return;
+  };
+  // 超采样时子像素可能落在像素之外，包围盒各向外扩一个像素
+  let margin = if state.super_sampling.is_some() { 1 } else { 0 };
+  let (min_x, min_y, max_x, max_y) = tri.bounds();
+  let min_x = (min_x - margin).max(clip.x as i64);
+  let max_x = (max_x + margin).min((clip.x + clip.width) as i64 - 1);
+  let min_y = (min_y - margin).max(clip.y as i64);
+  let max_y = (max_y + margin).min((clip.y + clip.height) as i64 - 1);
+  if min_x > max_x || min_y > max_y {
+    return;
+  }
+  let (min_x, max_x, min_y, max_y) = (min_x as u32, max_x as u32, min_y as u32, max_y as u32);
+  // 允许深度略微超出深度范围
+  let slack = 0.05 * (viewport.far - viewport.near).abs();
+  let (z_min, z_max) = (
+    viewport.near.min(viewport.far) - slack,
+    viewport.near.max(viewport.far) + slack,
+  );
 
-  if let Some(sub_pix_offset) = super_sampling {
+  if let Some(sub_pix_offset) = &state.super_sampling {
     for y in min_y..=max_y {
       for x in min_x..=max_x {
         let mut color = Vec4::default();
         let mut cnt = 0;
         for (dx, dy) in sub_pix_offset {
-          let (alpha, beta, gamma) = barycentric(a, b, c, (x as f32) + dx, (y as f32) + dy);
-          if alpha < 0. || beta < 0. || gamma < 0. {
+          let Some((alpha, beta, gamma)) = tri.cover(to_fixed(x as f32 + dx), to_fixed(y as f32 + dy))
+          else {
             continue;
-          }
+          };
           let p = a * alpha + b * beta + c * gamma;
           let k = 1. / wa * alpha + 1. / wb * beta + 1. / wc * gamma;
           let info = FragmentInfo {
-            width,
-            height,
+            viewport,
             z: [wa, wb, wc],
             vertices: [a, b, c],
             pos: p,
             bar: Vec3::new(alpha / wa / k, beta / wb / k, gamma / wc / k),
+            primitive_id,
           };
           match shader.fragment(info) {
             Fragment::Color(c) => {
@@ -143,11 +476,14 @@ fn draw_triangle<M: crate::model::Model, S: Shader<M>, I: Image>(
           }
         }
         if cnt > 0 {
-          let (alpha, beta, gamma) = barycentric(a, b, c, x as f32, y as f32);
+          let (alpha, beta, gamma) = tri.barycentric(to_fixed(x as f32), to_fixed(y as f32));
           let p = a * alpha + b * beta + c * gamma;
           let index = (y * img.width() + x) as usize;
-          if p.z > depth_buff[index] {
-            depth_buff[index] = p.z;
+          if depth_buff.test_depth(index, p.z, compare) {
+            depth_buff.set_depth(index, p.z);
+            if let Some(ids) = ids.as_deref_mut() {
+              ids.set(index, primitive_id);
+            }
             img.blending(x, y, color * (1. / cnt as f32))
           }
         }
@@ -156,34 +492,36 @@ fn draw_triangle<M: crate::model::Model, S: Shader<M>, I: Image>(
   } else {
     for y in min_y..=max_y {
       for x in min_x..=max_x {
-        let (alpha, beta, gamma) = barycentric(a, b, c, x as f32, y as f32);
-        if alpha < 0. || beta < 0. || gamma < 0. {
+        let Some((alpha, beta, gamma)) = tri.cover(to_fixed(x as f32), to_fixed(y as f32)) else {
           continue;
-        }
+        };
         let p = a * alpha + b * beta + c * gamma;
         let index = (y * img.width() + x) as usize;
         let k = 1. / wa * alpha + 1. / wb * beta + 1. / wc * gamma;
-        if p.z > depth_buff[index] && p.z <= 1.1 && p.z >= -1.1 {
+        if depth_buff.test_depth(index, p.z, compare) && p.z <= z_max && p.z >= z_min {
           // 通过Fragment shader 计算每个像素的颜色
 
           let info = FragmentInfo {
             z: [wa, wb, wc],
             vertices: [a, b, c],
             pos: p,
-            width,
-            height,
+            viewport,
             bar: Vec3::new(alpha / wa / k, beta / wb / k, gamma / wc / k),
+            primitive_id,
           };
           match shader.fragment(info) {
             Fragment::Color(c) => {
-              depth_buff[index] = p.z;
+              depth_buff.set_depth(index, p.z);
               img.set_rgb(x, y, c);
             }
             Fragment::Rgba(c) => {
-              depth_buff[index] = p.z;
+              depth_buff.set_depth(index, p.z);
               img.blending(x, y, c);
             }
-            Fragment::Discard => {}
+            Fragment::Discard => continue,
+          }
+          if let Some(ids) = ids.as_deref_mut() {
+            ids.set(index, primitive_id);
           }
         }
       }
@@ -193,22 +531,109 @@ fn draw_triangle<M: crate::model::Model, S: Shader<M>, I: Image>(
 
 pub fn render<S: Shader<M>, I: Image, M: crate::model::Model>(
   img: &mut I,
-  depth_buff: &mut Vec<f32>,
+  depth_buff: &mut [f32],
   shader: &mut S,
   model: &M,
   super_sampling: u32,
 ) {
-  let super_sampling = if super_sampling > 1 {
-    Some(super_sampling_offsets(super_sampling))
+  render_with(
+    img,
+    depth_buff,
+    shader,
+    model,
+    &RenderOptions::new().super_sampling(super_sampling),
+  )
+}
+
+pub fn render_with<S: Shader<M>, I: Image + ?Sized, M: crate::model::Model, D: DepthTarget + ?Sized>(
+  img: &mut I,
+  depth_buff: &mut D,
+  shader: &mut S,
+  model: &M,
+  options: &RenderOptions,
+) {
+  rasterize(img, depth_buff, None, shader, model, options)
+}
+
+// 模型的包围盒完全在视锥体之外时跳过整个模型，返回是否绘制。
+// mvp 为模型空间到裁剪空间的变换，应与着色器中使用的一致
+pub fn render_culled<S: Shader<M>, I: Image + ?Sized, M: crate::model::Model, D: DepthTarget + ?Sized>(
+  img: &mut I,
+  depth_buff: &mut D,
+  shader: &mut S,
+  model: &M,
+  bounds: &Aabb,
+  mvp: &Mat4,
+  options: &RenderOptions,
+) -> bool {
+  if !Frustum::new(mvp).intersects_aabb(bounds) {
+    return false;
+  }
+  render_with(img, depth_buff, shader, model, options);
+  true
+}
+
+// 同时把每个像素上可见的图元写入 ID 缓冲，用于拾取。ID 缓冲的大小必须与 img 相同
+pub fn render_with_ids<S: Shader<M>, I: Image + ?Sized, M: crate::model::Model, D: DepthTarget + ?Sized>(
+  img: &mut I,
+  depth_buff: &mut D,
+  ids: &mut IdBuffer,
+  shader: &mut S,
+  model: &M,
+  options: &RenderOptions,
+) {
+  assert_eq!(
+    (ids.width, ids.height),
+    (img.width(), img.height()),
+    "IdBuffer size must match the image"
+  );
+  rasterize(img, depth_buff, Some(ids), shader, model, options)
+}
+
+fn rasterize<S: Shader<M>, I: Image + ?Sized, M: crate::model::Model, D: DepthTarget + ?Sized>(
+  img: &mut I,
+  depth_buff: &mut D,
+  mut ids: Option<&mut IdBuffer>,
+  shader: &mut S,
+  model: &M,
+  options: &RenderOptions,
+) {
+  let super_sampling = if options.super_sampling > 1 {
+    Some(super_sampling_offsets(options.super_sampling))
   } else {
     None
   };
+  let viewport = options
+    .viewport
+    .unwrap_or_else(|| Viewport::new(0, 0, img.width(), img.height()));
+  let clip = Rect::new(0, 0, img.width(), img.height()).intersect(&viewport.rect());
+  let clip = match (clip, options.scissor) {
+    (Some(clip), Some(scissor)) => clip.intersect(&scissor),
+    (clip, _) => clip,
+  };
+  let Some(clip) = clip else {
+    return;
+  };
+  // 未指定视口时沿用原来的视口矩阵
+  let vp = if options.viewport.is_some() {
+    viewport.matrix()
+  } else {
+    crate::transform::viewport(img.width() as f32, img.height() as f32)
+  };
+  let state = RasterState {
+    viewport,
+    vp,
+    clip,
+    super_sampling,
+    depth_compare: options.depth_compare,
+    draw_id: options.draw_id,
+  };
 
   for n in 0..model.face_count() {
     // 通过顶点Shader 计算顶点的位置
     let a = shader.vertext(model, n, 0);
     let b = shader.vertext(model, n, 1);
     let c = shader.vertext(model, n, 2);
-    draw_triangle(img, depth_buff, a, b, c, shader, &super_sampling)
+    draw_triangle(img, depth_buff, ids.as_deref_mut(), [a, b, c], n, shader, &state)
   }
 }
@@ -0,0 +1,218 @@
+// 像素格式：Image 的默认实现是 RGBA8，这里的图片按像素类型保存数据，
+// 通过 Image::set_rgba/get_rgbaf 读写浮点颜色时不经过 8 位量化，
+// 所以光栅化器可以直接渲染到浮点的颜色缓冲、深度纹理和 16 位高度图上。
+use crate::{
+  geometry::Vec4,
+  image::{Image, ImageOriginPos, PixImage},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+  R8,
+  Rgba8,
+  Rgba16,
+  R32F,
+  Rgba32F,
+}
+impl PixelFormat {
+  pub fn channels(&self) -> usize {
+    match self {
+      PixelFormat::R8 | PixelFormat::R32F => 1,
+      PixelFormat::Rgba8 | PixelFormat::Rgba16 | PixelFormat::Rgba32F => 4,
+    }
+  }
+  pub fn is_float(&self) -> bool {
+    matches!(self, PixelFormat::R32F | PixelFormat::Rgba32F)
+  }
+}
+
+// 单通道的像素读取为灰色，写入时 r、g、b 相同则取 r，否则取亮度
+pub trait Pixel: Copy + Default {
+  const FORMAT: PixelFormat;
+  fn to_rgba(self) -> Vec4<f32>;
+  fn from_rgba(c: Vec4<f32>) -> Self;
+}
+
+pub(crate) fn gray(c: Vec4<f32>) -> f32 {
+  if c.x == c.y && c.y == c.z {
+    c.x
+  } else {
+    0.299 * c.x + 0.587 * c.y + 0.114 * c.z
+  }
+}
+fn unorm<const MAX: u32>(v: f32) -> u32 {
+  (v.clamp(0., 1.) * MAX as f32).round() as u32
+}
+
+impl Pixel for u8 {
+  const FORMAT: PixelFormat = PixelFormat::R8;
+  fn to_rgba(self) -> Vec4<f32> {
+    let v = self as f32 / 255.;
+    Vec4::new(v, v, v, 1.)
+  }
+  fn from_rgba(c: Vec4<f32>) -> u8 {
+    unorm::<255>(gray(c)) as u8
+  }
+}
+impl Pixel for f32 {
+  const FORMAT: PixelFormat = PixelFormat::R32F;
+  fn to_rgba(self) -> Vec4<f32> {
+    Vec4::new(self, self, self, 1.)
+  }
+  fn from_rgba(c: Vec4<f32>) -> f32 {
+    gray(c)
+  }
+}
+impl Pixel for [u16; 4] {
+  const FORMAT: PixelFormat = PixelFormat::Rgba16;
+  fn to_rgba(self) -> Vec4<f32> {
+    let [r, g, b, a] = self.map(|v| v as f32 / 65535.);
+    Vec4::new(r, g, b, a)
+  }
+  fn from_rgba(c: Vec4<f32>) -> [u16; 4] {
+    [c.x, c.y, c.z, c.w].map(|v| unorm::<65535>(v) as u16)
+  }
+}
+impl Pixel for [f32; 4] {
+  const FORMAT: PixelFormat = PixelFormat::Rgba32F;
+  fn to_rgba(self) -> Vec4<f32> {
+    Vec4::new(self[0], self[1], self[2], self[3])
+  }
+  fn from_rgba(c: Vec4<f32>) -> [f32; 4] {
+    [c.x, c.y, c.z, c.w]
+  }
+}
+
+// 浮点图片用 ImageF32 和 R32F，与深度缓冲一样，第一行是图片的最下面一行，像素 (x, y) 是 data[y * width + x]
+#[derive(Debug, Clone)]
+pub struct TypedImage<P: Pixel> {
+  pub width: u32,
+  pub height: u32,
+  pub data: Vec<P>,
+}
+pub type ImageF32 = TypedImage<[f32; 4]>;
+pub type RGBA16 = TypedImage<[u16; 4]>;
+pub type R32F = TypedImage<f32>;
+pub type R8 = TypedImage<u8>;
+
+impl<P: Pixel> TypedImage<P> {
+  pub fn new(width: u32, height: u32) -> TypedImage<P> {
+    TypedImage::from_data(
+      vec![P::default(); width as usize * height as usize],
+      width,
+      height,
+    )
+  }
+  pub fn from_data(data: Vec<P>, width: u32, height: u32) -> TypedImage<P> {
+    assert_eq!(data.len(), width as usize * height as usize);
+    TypedImage {
+      width,
+      height,
+      data,
+    }
+  }
+  // 从任意格式的图片转换，浮点格式之间的转换不会截断到 [0,1]
+  pub fn from_image<I: Image + ?Sized>(img: &I) -> TypedImage<P> {
+    let mut out = TypedImage::new(img.width(), img.height());
+    for y in 0..img.height() {
+      for x in 0..img.width() {
+        out.set_pixel(x, y, P::from_rgba(img.get_rgbaf(x, y)));
+      }
+    }
+    out
+  }
+  pub fn convert<Q: Pixel>(&self) -> TypedImage<Q> {
+    TypedImage::from_image(self)
+  }
+  pub fn to_pix_image(&self) -> PixImage {
+    let mut img = PixImage::new(self.width, self.height);
+    for y in 0..self.height {
+      for x in 0..self.width {
+        img.set_rgba32(x, y, self.get_rgba(x, y));
+      }
+    }
+    img
+  }
+  pub fn pixel(&self, x: u32, y: u32) -> P {
+    self.data[self.index(x, y)]
+  }
+  pub fn set_pixel(&mut self, x: u32, y: u32, p: P) {
+    if x >= self.width || y >= self.height {
+      return;
+    }
+    let i = self.index(x, y);
+    self.data[i] = p;
+  }
+}
+impl<P: Pixel> From<&PixImage> for TypedImage<P> {
+  fn from(img: &PixImage) -> TypedImage<P> {
+    TypedImage::from_image(img)
+  }
+}
+impl<P: Pixel> From<&TypedImage<P>> for PixImage {
+  fn from(img: &TypedImage<P>) -> PixImage {
+    img.to_pix_image()
+  }
+}
+impl<P: Pixel> Image for TypedImage<P> {
+  fn width(&self) -> u32 {
+    self.width
+  }
+  fn height(&self) -> u32 {
+    self.height
+  }
+  fn image_origin(&self) -> ImageOriginPos {
+    ImageOriginPos::LeftTop
+  }
+  fn pixel_format(&self) -> PixelFormat {
+    P::FORMAT
+  }
+  fn set_rgba(&mut self, x: u32, y: u32, color: Vec4<f32>) {
+    self.set_pixel(x, y, P::from_rgba(color));
+  }
+  fn get_rgbaf(&self, x: u32, y: u32) -> Vec4<f32> {
+    self.pixel(x, y).to_rgba()
+  }
+  fn set_rgba32(&mut self, x: u32, y: u32, c: Vec4<u8>) {
+    let c = Vec4::new(c.x as f32, c.y as f32, c.z as f32, c.w as f32);
+    self.set_rgba(x, y, c * (1. / 255.));
+  }
+  fn get_rgba(&self, x: u32, y: u32) -> Vec4<u8> {
+    let c = self.get_rgbaf(x, y);
+    let to_u8 = |v: f32| unorm::<255>(v) as u8;
+    Vec4::new(to_u8(c.x), to_u8(c.y), to_u8(c.z), to_u8(c.w))
+  }
+}
+
+#[test]
+fn test_pixel_formats() {
+  let mut img = PixImage::new(2, 1);
+  img.set_rgba32(0, 0, Vec4::new(10, 200, 30, 128));
+  img.set_rgba32(1, 0, Vec4::new(90, 90, 90, 255));
+  let rgba = |c: Vec4<u8>| [c.x, c.y, c.z, c.w];
+
+  // 8 位和 16 位、浮点之间无损往返
+  let f32: ImageF32 = (&img).into();
+  let u16: RGBA16 = f32.convert();
+  assert_eq!(u16.pixel(0, 0), [10 * 257, 200 * 257, 30 * 257, 128 * 257]);
+  assert_eq!(
+    rgba(PixImage::from(&u16).get_rgba(0, 0)),
+    [10, 200, 30, 128]
+  );
+  let r8: R8 = (&img).into();
+  assert_eq!((r8.pixel(0, 0), r8.pixel(1, 0)), (124, 90));
+  assert_eq!(r8.pixel_format().channels(), 1);
+
+  // 浮点格式保留超出 [0,1] 的值，转换为 8 位时截断
+  let mut hdr = ImageF32::new(1, 1);
+  hdr.set_rgb(0, 0, crate::geometry::Vec3::new(4., -1., 0.5));
+  assert_eq!(hdr.pixel(0, 0), [4., -1., 0.5, 1.]);
+  assert_eq!(rgba(hdr.get_rgba(0, 0)), [255, 0, 128, 255]);
+  let depth: R32F = hdr.convert();
+  assert!((depth.pixel(0, 0) - (1.196 - 0.587 + 0.057)).abs() < 1e-6);
+
+  // 像素 (x, y) 是 data[y * width + x]
+  let mut depth = R32F::new(2, 2);
+  depth.set_pixel(0, 1, 8.);
+  assert_eq!(depth.data, [0., 0., 8., 0.]);
+}
@@ -0,0 +1,601 @@
+// PNG 编解码，压缩和解压使用 zlib 模块中自己实现的 deflate。
+// 编码：每一行在 5 种滤波器中选择滤波后字节(按有符号数)绝对值之和最小的一种。
+// 解码：支持所有颜色类型和位深(调色板、灰度、灰度+透明度、RGB、RGBA，1~16 位)、tRNS 和 Adam7 隔行扫描，
+// 16 位的通道缩放到 8 位。
+use crate::{
+  error::{Error, Result},
+  image::{luma, Image, ImageOriginPos, PixImage},
+  image_decoder::Decoder,
+  image_encoder::Encoder,
+  zlib::{self, crc32, crc32_update},
+};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+// 每个 IDAT 块的最大长度
+const IDAT_SIZE: usize = 1 << 16;
+// Adam7 每一遍的起点和间隔：(x0, y0, dx, dy)
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+  (0, 0, 8, 8),
+  (4, 0, 8, 8),
+  (0, 4, 4, 8),
+  (2, 0, 4, 4),
+  (0, 2, 2, 4),
+  (1, 0, 2, 2),
+  (0, 1, 1, 2),
+];
+
+// 8 位的颜色类型，值为 IHDR 中的 color type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+  Gray = 0,
+  Rgb = 2,
+  Rgba = 6,
+}
+impl ColorType {
+  fn channels(self) -> usize {
+    match self {
+      ColorType::Gray => 1,
+      ColorType::Rgb => 3,
+      ColorType::Rgba => 4,
+    }
+  }
+}
+
+pub struct PNG {
+  pub color_type: ColorType,
+}
+impl PNG {
+  pub fn new() -> PNG {
+    PNG {
+      color_type: ColorType::Rgba,
+    }
+  }
+  pub fn color_type(mut self, color_type: ColorType) -> PNG {
+    self.color_type = color_type;
+    self
+  }
+}
+impl Default for PNG {
+  fn default() -> PNG {
+    PNG::new()
+  }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  out.extend((data.len() as u32).to_be_bytes());
+  out.extend(kind);
+  out.extend(data);
+  out.extend(crc32_update(crc32(kind), data).to_be_bytes());
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+  let p = a as i16 + b as i16 - c as i16;
+  let (pa, pb, pc) = (
+    (p - a as i16).abs(),
+    (p - b as i16).abs(),
+    (p - c as i16).abs(),
+  );
+  if pa <= pb && pa <= pc {
+    a
+  } else if pb <= pc {
+    b
+  } else {
+    c
+  }
+}
+
+// 滤波器的预测值，a、b、c 分别为左边、上边和左上的字节
+fn predict(filter: u8, a: u8, b: u8, c: u8) -> u8 {
+  match filter {
+    0 => 0,
+    1 => a,
+    2 => b,
+    3 => ((a as u16 + b as u16) / 2) as u8,
+    _ => paeth(a, b, c),
+  }
+}
+
+// 用滤波器 filter 处理一行，prev 为上一行(第一行为全 0)，bpp 为每像素的字节数
+fn filter_row(filter: u8, row: &[u8], prev: &[u8], bpp: usize, out: &mut Vec<u8>) {
+  out.push(filter);
+  for i in 0..row.len() {
+    let a = if i >= bpp { row[i - bpp] } else { 0 };
+    let c = if i >= bpp { prev[i - bpp] } else { 0 };
+    out.push(row[i].wrapping_sub(predict(filter, a, prev[i], c)));
+  }
+}
+
+// filter_row 的逆过程，在 row 上原地还原
+fn unfilter_row(filter: u8, row: &mut [u8], prev: &[u8], bpp: usize) -> Result<()> {
+  if filter > 4 {
+    return Err(Error::format(format!("unknown filter type {}", filter)));
+  }
+  for i in 0..row.len() {
+    let a = if i >= bpp { row[i - bpp] } else { 0 };
+    let c = if i >= bpp { prev[i - bpp] } else { 0 };
+    row[i] = row[i].wrapping_add(predict(filter, a, prev[i], c));
+  }
+  Ok(())
+}
+
+impl Encoder for PNG {
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    let (w, h) = (img.width(), img.height());
+    let bpp = self.color_type.channels();
+    let mut ihdr = vec![];
+    ihdr.extend(w.to_be_bytes());
+    ihdr.extend(h.to_be_bytes());
+    // 位深 8，压缩方式、滤波方式和隔行扫描都为 0
+    ihdr.extend([8, self.color_type as u8, 0, 0, 0]);
+
+    let mut filtered = Vec::with_capacity((w as usize * bpp + 1) * h as usize);
+    let mut prev = vec![0u8; w as usize * bpp];
+    let mut row = Vec::with_capacity(w as usize * bpp);
+    let mut candidate = Vec::with_capacity(w as usize * bpp + 1);
+    let mut best = Vec::with_capacity(w as usize * bpp + 1);
+    // 从最上面一行开始
+    for y in (0..h).rev() {
+      row.clear();
+      for x in 0..w {
+        let c = img.get_rgba(x, y);
+        match self.color_type {
+          ColorType::Gray => row.push(luma(c)),
+          ColorType::Rgb => row.extend([c.x, c.y, c.z]),
+          ColorType::Rgba => row.extend([c.x, c.y, c.z, c.w]),
+        }
+      }
+      let mut best_score = u64::MAX;
+      for filter in 0..5 {
+        candidate.clear();
+        filter_row(filter, &row, &prev, bpp, &mut candidate);
+        let score = candidate[1..]
+          .iter()
+          .map(|&v| (v as i8).unsigned_abs() as u64)
+          .sum();
+        if score < best_score {
+          best_score = score;
+          std::mem::swap(&mut best, &mut candidate);
+        }
+      }
+      filtered.extend_from_slice(&best);
+      std::mem::swap(&mut prev, &mut row);
+    }
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    for idat in zlib::compress(&filtered).chunks(IDAT_SIZE) {
+      write_chunk(&mut out, b"IDAT", idat);
+    }
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+  }
+}
+
+struct Header {
+  width: u32,
+  height: u32,
+  depth: u8,
+  color_type: u8,
+  interlaced: bool,
+}
+impl Header {
+  fn parse(data: &[u8]) -> Result<Header> {
+    if data.len() != 13 {
+      return Err(Error::format("invalid IHDR chunk"));
+    }
+    let header = Header {
+      width: u32::from_be_bytes(data[0..4].try_into().unwrap()),
+      height: u32::from_be_bytes(data[4..8].try_into().unwrap()),
+      depth: data[8],
+      color_type: data[9],
+      interlaced: data[12] == 1,
+    };
+    let depth_ok = match header.color_type {
+      0 => matches!(header.depth, 1 | 2 | 4 | 8 | 16),
+      3 => matches!(header.depth, 1 | 2 | 4 | 8),
+      2 | 4 | 6 => matches!(header.depth, 8 | 16),
+      t => return Err(Error::format(format!("unknown color type {}", t))),
+    };
+    if !depth_ok {
+      return Err(Error::format(format!(
+        "bit depth {} is not allowed for color type {}",
+        header.depth, header.color_type
+      )));
+    }
+    if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+      return Err(Error::format(
+        "unknown compression, filter or interlace method",
+      ));
+    }
+    if header.width == 0 || header.height == 0 {
+      return Err(Error::format("image is empty"));
+    }
+    Ok(header)
+  }
+  fn channels(&self) -> usize {
+    match self.color_type {
+      0 | 3 => 1,
+      4 => 2,
+      2 => 3,
+      _ => 4,
+    }
+  }
+  fn bits_per_pixel(&self) -> usize {
+    self.channels() * self.depth as usize
+  }
+}
+
+// 一行中第 i 个采样的原始值
+fn sample(row: &[u8], i: usize, depth: u8) -> u16 {
+  match depth {
+    8 => row[i] as u16,
+    16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]),
+    _ => {
+      // 低于 8 位时从字节的高位开始排列
+      let bit = i * depth as usize;
+      let shift = 8 - depth as usize - bit % 8;
+      (row[bit / 8] >> shift) as u16 & ((1 << depth) - 1)
+    }
+  }
+}
+
+// 把 depth 位的采样缩放到 8 位
+fn scale(v: u16, depth: u8) -> u8 {
+  let max = (1u32 << depth) - 1;
+  ((v as u32 * 255 + max / 2) / max) as u8
+}
+
+// 所有遍的扫描行加上每行的滤波器类型的总字节数，溢出时返回 None
+fn filtered_size(header: &Header, passes: &[(u32, u32, u32, u32)]) -> Option<usize> {
+  let (w, h) = (header.width, header.height);
+  passes
+    .iter()
+    .filter(|&&(x0, y0, _, _)| x0 < w && y0 < h)
+    .try_fold(0usize, |size, &(x0, y0, dx, dy)| {
+      let (pw, ph) = ((w - x0).div_ceil(dx), (h - y0).div_ceil(dy));
+      let row_len = (pw as usize)
+        .checked_mul(header.bits_per_pixel())?
+        .div_ceil(8);
+      size.checked_add((row_len + 1).checked_mul(ph as usize)?)
+    })
+}
+
+fn decode_png(data: &[u8]) -> Result<PixImage> {
+  if data.len() < 8 || data[..8] != SIGNATURE {
+    return Err(Error::format("missing PNG signature"));
+  }
+  let mut pos = 8;
+  let mut header = None;
+  let mut palette: Vec<[u8; 4]> = vec![];
+  let mut transparency = None;
+  let mut idat = vec![];
+  loop {
+    let len = data
+      .get(pos..pos + 4)
+      .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+      .ok_or_else(|| Error::format("missing IEND chunk"))?;
+    let body = data
+      .get(pos + 4..pos + 8 + len)
+      .ok_or_else(|| Error::format("truncated chunk"))?;
+    let crc = data
+      .get(pos + 8 + len..pos + 12 + len)
+      .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+      .ok_or_else(|| Error::format("truncated chunk"))?;
+    let (kind, content) = body.split_at(4);
+    if crc32(body) != crc {
+      return Err(Error::format(format!(
+        "CRC mismatch in {} chunk",
+        String::from_utf8_lossy(kind)
+      )));
+    }
+    pos += 12 + len;
+    match kind {
+      b"IHDR" => header = Some(Header::parse(content)?),
+      b"PLTE" => {
+        palette = content
+          .chunks_exact(3)
+          .map(|c| [c[0], c[1], c[2], 255])
+          .collect()
+      }
+      b"tRNS" => transparency = Some(content.to_vec()),
+      b"IDAT" => idat.extend_from_slice(content),
+      b"IEND" => break,
+      // 第一个字母大写的是关键块，不认识时无法解码
+      _ if kind[0].is_ascii_uppercase() => {
+        return Err(Error::unsupported(format!(
+          "critical chunk {}",
+          String::from_utf8_lossy(kind)
+        )))
+      }
+      _ => {}
+    }
+  }
+  let header = header.ok_or_else(|| Error::format("missing IHDR chunk"))?;
+  if header.color_type == 3 {
+    if palette.is_empty() {
+      return Err(Error::format("missing PLTE chunk"));
+    }
+    if let Some(alpha) = &transparency {
+      for (entry, &a) in palette.iter_mut().zip(alpha) {
+        entry[3] = a;
+      }
+    }
+  }
+  // 灰度和 RGB 的 tRNS 是一个完全透明的颜色，保存为原始的采样值
+  let transparent: Option<Vec<u16>> = match (header.color_type, &transparency) {
+    (0 | 2, Some(t)) => Some(
+      t.chunks_exact(2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .collect(),
+    ),
+    _ => None,
+  };
+
+  let (w, h) = (header.width, header.height);
+  let depth = header.depth;
+  let channels = header.channels();
+  let bpp = header.bits_per_pixel().div_ceil(8);
+  let passes: &[(u32, u32, u32, u32)] = if header.interlaced {
+    &ADAM7
+  } else {
+    &[(0, 0, 1, 1)]
+  };
+  // 宽高来自文件，解压时不超过图片需要的大小，并确认解压后的数据足够再分配像素
+  let too_large = || Error::format("image is too large");
+  let size = filtered_size(&header, passes).ok_or_else(too_large)?;
+  let raw = zlib::decompress_limited(&idat, size)?;
+  if raw.len() < size {
+    return Err(Error::format("image data is too short"));
+  }
+  let pixel_size = (w as usize)
+    .checked_mul(h as usize)
+    .and_then(|n| n.checked_mul(4))
+    .ok_or_else(too_large)?;
+  let mut pixels = vec![0u8; pixel_size];
+  let mut offset = 0;
+  let mut samples = [0u16; 4];
+  for &(x0, y0, dx, dy) in passes {
+    // 图片太小时有些遍是空的，没有任何数据
+    if x0 >= w || y0 >= h {
+      continue;
+    }
+    let (pw, ph) = ((w - x0).div_ceil(dx), (h - y0).div_ceil(dy));
+    let row_len = (pw as usize * header.bits_per_pixel()).div_ceil(8);
+    let mut prev = vec![0u8; row_len];
+    let mut row = vec![0u8; row_len];
+    for py in 0..ph {
+      let line = raw
+        .get(offset..offset + 1 + row_len)
+        .ok_or_else(|| Error::format("image data is too short"))?;
+      offset += 1 + row_len;
+      row.copy_from_slice(&line[1..]);
+      unfilter_row(line[0], &mut row, &prev, bpp)?;
+      let y = (y0 + py * dy) as usize;
+      for px in 0..pw as usize {
+        for (c, s) in samples[..channels].iter_mut().enumerate() {
+          *s = sample(&row, px * channels + c, depth);
+        }
+        let rgba = match header.color_type {
+          0 => {
+            let v = scale(samples[0], depth);
+            let opaque = transparent.as_deref() != Some(&samples[..1]);
+            [v, v, v, if opaque { 255 } else { 0 }]
+          }
+          2 => {
+            let opaque = transparent.as_deref() != Some(&samples[..3]);
+            [
+              scale(samples[0], depth),
+              scale(samples[1], depth),
+              scale(samples[2], depth),
+              if opaque { 255 } else { 0 },
+            ]
+          }
+          3 => *palette
+            .get(samples[0] as usize)
+            .ok_or_else(|| Error::format("palette index out of range"))?,
+          4 => {
+            let v = scale(samples[0], depth);
+            [v, v, v, scale(samples[1], depth)]
+          }
+          _ => samples.map(|s| scale(s, depth)),
+        };
+        let x = (x0 + px as u32 * dx) as usize;
+        let i = (y * w as usize + x) * 4;
+        pixels[i..i + 4].copy_from_slice(&rgba);
+      }
+      std::mem::swap(&mut prev, &mut row);
+    }
+  }
+  // PNG 的第一行是图片的最上面一行
+  Ok(PixImage::from_data(
+    pixels,
+    w,
+    h,
+    ImageOriginPos::LeftBottom,
+  ))
+}
+
+impl Decoder for PNG {
+  fn decode(&self, data: Vec<u8>) -> Result<PixImage> {
+    decode_png(&data)
+  }
+}
+
+#[test]
+fn test_png_encode() {
+  use crate::{geometry::Vec4, image::PixImage};
+  let mut img = PixImage::new(5, 3);
+  for y in 0..3 {
+    for x in 0..5 {
+      img.set_rgba32(x, y, Vec4::new(x as u8 * 50, y as u8 * 100, 7, 200));
+    }
+  }
+  let data = PNG::new().encode(&img);
+  assert_eq!(data[..8], SIGNATURE);
+  // 每个块的长度和 CRC 都正确，依次为 IHDR、IDAT、IEND
+  let mut pos = 8;
+  let mut kinds = vec![];
+  while pos < data.len() {
+    let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+    let body = &data[pos + 4..pos + 8 + len];
+    let crc = u32::from_be_bytes(data[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+    assert_eq!(crc32(body), crc);
+    kinds.push(String::from_utf8(body[..4].to_vec()).unwrap());
+    pos += 12 + len;
+  }
+  assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+  assert_eq!(data[16..26], [0, 0, 0, 5, 0, 0, 0, 3, 8, 6]);
+
+  let mut out = vec![];
+  filter_row(4, &[10, 20, 30, 40], &[5, 5, 50, 50], 2, &mut out);
+  assert_eq!(out, [4, 5, 15, 236, 246]);
+}
+
+#[test]
+fn test_png_decode() {
+  use crate::geometry::Vec4;
+  // 往返：编码器输出的三种颜色类型
+  let mut img = PixImage::new(7, 4);
+  for y in 0..4 {
+    for x in 0..7 {
+      img.set_rgba32(
+        x,
+        y,
+        Vec4::new(x as u8 * 30, y as u8 * 60, 90, 100 + x as u8),
+      );
+    }
+  }
+  for color_type in [ColorType::Gray, ColorType::Rgb, ColorType::Rgba] {
+    let decoded = PNG::new()
+      .decode(PNG::new().color_type(color_type).encode(&img))
+      .unwrap();
+    for y in 0..4 {
+      for x in 0..7 {
+        let (a, b) = (img.get_rgba(x, y), decoded.get_rgba(x, y));
+        match color_type {
+          ColorType::Gray => {
+            assert_eq!((b.x, b.y, b.w), (b.z, b.z, 255));
+            assert!(
+              (b.x as i32 - (a.x as i32 * 299 + a.y as i32 * 587 + 90 * 114) / 1000).abs() <= 1
+            );
+          }
+          ColorType::Rgb => assert_eq!((b.x, b.y, b.z, b.w), (a.x, a.y, a.z, 255)),
+          ColorType::Rgba => assert_eq!((b.x, b.y, b.z, b.w), (a.x, a.y, a.z, a.w)),
+        }
+      }
+    }
+  }
+
+  // 手工构造的 PNG，raw 为滤波后的扫描行
+  let build = |w: u32,
+               h: u32,
+               depth: u8,
+               color_type: u8,
+               interlace: u8,
+               extra: &[(&[u8; 4], &[u8])],
+               raw: &[u8]| {
+    let mut ihdr = vec![];
+    ihdr.extend(w.to_be_bytes());
+    ihdr.extend(h.to_be_bytes());
+    ihdr.extend([depth, color_type, 0, 0, interlace]);
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    for (kind, data) in extra {
+      write_chunk(&mut out, kind, data);
+    }
+    write_chunk(&mut out, b"IDAT", &zlib::compress(raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+  };
+  let png = |w, h, depth, color_type, interlace, extra: &[(&[u8; 4], &[u8])], raw: &[u8]| {
+    PNG::new()
+      .decode(build(w, h, depth, color_type, interlace, extra, raw))
+      .unwrap()
+  };
+  let rows = |img: &PixImage| -> Vec<Vec<[u8; 4]>> {
+    (0..img.height)
+      .rev()
+      .map(|y| {
+        (0..img.width)
+          .map(|x| {
+            let c = img.get_rgba(x, y);
+            [c.x, c.y, c.z, c.w]
+          })
+          .collect()
+      })
+      .collect()
+  };
+
+  // 2 位调色板，第二个颜色半透明；第二行用 Up 滤波器
+  let plte: &[u8] = &[255, 0, 0, 0, 255, 0, 0, 0, 255];
+  let img = png(
+    3,
+    2,
+    2,
+    3,
+    0,
+    &[(b"PLTE", plte), (b"tRNS", &[255, 128])],
+    &[0, 0b00_01_10_00, 2, 0b10_00_00_00],
+  );
+  let (r, g, b) = ([255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 255]);
+  assert_eq!(rows(&img), [[r, g, b], [b, g, b]]);
+
+  // 1 位灰度，tRNS 使值为 1 的像素透明
+  let img = png(4, 1, 1, 0, 0, &[(b"tRNS", &[0, 1])], &[0, 0b1010_0000]);
+  assert_eq!(
+    rows(&img),
+    [[
+      [255, 255, 255, 0],
+      [0, 0, 0, 255],
+      [255, 255, 255, 0],
+      [0, 0, 0, 255]
+    ]]
+  );
+
+  // 16 位 RGBA 和灰度+透明度，Sub 滤波器
+  let img = png(
+    2,
+    1,
+    16,
+    6,
+    0,
+    &[],
+    &[
+      1, 0xff, 0xff, 0, 0, 0x80, 0, 0xff, 0xff, 2, 1, 0, 0, 0, 0, 0, 0,
+    ],
+  );
+  assert_eq!(rows(&img), [[[255, 0, 128, 255], [1, 0, 128, 255]]]);
+  let img = png(1, 1, 16, 4, 0, &[], &[0, 0x40, 0x00, 0x00, 0x7f]);
+  assert_eq!(rows(&img), [[[64, 64, 64, 0]]]);
+
+  // 3x3 的 Adam7：第 2、3 遍为空，其余各遍依次为 (0,0)、(2,0)、(0,2) (2,2)、(1,0) (1,2)、第 1 行
+  let raw = [0, 1, 0, 3, 0, 7, 9, 0, 2, 0, 8, 0, 4, 5, 6];
+  let img = png(3, 3, 8, 0, 1, &[], &raw);
+  let gray: Vec<Vec<u8>> = rows(&img)
+    .iter()
+    .map(|r| r.iter().map(|c| c[0]).collect())
+    .collect();
+  assert_eq!(gray, [[1, 2, 3], [4, 5, 6], [7, 8, 9]]);
+
+  // IHDR 的大小与数据不符时返回错误，不按 IHDR 分配内存
+  let huge = build(u32::MAX, u32::MAX, 16, 6, 0, &[], &[0; 9]);
+  assert!(PNG::new().decode(huge).is_err());
+  let short = build(1 << 20, 1 << 20, 8, 6, 1, &[], &[0; 9]);
+  assert!(PNG::new().decode(short).is_err());
+  // 解压后的数据比图片需要的多时不全部解压
+  let bomb = build(1, 1, 8, 0, 0, &[], &vec![0; 1 << 20]);
+  assert!(bomb.len() < 2048);
+  let err = PNG::new().decode(bomb).unwrap_err();
+  assert!(err.to_string().contains("size limit"), "{}", err);
+
+  // 带有 cHRM、iCCP 等辅助块的实际文件，与同一纹理的 TGA 一致
+  let png_texture = PNG::new()
+    .decode(std::fs::read("models/spot/spot_texture.png").unwrap())
+    .unwrap();
+  let tga_texture = crate::util::load_image("models/spot/spot_texture.tga").unwrap();
+  assert_eq!((png_texture.width, png_texture.height), (1024, 1024));
+  for (x, y) in [(0, 0), (100, 900), (512, 512), (1023, 1023), (700, 37)] {
+    let (a, b) = (png_texture.get_rgba(x, y), tga_texture.get_rgba(x, y));
+    assert_eq!((a.x, a.y, a.z, a.w), (b.x, b.y, b.z, b.w));
+  }
+}
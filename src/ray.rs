@@ -0,0 +1,142 @@
+use crate::{bounds::Aabb, geometry::Vec3, mat::Mat4};
+
+// 与三角形所在平面接近平行的射线视为不相交
+const PARALLEL_EPSILON: f32 = 1e-12;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+  pub origin: Vec3<f32>,
+  // 不要求是单位向量
+  pub dir: Vec3<f32>,
+}
+impl Ray {
+  pub fn new(origin: Vec3<f32>, dir: Vec3<f32>) -> Ray {
+    Ray { origin, dir }
+  }
+  pub fn at(&self, t: f32) -> Vec3<f32> {
+    self.origin + self.dir * t
+  }
+  // 由投影和视图矩阵的逆反求经过标准立方体中 (x,y) 的射线，射线从近平面(z=1)出发。
+  // 取 z=0 上的点确定方向，这样反向 Z 的无穷远投影也适用
+  pub fn from_ndc(inv_view_projection: &Mat4, x: f32, y: f32) -> Ray {
+    let near = inv_view_projection * &Vec3::new(x, y, 1.);
+    let far = inv_view_projection * &Vec3::new(x, y, 0.);
+    Ray::new(near, (far - near).normalize())
+  }
+}
+
+// Möller–Trumbore 射线与三角形求交，返回 (t, u, v)，重心坐标为 (1-u-v, u, v)。
+// 只返回 t > t_min 的交点
+pub fn intersect_triangle(
+  ray: &Ray,
+  verts: &[Vec3<f32>; 3],
+  t_min: f32,
+) -> Option<(f32, f32, f32)> {
+  let e1 = verts[1] - verts[0];
+  let e2 = verts[2] - verts[0];
+  let p = ray.dir.cross_product(e2);
+  let det = e1 * p;
+  if det.abs() < PARALLEL_EPSILON {
+    return None;
+  }
+  let inv_det = 1. / det;
+  let s = ray.origin - verts[0];
+  let u = (s * p) * inv_det;
+  if !(0. ..=1.).contains(&u) {
+    return None;
+  }
+  let q = s.cross_product(e1);
+  let v = (ray.dir * q) * inv_det;
+  if v < 0. || u + v > 1. {
+    return None;
+  }
+  let t = (e2 * q) * inv_det;
+  if t > t_min {
+    Some((t, u, v))
+  } else {
+    None
+  }
+}
+
+// slab 方法求射线与包围盒的交，返回进入和离开的 t，起点在盒内时进入的 t 为负
+pub fn intersect_aabb(ray: &Ray, aabb: &Aabb) -> Option<(f32, f32)> {
+  let inv_dir = Vec3::new(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+  slab(ray.origin, inv_dir, aabb, f32::INFINITY)
+}
+
+// inv_dir 为射线方向的倒数，方便遍历 BVH 时重复使用
+pub(crate) fn slab(
+  origin: Vec3<f32>,
+  inv_dir: Vec3<f32>,
+  aabb: &Aabb,
+  t_max: f32,
+) -> Option<(f32, f32)> {
+  let mut t0 = f32::NEG_INFINITY;
+  let mut t1 = t_max;
+  for i in 0..3u8 {
+    let a = (aabb.min[i] - origin[i]) * inv_dir[i];
+    let b = (aabb.max[i] - origin[i]) * inv_dir[i];
+    // 射线与 slab 平行且起点在 slab 上时 a 或 b 为 NaN，min/max 会忽略 NaN
+    t0 = t0.max(a.min(b));
+    t1 = t1.min(a.max(b));
+  }
+  if t0 <= t1 && t1 >= 0. {
+    Some((t0, t1))
+  } else {
+    None
+  }
+}
+
+// 射线与球的最近交点（t > 0）
+pub fn intersect_sphere(ray: &Ray, center: Vec3<f32>, radius: f32) -> Option<f32> {
+  let oc = ray.origin - center;
+  let a = ray.dir * ray.dir;
+  let half_b = oc * ray.dir;
+  let c = oc * oc - radius * radius;
+  let discriminant = half_b * half_b - a * c;
+  if discriminant < 0. {
+    return None;
+  }
+  let sqrt_d = discriminant.sqrt();
+  let t = (-half_b - sqrt_d) / a;
+  if t > 0. {
+    return Some(t);
+  }
+  let t = (-half_b + sqrt_d) / a;
+  if t > 0. {
+    Some(t)
+  } else {
+    None
+  }
+}
+
+#[test]
+fn test_intersect() {
+  let ray = Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+  let t = intersect_sphere(&ray, Vec3::new(0., 0., 0.), 1.).unwrap();
+  assert!((t - 4.).abs() < 1e-6);
+  let aabb = Aabb::new(Vec3::new(-1., -1., -1.), Vec3::new(1., 1., 1.));
+  assert_eq!(intersect_aabb(&ray, &aabb), Some((4., 6.)));
+  // 起点在盒内
+  let ray = Ray::new(Vec3::new(0., 0., 0.), Vec3::new(1., 0., 0.));
+  assert_eq!(intersect_aabb(&ray, &aabb), Some((-1., 1.)));
+  let ray = Ray::new(Vec3::new(0., 3., 5.), Vec3::new(0., 0., -1.));
+  assert!(intersect_aabb(&ray, &aabb).is_none());
+  assert!(intersect_sphere(&ray, Vec3::new(0., 0., 0.), 1.).is_none());
+}
+
+#[test]
+fn test_intersect_triangle() {
+  let verts = [
+    Vec3::new(-1., -1., 0.),
+    Vec3::new(1., -1., 0.),
+    Vec3::new(0., 1., 0.),
+  ];
+  let ray = Ray::new(Vec3::new(0., 0., 2.), Vec3::new(0., 0., -1.));
+  let (t, u, v) = intersect_triangle(&ray, &verts, 0.).unwrap();
+  assert!((t - 2.).abs() < 1e-6);
+  assert!((u - 0.25).abs() < 1e-6 && (v - 0.5).abs() < 1e-6);
+  assert!(intersect_triangle(&ray, &verts, 2.).is_none());
+  let ray = Ray::new(Vec3::new(2., 0., 2.), Vec3::new(0., 0., -1.));
+  assert!(intersect_triangle(&ray, &verts, 0.).is_none());
+}
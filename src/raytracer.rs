@@ -0,0 +1,435 @@
+// 光线追踪后端，作为光栅化结果的参照。
+// 场景由任意 Model 组成，相机使用与光栅化相同的视图和投影矩阵，像素的采样位置也与
+// 光栅化相同，两者的结果可以直接用 compare 模块对比。
+use std::cell::OnceCell;
+
+use crate::{
+  bvh::Bvh,
+  geometry::{Vec3, Vec4},
+  image::{Image, PixImage},
+  mat::Mat4,
+  model::Model,
+  ray::Ray,
+  texture::Texture,
+};
+
+// 次级射线起点沿法向量的偏移，避免与自身相交
+const EPSILON: f32 = 1e-4;
+
+pub struct Camera {
+  inv_view_projection: Mat4,
+}
+impl Camera {
+  pub fn new(view: &Mat4, projection: &Mat4) -> Camera {
+    Camera {
+      inv_view_projection: (projection * view).invert(),
+    }
+  }
+  // 经过像素 (x,y) 的射线，与 pipeline2 的视口变换一致
+  pub fn ray(&self, x: f32, y: f32, width: u32, height: u32) -> Ray {
+    Ray::from_ndc(
+      &self.inv_view_projection,
+      x / width as f32 * 2. - 1.,
+      y / height as f32 * 2. - 1.,
+    )
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct Material<'a> {
+  pub color: Vec3<f32>,
+  pub texture: Option<&'a Texture>,
+  pub emission: Vec3<f32>,
+  // 镜面反射的比例
+  pub reflectivity: f32,
+  // 透射的比例
+  pub transparency: f32,
+  // 折射率
+  pub ior: f32,
+}
+impl<'a> Material<'a> {
+  pub fn diffuse(color: Vec3<f32>) -> Material<'a> {
+    Material {
+      color,
+      texture: None,
+      emission: Vec3::default(),
+      reflectivity: 0.,
+      transparency: 0.,
+      ior: 1.,
+    }
+  }
+  pub fn texture(mut self, texture: &'a Texture) -> Material<'a> {
+    self.texture = Some(texture);
+    self
+  }
+  pub fn emission(mut self, emission: Vec3<f32>) -> Material<'a> {
+    self.emission = emission;
+    self
+  }
+  pub fn reflectivity(mut self, reflectivity: f32) -> Material<'a> {
+    self.reflectivity = reflectivity;
+    self
+  }
+  pub fn transparency(mut self, transparency: f32, ior: f32) -> Material<'a> {
+    self.transparency = transparency;
+    self.ior = ior;
+    self
+  }
+  // 漫反射所占的比例
+  fn diffuse_weight(&self) -> f32 {
+    (1. - self.reflectivity - self.transparency).max(0.)
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+  // dir 为指向光源的方向，与着色器中的 uniform_light 相同
+  Directional { dir: Vec3<f32>, color: Vec3<f32> },
+  // 强度按距离平方衰减
+  Point { pos: Vec3<f32>, color: Vec3<f32> },
+}
+
+struct Triangle {
+  verts: [Vec3<f32>; 3],
+  normals: [Vec3<f32>; 3],
+  uvs: [Vec3<f32>; 3],
+  material: usize,
+}
+
+// 场景中所有变换到世界空间的三角形，作为一个模型构建 BVH
+struct Mesh(Vec<Triangle>);
+impl Model for Mesh {
+  fn vert_count(&self) -> usize {
+    self.0.len() * 3
+  }
+  fn face_count(&self) -> usize {
+    self.0.len()
+  }
+  fn vert(&self, face: usize, nth_vert: usize) -> Vec3<f32> {
+    self.0[face].verts[nth_vert]
+  }
+  fn normal(&self, face: usize, nth_vert: usize) -> Vec3<f32> {
+    self.0[face].normals[nth_vert]
+  }
+  fn normal_of_face(&self, face: usize) -> Vec3<f32> {
+    let v = &self.0[face].verts;
+    (v[1] - v[0]).cross_product(v[2] - v[0]).normalize()
+  }
+  fn texture_coord(&self, face: usize, nth_vert: usize) -> Vec3<f32> {
+    self.0[face].uvs[nth_vert]
+  }
+}
+
+pub struct Hit {
+  pub t: f32,
+  pub pos: Vec3<f32>,
+  // 插值后的法向量
+  pub normal: Vec3<f32>,
+  pub uv: Vec3<f32>,
+  // 场景中三角形的序号
+  pub face: usize,
+  pub material: usize,
+}
+
+pub struct Scene<'a> {
+  mesh: Mesh,
+  // 第一次求交时构建，加入模型后重新构建
+  bvh: OnceCell<Bvh>,
+  materials: Vec<Material<'a>>,
+  pub lights: Vec<Light>,
+  // 没有击中任何物体的射线的颜色，路径追踪时作为环境光
+  pub background: Vec3<f32>,
+  // Whitted 光线追踪的环境光
+  pub ambient: Vec3<f32>,
+}
+impl<'a> Default for Scene<'a> {
+  fn default() -> Self {
+    Scene::new()
+  }
+}
+impl<'a> Scene<'a> {
+  pub fn new() -> Scene<'a> {
+    Scene {
+      mesh: Mesh(vec![]),
+      bvh: OnceCell::new(),
+      materials: vec![],
+      lights: vec![],
+      background: Vec3::default(),
+      ambient: Vec3::new(0.1, 0.1, 0.1),
+    }
+  }
+  // 把模型变换到世界空间后加入场景
+  pub fn add<M: Model>(&mut self, model: &M, transform: &Mat4, material: Material<'a>) {
+    let normal_mat = transform.invert().transpose();
+    let material_idx = self.materials.len();
+    for face in 0..model.face_count() {
+      let mut tri = Triangle {
+        verts: [Vec3::default(); 3],
+        normals: [Vec3::default(); 3],
+        uvs: [Vec3::default(); 3],
+        material: material_idx,
+      };
+      for i in 0..3 {
+        tri.verts[i] = transform * &model.vert(face, i);
+        let n = &normal_mat * Vec4::from_vector(&model.normal(face, i));
+        tri.normals[i] = n.to_3d_vector().normalize();
+        if model.has_texture_coord() {
+          tri.uvs[i] = model.texture_coord(face, i);
+        }
+      }
+      self.mesh.0.push(tri);
+    }
+    self.materials.push(material);
+    self.bvh = OnceCell::new();
+  }
+  pub fn add_light(&mut self, light: Light) {
+    self.lights.push(light);
+  }
+  pub fn material(&self, hit: &Hit) -> &Material<'a> {
+    &self.materials[hit.material]
+  }
+  fn bvh(&self) -> &Bvh {
+    self.bvh.get_or_init(|| Bvh::new(&self.mesh))
+  }
+  // 最近的交点，t 在 (0, t_max) 内
+  pub fn intersect(&self, ray: &Ray, t_max: f32) -> Option<Hit> {
+    self.bvh().intersect(ray, EPSILON, t_max).map(|hit| Hit {
+      t: hit.t,
+      pos: hit.pos,
+      normal: hit.normal,
+      uv: hit.uv,
+      face: hit.face,
+      material: self.mesh.0[hit.face].material,
+    })
+  }
+  fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+    self.bvh().occluded(ray, EPSILON, t_max)
+  }
+  fn albedo(&self, hit: &Hit) -> Vec3<f32> {
+    let m = self.material(hit);
+    match m.texture {
+      Some(texture) => m.color.components_mul(texture.get(hit.uv.x, hit.uv.y)),
+      None => m.color,
+    }
+  }
+  // 点光源和平行光的直接光照（带阴影）
+  fn direct_light(&self, pos: Vec3<f32>, normal: Vec3<f32>, albedo: Vec3<f32>) -> Vec3<f32> {
+    let origin = pos + normal * EPSILON;
+    let mut color = Vec3::default();
+    for light in &self.lights {
+      let (dir, dist, radiance) = match *light {
+        Light::Directional { dir, color } => (dir.normalize(), f32::INFINITY, color),
+        Light::Point { pos: light_pos, color } => {
+          let d = light_pos - pos;
+          let dist = d.norm();
+          (d * (1. / dist), dist, color * (1. / (dist * dist)))
+        }
+      };
+      let cos = normal * dir;
+      if cos <= 0. || self.occluded(&Ray::new(origin, dir), dist) {
+        continue;
+      }
+      color = color + albedo.components_mul(radiance) * cos;
+    }
+    color
+  }
+}
+
+fn reflect(dir: Vec3<f32>, normal: Vec3<f32>) -> Vec3<f32> {
+  dir - normal * (2. * (dir * normal))
+}
+
+// 斯涅尔定律，eta 为入射介质与出射介质折射率之比，全反射时返回 None
+fn refract(dir: Vec3<f32>, normal: Vec3<f32>, eta: f32) -> Option<Vec3<f32>> {
+  let dir = dir.normalize();
+  let cos_i = -(dir * normal);
+  let k = 1. - eta * eta * (1. - cos_i * cos_i);
+  if k < 0. {
+    None
+  } else {
+    Some(dir * eta + normal * (eta * cos_i - k.sqrt()))
+  }
+}
+
+// 法向量总是朝向射线的来向，返回 (法向量, 是否从物体内部射出)
+fn face_forward(normal: Vec3<f32>, dir: Vec3<f32>) -> (Vec3<f32>, bool) {
+  if normal * dir > 0. {
+    (normal * -1., true)
+  } else {
+    (normal, false)
+  }
+}
+
+fn render<F: FnMut(&Ray, u32, u32) -> Vec3<f32>>(
+  camera: &Camera,
+  width: u32,
+  height: u32,
+  mut trace: F,
+) -> PixImage {
+  let mut img = PixImage::new(width, height);
+  for y in 0..height {
+    for x in 0..width {
+      let ray = camera.ray(x as f32, y as f32, width, height);
+      img.set_rgb(x, y, trace(&ray, x, y));
+    }
+  }
+  img
+}
+
+// Whitted 光线追踪：直接光照、阴影、镜面反射和折射
+pub struct Whitted {
+  pub max_depth: u32,
+}
+impl Whitted {
+  pub fn new() -> Whitted {
+    Whitted { max_depth: 5 }
+  }
+  pub fn render(&self, scene: &Scene, camera: &Camera, width: u32, height: u32) -> PixImage {
+    render(camera, width, height, |ray, _, _| self.trace(scene, ray, 0))
+  }
+  pub fn trace(&self, scene: &Scene, ray: &Ray, depth: u32) -> Vec3<f32> {
+    let Some(hit) = scene.intersect(ray, f32::INFINITY) else {
+      return scene.background;
+    };
+    let m = scene.material(&hit);
+    let albedo = scene.albedo(&hit);
+    let (normal, inside) = face_forward(hit.normal, ray.dir);
+    let mut color = m.emission
+      + (albedo.components_mul(scene.ambient) + scene.direct_light(hit.pos, normal, albedo))
+        * m.diffuse_weight();
+    if depth >= self.max_depth {
+      return color;
+    }
+    let reflected = Ray::new(hit.pos + normal * EPSILON, reflect(ray.dir, normal));
+    if m.reflectivity > 0. {
+      color = color + self.trace(scene, &reflected, depth + 1) * m.reflectivity;
+    }
+    if m.transparency > 0. {
+      let eta = if inside { m.ior } else { 1. / m.ior };
+      let transmitted = match refract(ray.dir, normal, eta) {
+        Some(dir) => self.trace(scene, &Ray::new(hit.pos - normal * EPSILON, dir), depth + 1),
+        // 全反射
+        None => self.trace(scene, &reflected, depth + 1),
+      };
+      color = color + transmitted * m.transparency;
+    }
+    color
+  }
+}
+impl Default for Whitted {
+  fn default() -> Self {
+    Whitted::new()
+  }
+}
+
+// xorshift64* 伪随机数，保证结果可重现
+struct Rng(u64);
+impl Rng {
+  fn new(seed: u64) -> Rng {
+    // splitmix64 打散种子
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    Rng((z ^ (z >> 31)) | 1)
+  }
+  // [0,1) 的均匀分布
+  fn next_f32(&mut self) -> f32 {
+    self.0 ^= self.0 >> 12;
+    self.0 ^= self.0 << 25;
+    self.0 ^= self.0 >> 27;
+    let v = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D);
+    (v >> 40) as f32 / (1u64 << 24) as f32
+  }
+}
+
+// 以法向量为中心的余弦加权半球采样
+fn cosine_sample_hemisphere(normal: Vec3<f32>, rng: &mut Rng) -> Vec3<f32> {
+  let a = if normal.x.abs() > 0.9 {
+    Vec3::new(0., 1., 0.)
+  } else {
+    Vec3::new(1., 0., 0.)
+  };
+  let t = normal.cross_product(a).normalize();
+  let b = normal.cross_product(t);
+  let phi = 2. * std::f32::consts::PI * rng.next_f32();
+  let r2 = rng.next_f32();
+  let r = r2.sqrt();
+  t * (phi.cos() * r) + b * (phi.sin() * r) + normal * (1. - r2).sqrt()
+}
+
+// 蒙特卡洛路径追踪。漫反射使用余弦加权采样，点光源和平行光直接采样，
+// 自发光的材质通过随机反弹被击中，超过 3 次反弹后使用俄罗斯轮盘赌终止路径
+pub struct PathTracer {
+  // 每个像素的采样数
+  pub samples: u32,
+  pub max_depth: u32,
+  pub seed: u64,
+}
+impl PathTracer {
+  pub fn new(samples: u32) -> PathTracer {
+    PathTracer {
+      samples,
+      max_depth: 8,
+      seed: 0,
+    }
+  }
+  pub fn render(&self, scene: &Scene, camera: &Camera, width: u32, height: u32) -> PixImage {
+    render(camera, width, height, |_, x, y| {
+      let mut rng = Rng::new(self.seed ^ ((y as u64) << 32 | x as u64));
+      let mut color = Vec3::default();
+      for _ in 0..self.samples {
+        // 在像素内抖动采样位置
+        let dx = rng.next_f32() - 0.5;
+        let dy = rng.next_f32() - 0.5;
+        let ray = camera.ray(x as f32 + dx, y as f32 + dy, width, height);
+        color = color + self.trace(scene, ray, &mut rng);
+      }
+      color * (1. / self.samples.max(1) as f32)
+    })
+  }
+  fn trace(&self, scene: &Scene, ray: Ray, rng: &mut Rng) -> Vec3<f32> {
+    let mut ray = ray;
+    let mut radiance = Vec3::default();
+    let mut throughput = Vec3::new(1., 1., 1.);
+    for depth in 0..self.max_depth {
+      let Some(hit) = scene.intersect(&ray, f32::INFINITY) else {
+        radiance = radiance + throughput.components_mul(scene.background);
+        break;
+      };
+      let m = scene.material(&hit);
+      let (normal, inside) = face_forward(hit.normal, ray.dir);
+      radiance = radiance + throughput.components_mul(m.emission);
+      // 按比例随机选择反射、折射或漫反射
+      let r = rng.next_f32();
+      let dir = if r < m.reflectivity {
+        reflect(ray.dir, normal)
+      } else if r < m.reflectivity + m.transparency {
+        let eta = if inside { m.ior } else { 1. / m.ior };
+        match refract(ray.dir, normal, eta) {
+          Some(dir) => {
+            ray = Ray::new(hit.pos - normal * EPSILON, dir);
+            continue;
+          }
+          None => reflect(ray.dir, normal),
+        }
+      } else if r < m.reflectivity + m.transparency + m.diffuse_weight() {
+        let albedo = scene.albedo(&hit);
+        radiance = radiance + throughput.components_mul(scene.direct_light(hit.pos, normal, albedo));
+        throughput = throughput.components_mul(albedo);
+        cosine_sample_hemisphere(normal, rng)
+      } else {
+        // 被吸收
+        break;
+      };
+      ray = Ray::new(hit.pos + normal * EPSILON, dir);
+      if depth >= 3 {
+        let p = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+        if rng.next_f32() >= p {
+          break;
+        }
+        throughput = throughput * (1. / p);
+      }
+    }
+    radiance
+  }
+}
@@ -0,0 +1,142 @@
+// 录制 sdl::frame/headless 的每一帧，用于把动画放进文档或问题报告。
+// 帧交给 FrameSink 处理，可以保存为编号的图片文件，也可以交给动画编码器。
+use std::{ops::Range, path::PathBuf};
+
+use crate::{
+  image::{ImageOriginPos, PixImage},
+  image_encoder::{Encoder, PPM},
+  input::FrameContext,
+};
+
+pub trait FrameSink {
+  // index 为帧的序号
+  fn write_frame(&mut self, index: u64, img: &PixImage) -> std::io::Result<()>;
+  // 录制结束时调用一次
+  fn finish(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+// 编号的图片文件：<dir>/<name>-0000.<extension>
+pub struct ImageSequence<E: Encoder = PPM> {
+  dir: PathBuf,
+  name: String,
+  encoder: E,
+  extension: String,
+}
+impl ImageSequence {
+  pub fn new<P: Into<PathBuf>>(dir: P, name: &str) -> ImageSequence {
+    ImageSequence {
+      dir: dir.into(),
+      name: file_name(name),
+      encoder: PPM,
+      extension: "ppm".to_string(),
+    }
+  }
+}
+impl<E: Encoder> ImageSequence<E> {
+  pub fn encoder<E2: Encoder>(self, encoder: E2, extension: &str) -> ImageSequence<E2> {
+    ImageSequence {
+      dir: self.dir,
+      name: self.name,
+      encoder,
+      extension: extension.to_string(),
+    }
+  }
+  pub fn path(&self, index: u64) -> PathBuf {
+    self
+      .dir
+      .join(format!("{}-{:04}.{}", self.name, index, self.extension))
+  }
+}
+impl<E: Encoder> FrameSink for ImageSequence<E> {
+  fn write_frame(&mut self, index: u64, img: &PixImage) -> std::io::Result<()> {
+    std::fs::create_dir_all(&self.dir)?;
+    std::fs::write(self.path(index), self.encoder.encode(img))
+  }
+}
+
+// 按帧序号选择要录制的帧，交给 sink
+pub struct Recorder {
+  sink: Box<dyn FrameSink>,
+  range: Option<Range<u64>>,
+  finished: bool,
+}
+impl Recorder {
+  pub fn new<S: FrameSink + 'static>(sink: S) -> Recorder {
+    Recorder {
+      sink: Box::new(sink),
+      range: None,
+      finished: false,
+    }
+  }
+  // 只录制序号在 range 内的帧，默认录制所有帧
+  pub fn range(mut self, range: Range<u64>) -> Recorder {
+    self.range = Some(range);
+    self
+  }
+  // data 为回调绘制的 RGBA 数据，第一行是图片的最上面一行
+  pub fn capture(&mut self, ctx: &FrameContext, data: &[u8]) -> std::io::Result<()> {
+    if self.finished {
+      return Ok(());
+    }
+    match &self.range {
+      Some(range) if ctx.index >= range.end => return self.finish(),
+      Some(range) if ctx.index < range.start => return Ok(()),
+      _ => {}
+    }
+    let img = PixImage::from_data(
+      data.to_vec(),
+      ctx.width,
+      ctx.height,
+      ImageOriginPos::LeftBottom,
+    );
+    self.sink.write_frame(ctx.index, &img)
+  }
+  pub fn is_finished(&self) -> bool {
+    self.finished
+  }
+  // 可以多次调用，只有第一次会通知 sink
+  pub fn finish(&mut self) -> std::io::Result<()> {
+    if self.finished {
+      return Ok(());
+    }
+    self.finished = true;
+    self.sink.finish()
+  }
+}
+
+// 把窗口标题转换为可以用作文件名的字符串
+pub(crate) fn file_name(title: &str) -> String {
+  title
+    .chars()
+    .map(|ch| if ch.is_alphanumeric() { ch } else { '_' })
+    .collect()
+}
+
+#[test]
+fn test_recorder_range() {
+  use std::{cell::RefCell, rc::Rc};
+  struct Frames(Rc<RefCell<(Vec<u64>, bool)>>);
+  impl FrameSink for Frames {
+    fn write_frame(&mut self, index: u64, img: &PixImage) -> std::io::Result<()> {
+      assert_eq!((img.width, img.height), (2, 1));
+      self.0.borrow_mut().0.push(index);
+      Ok(())
+    }
+    fn finish(&mut self) -> std::io::Result<()> {
+      self.0.borrow_mut().1 = true;
+      Ok(())
+    }
+  }
+  let frames = Rc::new(RefCell::new((vec![], false)));
+  let mut recorder = Recorder::new(Frames(frames.clone())).range(2..4);
+  let mut ctx = FrameContext::new(2, 1);
+  for _ in 0..6 {
+    ctx.begin(std::time::Duration::from_millis(10));
+    recorder.capture(&ctx, &[0; 8]).unwrap();
+    ctx.end();
+  }
+  assert_eq!(*frames.borrow(), (vec![2, 3], true));
+  assert!(recorder.is_finished());
+}
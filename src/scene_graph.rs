@@ -0,0 +1,424 @@
+// 场景图：节点保存相对父节点的局部变换(TRS)，遍历树得到世界矩阵。
+// 节点上可以挂模型和着色器、相机或灯光，渲染时从相机节点观察，遍历所有节点绘制。
+use crate::{
+  bounds::Aabb,
+  depth::DepthTarget,
+  geometry::{Vec3, Vec4},
+  image::Image,
+  mat::Mat4,
+  model::Model,
+  pipeline::Fragment,
+  pipeline2::{self, FragmentInfo, RenderOptions, Shader},
+  raytracer::Light,
+  texture::Texture,
+  transform::Transform,
+};
+
+// 局部变换：先缩放，再依次绕 x、y、z 轴旋转(弧度)，最后平移
+#[derive(Debug, Clone, Copy)]
+pub struct Trs {
+  pub translation: Vec3<f32>,
+  pub rotation: Vec3<f32>,
+  pub scale: Vec3<f32>,
+}
+impl Default for Trs {
+  fn default() -> Self {
+    Trs::new()
+  }
+}
+impl Trs {
+  pub fn new() -> Trs {
+    Trs {
+      translation: Vec3::default(),
+      rotation: Vec3::default(),
+      scale: Vec3::new(1., 1., 1.),
+    }
+  }
+  pub fn translate(mut self, x: f32, y: f32, z: f32) -> Trs {
+    self.translation = Vec3::new(x, y, z);
+    self
+  }
+  pub fn rotate(mut self, x: f32, y: f32, z: f32) -> Trs {
+    self.rotation = Vec3::new(x, y, z);
+    self
+  }
+  pub fn scale(mut self, x: f32, y: f32, z: f32) -> Trs {
+    self.scale = Vec3::new(x, y, z);
+    self
+  }
+  pub fn matrix(&self) -> Mat4 {
+    let (t, r, s) = (self.translation, self.rotation, self.scale);
+    Transform::new()
+      .scale(s.x, s.y, s.z)
+      .rotate_x(r.x)
+      .rotate_y(r.y)
+      .rotate_z(r.z)
+      .translate(t.x, t.y, t.z)
+      .build()
+  }
+}
+
+// 传给着色器的矩阵和灯光，灯光已变换到世界空间
+pub struct Uniforms<'a> {
+  pub model: Mat4,
+  pub view: Mat4,
+  pub projection: Mat4,
+  pub camera_pos: Vec3<f32>,
+  pub lights: &'a [Light],
+}
+
+// 场景图中的着色器，绘制前由场景设置矩阵和灯光
+pub trait SceneShader<M: Model>: Shader<M> {
+  fn set_uniforms(&mut self, uniforms: &Uniforms);
+}
+
+// 可以挂在节点上绘制的对象
+pub trait Drawable {
+  fn draw(
+    &mut self,
+    img: &mut dyn Image,
+    depth_buff: &mut dyn DepthTarget,
+    uniforms: &Uniforms,
+    options: &RenderOptions,
+  );
+}
+
+struct Mesh<'a, M, S> {
+  model: &'a M,
+  shader: S,
+  // 模型空间的包围盒，用于视锥体剔除
+  bounds: Aabb,
+}
+impl<'a, M: Model, S: SceneShader<M>> Drawable for Mesh<'a, M, S> {
+  fn draw(
+    &mut self,
+    img: &mut dyn Image,
+    depth_buff: &mut dyn DepthTarget,
+    uniforms: &Uniforms,
+    options: &RenderOptions,
+  ) {
+    self.shader.set_uniforms(uniforms);
+    let mvp = &(&uniforms.projection * &uniforms.view) * &uniforms.model;
+    pipeline2::render_culled(
+      img,
+      depth_buff,
+      &mut self.shader,
+      self.model,
+      &self.bounds,
+      &mvp,
+      options,
+    );
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+impl NodeId {
+  pub fn index(&self) -> usize {
+    self.0
+  }
+}
+
+pub struct Node<'a> {
+  pub name: String,
+  pub transform: Trs,
+  parent: Option<NodeId>,
+  children: Vec<NodeId>,
+  drawable: Option<Box<dyn Drawable + 'a>>,
+  // 相机的投影矩阵，相机沿局部坐标系的 -z 方向观察
+  camera: Option<Mat4>,
+  // 局部坐标系中的灯光
+  light: Option<Light>,
+  world: Mat4,
+}
+impl<'a> Node<'a> {
+  pub fn new(name: &str) -> Node<'a> {
+    Node {
+      name: name.to_string(),
+      transform: Trs::new(),
+      parent: None,
+      children: vec![],
+      drawable: None,
+      camera: None,
+      light: None,
+      world: Mat4::identity(),
+    }
+  }
+  pub fn transform(mut self, transform: Trs) -> Node<'a> {
+    self.transform = transform;
+    self
+  }
+  pub fn model<M: Model, S: SceneShader<M> + 'a>(mut self, model: &'a M, shader: S) -> Node<'a> {
+    self.drawable = Some(Box::new(Mesh {
+      model,
+      shader,
+      bounds: Aabb::from_model(model),
+    }));
+    self
+  }
+  pub fn drawable<D: Drawable + 'a>(mut self, drawable: D) -> Node<'a> {
+    self.drawable = Some(Box::new(drawable));
+    self
+  }
+  pub fn camera(mut self, projection: Mat4) -> Node<'a> {
+    self.camera = Some(projection);
+    self
+  }
+  pub fn light(mut self, light: Light) -> Node<'a> {
+    self.light = Some(light);
+    self
+  }
+  pub fn parent(&self) -> Option<NodeId> {
+    self.parent
+  }
+  pub fn children(&self) -> &[NodeId] {
+    &self.children
+  }
+  // 最近一次 update 计算的世界矩阵
+  pub fn world(&self) -> &Mat4 {
+    &self.world
+  }
+}
+
+#[derive(Default)]
+pub struct SceneGraph<'a> {
+  nodes: Vec<Node<'a>>,
+  roots: Vec<NodeId>,
+}
+impl<'a> SceneGraph<'a> {
+  pub fn new() -> SceneGraph<'a> {
+    SceneGraph {
+      nodes: vec![],
+      roots: vec![],
+    }
+  }
+  // 添加根节点
+  pub fn add(&mut self, node: Node<'a>) -> NodeId {
+    let id = NodeId(self.nodes.len());
+    self.nodes.push(node);
+    self.roots.push(id);
+    id
+  }
+  pub fn add_child(&mut self, parent: NodeId, mut node: Node<'a>) -> NodeId {
+    let id = NodeId(self.nodes.len());
+    node.parent = Some(parent);
+    self.nodes.push(node);
+    self.nodes[parent.0].children.push(id);
+    id
+  }
+  pub fn node(&self, id: NodeId) -> &Node<'a> {
+    &self.nodes[id.0]
+  }
+  pub fn node_mut(&mut self, id: NodeId) -> &mut Node<'a> {
+    &mut self.nodes[id.0]
+  }
+  pub fn find(&self, name: &str) -> Option<NodeId> {
+    self.nodes.iter().position(|n| n.name == name).map(NodeId)
+  }
+  pub fn world(&self, id: NodeId) -> &Mat4 {
+    &self.nodes[id.0].world
+  }
+  // 从根节点开始遍历，世界矩阵 = 父节点的世界矩阵 * 局部矩阵
+  pub fn update(&mut self) {
+    let mut stack: Vec<(NodeId, Mat4)> = self
+      .roots
+      .iter()
+      .map(|&id| (id, Mat4::identity()))
+      .collect();
+    while let Some((id, parent)) = stack.pop() {
+      let node = &mut self.nodes[id.0];
+      node.world = &parent * &node.transform.matrix();
+      for &child in &node.children {
+        stack.push((child, node.world.clone()));
+      }
+    }
+  }
+  // 世界空间中的所有灯光
+  pub fn lights(&self) -> Vec<Light> {
+    self
+      .nodes
+      .iter()
+      .filter_map(|node| {
+        node.light.map(|light| match light {
+          Light::Directional { dir, color } => Light::Directional {
+            dir: (&node.world * Vec4::from_vector(&dir))
+              .to_3d_vector()
+              .normalize(),
+            color,
+          },
+          Light::Point { pos, color } => Light::Point {
+            pos: &node.world * &pos,
+            color,
+          },
+        })
+      })
+      .collect()
+  }
+  // 更新世界矩阵后从 camera 节点观察，绘制所有节点。
+  // draw id 为节点的序号，与 picking 一起使用时可以知道拾取到了哪个节点
+  pub fn render<I: Image, D: DepthTarget>(
+    &mut self,
+    img: &mut I,
+    depth_buff: &mut D,
+    camera: NodeId,
+    options: &RenderOptions,
+  ) {
+    self.update();
+    let camera_node = &self.nodes[camera.0];
+    let projection = camera_node
+      .camera
+      .clone()
+      .expect("The camera node has no projection");
+    let view = camera_node.world.invert();
+    let camera_pos = &camera_node.world * &Vec3::new(0., 0., 0.);
+    let lights = self.lights();
+    for (idx, node) in self.nodes.iter_mut().enumerate() {
+      let Some(drawable) = node.drawable.as_mut() else {
+        continue;
+      };
+      let uniforms = Uniforms {
+        model: node.world.clone(),
+        view: view.clone(),
+        projection: projection.clone(),
+        camera_pos,
+        lights: &lights,
+      };
+      let options = RenderOptions {
+        draw_id: idx as u32,
+        ..*options
+      };
+      drawable.draw(img, depth_buff, &uniforms, &options);
+    }
+  }
+}
+
+// 场景图使用的 Lambert 着色器，可以使用纹理，ambient 设为 1 时不受光照影响(如太阳)
+pub struct LambertShader<'a> {
+  pub color: Vec3<f32>,
+  pub texture: Option<&'a Texture>,
+  pub ambient: Vec3<f32>,
+  mvp: Mat4,
+  model: Mat4,
+  normal_mat: Mat4,
+  lights: Vec<Light>,
+  varying_pos: [Vec3<f32>; 3],
+  varying_normals: [Vec3<f32>; 3],
+  varying_uvs: [Vec3<f32>; 3],
+}
+impl<'a> LambertShader<'a> {
+  pub fn new(color: Vec3<f32>) -> LambertShader<'a> {
+    LambertShader {
+      color,
+      texture: None,
+      ambient: Vec3::new(0.1, 0.1, 0.1),
+      mvp: Mat4::identity(),
+      model: Mat4::identity(),
+      normal_mat: Mat4::identity(),
+      lights: vec![],
+      varying_pos: [Vec3::default(); 3],
+      varying_normals: [Vec3::default(); 3],
+      varying_uvs: [Vec3::default(); 3],
+    }
+  }
+  pub fn texture(mut self, texture: &'a Texture) -> LambertShader<'a> {
+    self.texture = Some(texture);
+    self
+  }
+  pub fn ambient(mut self, ambient: Vec3<f32>) -> LambertShader<'a> {
+    self.ambient = ambient;
+    self
+  }
+}
+impl<'a, M: Model> Shader<M> for LambertShader<'a> {
+  fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+    let v = model.vert(face, nth_vert);
+    self.varying_pos[nth_vert] = &self.model * &v;
+    self.varying_normals[nth_vert] =
+      (&self.normal_mat * Vec4::from_vector(&model.normal(face, nth_vert))).to_3d_vector();
+    if self.texture.is_some() {
+      self.varying_uvs[nth_vert] = model.texture_coord(face, nth_vert);
+    }
+    &self.mvp * Vec4::from_point(&v)
+  }
+  fn fragment(&self, info: FragmentInfo) -> Fragment {
+    let normal = info
+      .barycentric_interpolate(&self.varying_normals)
+      .normalize();
+    let pos = info.barycentric_interpolate(&self.varying_pos);
+    let albedo = match self.texture {
+      Some(texture) => {
+        let uv = info.barycentric_interpolate(&self.varying_uvs);
+        self.color.components_mul(texture.get(uv.x, uv.y))
+      }
+      None => self.color,
+    };
+    let mut light = self.ambient;
+    for l in &self.lights {
+      let (dir, radiance) = match *l {
+        Light::Directional { dir, color } => (dir, color),
+        Light::Point {
+          pos: light_pos,
+          color,
+        } => {
+          let d = light_pos - pos;
+          (d.normalize(), color * (1. / (d * d)))
+        }
+      };
+      light = light + radiance * (normal * dir).max(0.);
+    }
+    Fragment::Color(albedo.components_mul(light))
+  }
+}
+impl<'a, M: Model> SceneShader<M> for LambertShader<'a> {
+  fn set_uniforms(&mut self, uniforms: &Uniforms) {
+    self.model = uniforms.model.clone();
+    self.normal_mat = uniforms.model.invert().transpose();
+    self.mvp = &(&uniforms.projection * &uniforms.view) * &uniforms.model;
+    self.lights = uniforms.lights.to_vec();
+  }
+}
+
+#[test]
+fn test_world_matrix() {
+  let mut scene = SceneGraph::new();
+  let parent = scene.add(
+    Node::new("parent").transform(Trs::new().translate(1., 0., 0.).rotate(
+      0.,
+      std::f32::consts::FRAC_PI_2,
+      0.,
+    )),
+  );
+  let child = scene.add_child(
+    parent,
+    Node::new("child").transform(Trs::new().translate(1., 0., 0.)),
+  );
+  scene.update();
+  let p = scene.world(child) * &Vec3::new(0., 0., 0.);
+  assert!((p - Vec3::new(1., 0., -1.)).norm() < 1e-6);
+  assert_eq!(scene.find("child"), Some(child));
+  assert_eq!(scene.node(child).parent(), Some(parent));
+}
+
+#[test]
+fn test_render_scene() {
+  use crate::{image::PixImage, shape::Plane};
+  let plane = Plane::new();
+  let mut scene = SceneGraph::new();
+  let camera = scene.add(
+    Node::new("camera")
+      .transform(Trs::new().translate(0., 0., 3.))
+      .camera(crate::transform::perspective(60., 1., -0.1, -100.)),
+  );
+  let white = LambertShader::new(Vec3::new(1., 1., 1.)).ambient(Vec3::new(1., 1., 1.));
+  let node = scene.add(Node::new("plane").model(&plane, white));
+  let mut img = PixImage::new(32, 32);
+  let mut depth = vec![f32::MIN; 32 * 32];
+  scene.render(&mut img, &mut depth, camera, &RenderOptions::new());
+  assert_eq!(img.get(16, 16).x, 255);
+  // 移到相机后面，整个模型被剔除
+  scene.node_mut(node).transform = Trs::new().translate(0., 0., 10.);
+  let mut img = PixImage::new(32, 32);
+  let mut depth = vec![f32::MIN; 32 * 32];
+  scene.render(&mut img, &mut depth, camera, &RenderOptions::new());
+  assert_eq!(img.get(16, 16).x, 0);
+}
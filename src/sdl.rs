@@ -1,55 +1,191 @@
+use std::{
+  ops::Range,
+  path::PathBuf,
+  time::{Duration, Instant},
+};
+
 use sdl2::{event::Event, keyboard::Keycode, pixels::PixelFormatEnum, rect::Rect};
 
-pub fn frame<F: FnMut((&mut [u8], u32, u32), f32) -> ()>(title: &str, w: u32, h: u32, mut draw: F) {
-  let sdl_context = sdl2::init().unwrap();
-  let video_subsystem = sdl_context.video().unwrap();
-  let window = video_subsystem
-    .window(title, w, h)
-    .position_centered()
-    .build()
-    .unwrap();
-  let mut canvas = window.into_canvas().build().unwrap();
-  let texture_creator = canvas.texture_creator();
-  let mut texture = texture_creator
-    .create_texture(
-      PixelFormatEnum::RGBA32,
-      sdl2::render::TextureAccess::Streaming,
-      w,
-      h,
-    )
-    .expect("Failed to create texture");
-  let mut event_pump = sdl_context.event_pump().unwrap();
-  let mut fps = 0.;
-  'running: loop {
-    let start = std::time::Instant::now();
-    for event in event_pump.poll_iter() {
-      match event {
-        Event::Quit { .. }
-        | Event::KeyDown {
-          keycode: Some(Keycode::Escape),
-          ..
-        } => break 'running,
-        _ => {}
+use crate::{
+  gif::GifWriter,
+  image::{ImageOriginPos, PixImage},
+  input::{FrameContext, InputEvent, Key},
+  record::{FrameSink, ImageSequence, Recorder},
+};
+
+pub struct Window {
+  title: String,
+  width: u32,
+  height: u32,
+  recorder: Option<Recorder>,
+  screenshot_key: Option<Key>,
+  screenshot_dir: PathBuf,
+  resizable: bool,
+}
+impl Window {
+  pub fn new(title: &str, width: u32, height: u32) -> Window {
+    Window {
+      title: title.to_string(),
+      width,
+      height,
+      recorder: None,
+      screenshot_key: Some(Key::F(12)),
+      screenshot_dir: PathBuf::from("screenshots"),
+      resizable: false,
+    }
+  }
+  // 窗口是否可以改变大小，默认不可以。改变大小后传给回调的图片大小随之改变，ctx.resized 为 true，
+  // 回调需要按 ctx.width 和 ctx.height 重新分配深度缓冲等
+  pub fn resizable(mut self, resizable: bool) -> Window {
+    self.resizable = resizable;
+    self
+  }
+  // 把呈现的帧交给 recorder
+  pub fn record(mut self, recorder: Recorder) -> Window {
+    self.recorder = Some(recorder);
+    self
+  }
+  // 按下这个键时把当前帧保存到 screenshot_dir，为 None 时关闭截图
+  pub fn screenshot_key(mut self, key: Option<Key>) -> Window {
+    self.screenshot_key = key;
+    self
+  }
+  pub fn screenshot_dir<P: Into<PathBuf>>(mut self, dir: P) -> Window {
+    self.screenshot_dir = dir.into();
+    self
+  }
+  // 设置 YATSR_RECORD_DIR 时把帧保存为编号的图片，设置 YATSR_RECORD_GIF 时保存为 GIF 动画，
+  // YATSR_RECORD_FRAMES=start..end 选择帧的范围。不能创建 GIF 文件时返回错误
+  pub fn from_env(mut self) -> std::io::Result<Window> {
+    let recorder = if let Ok(path) = std::env::var("YATSR_RECORD_GIF") {
+      let gif = GifWriter::create(path, self.width, self.height)?;
+      Some(Recorder::new(gif))
+    } else {
+      std::env::var("YATSR_RECORD_DIR")
+        .ok()
+        .map(|dir| Recorder::new(ImageSequence::new(dir, &self.title)))
+    };
+    if let Some(mut recorder) = recorder {
+      if let Some(range) = std::env::var("YATSR_RECORD_FRAMES")
+        .ok()
+        .and_then(|v| parse_range(&v))
+      {
+        recorder = recorder.range(range);
       }
+      self.recorder = Some(recorder);
     }
-    texture
-      .with_lock(Rect::new(0, 0, w, h), |data, _| {
-        data.fill(0);
-        let img = (data, w, h);
-        draw(img, fps);
-      })
-      .unwrap();
+    Ok(self)
+  }
+  // 回调收到紧密排列的 RGBA 数据，第一行是图片的最上面一行。
+  // 录制或截图失败时输出错误，录制失败后停止录制，窗口继续运行
+  pub fn run<F: FnMut((&mut [u8], u32, u32), &FrameContext)>(mut self, mut draw: F) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let mut window = video_subsystem.window(&self.title, self.width, self.height);
+    window.position_centered();
+    if self.resizable {
+      window.resizable();
+    }
+    let window = window.build().unwrap();
+    let mut canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let create_texture = |w, h| {
+      texture_creator
+        .create_texture(
+          PixelFormatEnum::RGBA32,
+          sdl2::render::TextureAccess::Streaming,
+          w,
+          h,
+        )
+        .expect("Failed to create texture")
+    };
+    let mut texture = create_texture(self.width, self.height);
+    let mut pixels = vec![0; (self.width * self.height * 4) as usize];
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut screenshots = ImageSequence::new(&self.screenshot_dir, &self.title);
+    let mut ctx = FrameContext::new(self.width, self.height);
+    let mut last = None;
+    'running: loop {
+      let start = Instant::now();
+      ctx.begin(last.map_or(Duration::ZERO, |last| start - last));
+      last = Some(start);
+      for event in event_pump.poll_iter() {
+        match event {
+          Event::Quit { .. }
+          | Event::KeyDown {
+            keycode: Some(Keycode::Escape),
+            ..
+          } => break 'running,
+          _ => {
+            if let Some(event) = InputEvent::from_sdl(&event) {
+              ctx.push(event);
+            }
+          }
+        }
+      }
+      let (w, h) = (ctx.width, ctx.height);
+      if ctx.resized {
+        texture = create_texture(w, h);
+        pixels = vec![0; (w * h * 4) as usize];
+      }
+      pixels.fill(0);
+      draw((&mut pixels, w, h), &ctx);
+      if let Some(Err(e)) = self.recorder.as_mut().map(|r| r.capture(&ctx, &pixels)) {
+        eprintln!("Recording stopped: {}", e);
+        self.stop_recording();
+      }
+      if self
+        .screenshot_key
+        .is_some_and(|key| ctx.events.contains(&InputEvent::KeyDown(key)))
+      {
+        let img = PixImage::from_data(pixels.clone(), w, h, ImageOriginPos::LeftBottom);
+        if let Err(e) = screenshots.write_frame(ctx.index, &img) {
+          eprintln!("Failed to save screenshot: {}", e);
+        }
+      }
+      // 纹理的每一行可能有填充，按紧密排列的行宽上传
+      texture.update(None, &pixels, w as usize * 4).unwrap();
 
-    canvas
-      .copy(&texture, Rect::new(0, 0, w, h), Rect::new(0, 0, w, h))
-      .unwrap();
+      canvas
+        .copy(&texture, Rect::new(0, 0, w, h), Rect::new(0, 0, w, h))
+        .unwrap();
 
-    canvas.present();
-    let duration = start.elapsed();
-    fps = 1000. / (duration.as_millis() as f32);
-    //::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+      canvas.present();
+      ctx.end();
+    }
+    self.stop_recording();
+  }
+  fn stop_recording(&mut self) {
+    if let Some(Err(e)) = self.recorder.take().map(|mut r| r.finish()) {
+      eprintln!("Failed to finish recording: {}", e);
+    }
   }
 }
+
+fn parse_range(s: &str) -> Option<Range<u64>> {
+  let (start, end) = s.split_once("..")?;
+  Some(start.trim().parse().ok()?..end.trim().parse().ok()?)
+}
+
+// 不能创建录制文件时输出错误，不录制
+fn window_from_env(title: &str, w: u32, h: u32) -> Window {
+  Window::new(title, w, h).from_env().unwrap_or_else(|e| {
+    eprintln!("Recording disabled: {}", e);
+    Window::new(title, w, h)
+  })
+}
+pub fn frame<F: FnMut((&mut [u8], u32, u32), &FrameContext)>(title: &str, w: u32, h: u32, draw: F) {
+  window_from_env(title, w, h).run(draw)
+}
+// 窗口可以改变大小，见 Window::resizable
+pub fn resizable_frame<F: FnMut((&mut [u8], u32, u32), &FrameContext)>(
+  title: &str,
+  w: u32,
+  h: u32,
+  draw: F,
+) {
+  window_from_env(title, w, h).resizable(true).run(draw)
+}
 pub fn one_frame<F: FnMut((&mut [u8], u32, u32)) -> ()>(title: &str, w: u32, h: u32, mut draw: F) {
   let sdl_context = sdl2::init().unwrap();
   let video_subsystem = sdl_context.video().unwrap();
@@ -68,13 +204,9 @@ pub fn one_frame<F: FnMut((&mut [u8], u32, u32)) -> ()>(title: &str, w: u32, h:
       h,
     )
     .expect("Failed to create texture");
-  texture
-    .with_lock(Rect::new(0, 0, w, h), |data, _| {
-      data.fill(0);
-      let img = (data, w, h);
-      draw(img);
-    })
-    .unwrap();
+  let mut pixels = vec![0; (w * h * 4) as usize];
+  draw((&mut pixels, w, h));
+  texture.update(None, &pixels, w as usize * 4).unwrap();
 
   canvas
     .copy(&texture, Rect::new(0, 0, w, h), Rect::new(0, 0, w, h))
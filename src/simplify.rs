@@ -0,0 +1,360 @@
+// 基于二次误差度量(QEM, Garland–Heckbert)的网格简化。
+// 每次把一个顶点合并到相邻顶点上(半边折叠)，合并后的位置和属性取自保留的顶点，
+// 所以不需要插值纹理坐标和法向量。纹理坐标或法向量的接缝只能沿接缝折叠，边界和接缝上
+// 额外加入垂直于表面的约束平面，保持它们的形状。
+use std::{
+  cmp::Ordering,
+  collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use crate::{
+  geometry::Vec3,
+  model::{IndexedMesh, Model},
+};
+
+// 边界和接缝约束平面的权重
+const BORDER_WEIGHT: f64 = 100.;
+
+// 对称 4x4 矩阵的上三角
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+impl Quadric {
+  // 平面 n·p + d = 0，n 为单位向量
+  fn from_plane(n: Vec3<f32>, d: f32, weight: f64) -> Quadric {
+    let (a, b, c, d) = (n.x as f64, n.y as f64, n.z as f64, d as f64);
+    Quadric([
+      a * a,
+      a * b,
+      a * c,
+      a * d,
+      b * b,
+      b * c,
+      b * d,
+      c * c,
+      c * d,
+      d * d,
+    ])
+    .scale(weight)
+  }
+  fn scale(self, k: f64) -> Quadric {
+    Quadric(self.0.map(|v| v * k))
+  }
+  fn add(&mut self, other: &Quadric) {
+    for i in 0..10 {
+      self.0[i] += other.0[i];
+    }
+  }
+  // 点到所有平面距离的平方和
+  fn error(&self, p: Vec3<f32>) -> f64 {
+    let (x, y, z) = (p.x as f64, p.y as f64, p.z as f64);
+    let q = &self.0;
+    q[0] * x * x
+      + 2. * q[1] * x * y
+      + 2. * q[2] * x * z
+      + 2. * q[3] * x
+      + q[4] * y * y
+      + 2. * q[5] * y * z
+      + 2. * q[6] * y
+      + q[7] * z * z
+      + 2. * q[8] * z
+      + q[9]
+  }
+}
+
+// 把 from 合并到 to 的候选，version 用来丢弃过期的候选
+struct Collapse {
+  cost: f64,
+  from: usize,
+  to: usize,
+  version: (u32, u32),
+}
+impl PartialEq for Collapse {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+impl Eq for Collapse {}
+impl PartialOrd for Collapse {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+impl Ord for Collapse {
+  // BinaryHeap 是最大堆，代价小的优先。代价相同时按顶点序号，使结果与哈希表的遍历顺序无关
+  fn cmp(&self, other: &Self) -> Ordering {
+    other
+      .cost
+      .total_cmp(&self.cost)
+      .then_with(|| (other.from, other.to).cmp(&(self.from, self.to)))
+  }
+}
+
+struct Simplifier {
+  // 每个位置(几何顶点)的坐标，网格顶点通过 pos_of 映射到位置
+  positions: Vec<Vec3<f32>>,
+  pos_of: Vec<usize>,
+  faces: Vec<[usize; 3]>,
+  alive: Vec<bool>,
+  alive_count: usize,
+  // 每个位置相邻的面，可能包含已经删除的面
+  pos_faces: Vec<Vec<usize>>,
+  quadrics: Vec<Quadric>,
+  version: Vec<u32>,
+  heap: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+  fn new(mesh: &IndexedMesh) -> Simplifier {
+    let bits = |v: Vec3<f32>| [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()];
+    let mut positions = vec![];
+    let mut index_of = HashMap::new();
+    let pos_of = mesh
+      .positions
+      .iter()
+      .map(|p| {
+        *index_of.entry(bits(*p)).or_insert_with(|| {
+          positions.push(*p);
+          positions.len() - 1
+        })
+      })
+      .collect::<Vec<_>>();
+    let faces: Vec<[usize; 3]> = mesh.indices.iter().map(|f| f.map(|i| i as usize)).collect();
+    let n = positions.len();
+    let mut s = Simplifier {
+      positions,
+      pos_of,
+      alive: vec![true; faces.len()],
+      alive_count: faces.len(),
+      faces,
+      pos_faces: vec![vec![]; n],
+      quadrics: vec![Quadric::default(); n],
+      version: vec![0; n],
+      heap: BinaryHeap::new(),
+    };
+    // 边 -> 相邻的面
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for f in 0..s.faces.len() {
+      let p = s.face_pos(f);
+      if p[0] == p[1] || p[1] == p[2] || p[2] == p[0] {
+        // 退化的面
+        s.alive[f] = false;
+        s.alive_count -= 1;
+        continue;
+      }
+      let cross = s.face_cross(p);
+      let area = cross.norm();
+      for i in 0..3 {
+        s.pos_faces[p[i]].push(f);
+        let key = (p[i].min(p[(i + 1) % 3]), p[i].max(p[(i + 1) % 3]));
+        edges.entry(key).or_default().push(f);
+      }
+      if area > 0. {
+        let normal = cross * (1. / area);
+        let q = Quadric::from_plane(normal, -(normal * s.positions[p[0]]), area as f64 / 2.);
+        p.iter().for_each(|&i| s.quadrics[i].add(&q));
+      }
+    }
+    // 边界和接缝上的约束平面
+    for (&(a, b), faces) in &edges {
+      let seam = match faces.as_slice() {
+        [_] => true,
+        [f0, f1] => s.wedges_on_edge(*f0, a, b) != s.wedges_on_edge(*f1, a, b),
+        _ => false,
+      };
+      if !seam {
+        continue;
+      }
+      let (pa, pb) = (s.positions[a], s.positions[b]);
+      let edge = pb - pa;
+      for &f in faces {
+        let face_normal = s.face_cross(s.face_pos(f));
+        let normal = edge.cross_product(face_normal);
+        if normal.norm() == 0. {
+          continue;
+        }
+        let normal = normal.normalize();
+        let q = Quadric::from_plane(normal, -(normal * pa), BORDER_WEIGHT * (edge * edge) as f64);
+        s.quadrics[a].add(&q);
+        s.quadrics[b].add(&q);
+      }
+    }
+    for &(a, b) in edges.keys() {
+      s.push(a, b);
+      s.push(b, a);
+    }
+    s
+  }
+  fn face_pos(&self, f: usize) -> [usize; 3] {
+    self.faces[f].map(|w| self.pos_of[w])
+  }
+  fn face_cross(&self, p: [usize; 3]) -> Vec3<f32> {
+    let [a, b, c] = p.map(|i| self.positions[i]);
+    (b - a).cross_product(c - a)
+  }
+  // 面 f 中位置 a、b 对应的网格顶点
+  fn wedges_on_edge(&self, f: usize, a: usize, b: usize) -> (usize, usize) {
+    let wedge = |p| self.faces[f][self.face_pos(f).iter().position(|&i| i == p).unwrap()];
+    (wedge(a), wedge(b))
+  }
+  fn push(&mut self, from: usize, to: usize) {
+    let mut q = self.quadrics[from];
+    q.add(&self.quadrics[to]);
+    self.heap.push(Collapse {
+      cost: q.error(self.positions[to]),
+      from,
+      to,
+      version: (self.version[from], self.version[to]),
+    });
+  }
+  fn alive_faces(&self, p: usize) -> Vec<usize> {
+    self.pos_faces[p]
+      .iter()
+      .copied()
+      .filter(|&f| self.alive[f])
+      .collect()
+  }
+  fn neighbors(&self, p: usize) -> HashSet<usize> {
+    self
+      .alive_faces(p)
+      .iter()
+      .flat_map(|&f| self.face_pos(f))
+      .filter(|&i| i != p)
+      .collect()
+  }
+  // 检查折叠是否合法，返回 from 的网格顶点到 to 的网格顶点的映射
+  fn check(&self, from: usize, to: usize) -> Option<HashMap<usize, usize>> {
+    let faces = self.alive_faces(from);
+    let (shared, rest): (Vec<usize>, Vec<usize>) =
+      faces.iter().partition(|&&f| self.face_pos(f).contains(&to));
+    if shared.is_empty() {
+      return None;
+    }
+    // 连接条件：两个顶点共同的邻居只能是共享面的第三个顶点，否则折叠后不是流形
+    let common = self
+      .neighbors(from)
+      .intersection(&self.neighbors(to))
+      .count();
+    if common != shared.len() {
+      return None;
+    }
+    // 折叠边两侧的面确定顶点属性的对应关系，接缝顶点只能沿接缝折叠
+    let mut map = HashMap::new();
+    for &f in &shared {
+      let (w_to, w_from) = self.wedges_on_edge(f, to, from);
+      if *map.entry(w_from).or_insert(w_to) != w_to {
+        return None;
+      }
+    }
+    for &f in &rest {
+      let p = self.face_pos(f);
+      let i = p.iter().position(|&i| i == from).unwrap();
+      if !map.contains_key(&self.faces[f][i]) {
+        return None;
+      }
+      // 不能使面翻转
+      let old = self.face_cross(p);
+      let mut moved = p;
+      moved[i] = to;
+      if old * self.face_cross(moved) <= 0. {
+        return None;
+      }
+    }
+    Some(map)
+  }
+  fn collapse(&mut self, from: usize, to: usize, map: &HashMap<usize, usize>) {
+    for f in self.alive_faces(from) {
+      if self.face_pos(f).contains(&to) {
+        self.alive[f] = false;
+        self.alive_count -= 1;
+      } else {
+        for w in self.faces[f].iter_mut() {
+          if let Some(&new) = map.get(w) {
+            *w = new;
+          }
+        }
+        self.pos_faces[to].push(f);
+      }
+    }
+    self.pos_faces[from].clear();
+    let q = self.quadrics[from];
+    self.quadrics[to].add(&q);
+    self.version[from] += 1;
+    self.version[to] += 1;
+    for n in self.neighbors(to) {
+      self.push(to, n);
+      self.push(n, to);
+    }
+  }
+  fn run(&mut self, target: usize) {
+    while self.alive_count > target {
+      let Some(c) = self.heap.pop() else {
+        break;
+      };
+      if c.version != (self.version[c.from], self.version[c.to]) {
+        continue;
+      }
+      if let Some(map) = self.check(c.from, c.to) {
+        self.collapse(c.from, c.to, &map);
+      }
+    }
+  }
+  fn build(&self, mesh: &IndexedMesh) -> IndexedMesh {
+    let mut out = IndexedMesh::default();
+    let mut index_of = HashMap::new();
+    for (f, face) in self.faces.iter().enumerate() {
+      if !self.alive[f] {
+        continue;
+      }
+      let tri = face.map(|w| {
+        *index_of.entry(w).or_insert_with(|| {
+          out.positions.push(mesh.positions[w]);
+          out.normals.push(mesh.normals[w]);
+          if !mesh.uvs.is_empty() {
+            out.uvs.push(mesh.uvs[w]);
+          }
+          (out.positions.len() - 1) as u32
+        })
+      });
+      out.indices.push(tri);
+    }
+    out
+  }
+}
+
+impl IndexedMesh {
+  // 简化到不超过 target 个三角形。不能继续折叠而不破坏拓扑时，结果可能多于 target
+  pub fn simplify(&self, target: usize) -> IndexedMesh {
+    let mut s = Simplifier::new(self);
+    s.run(target);
+    s.build(self)
+  }
+}
+
+// 简化任意模型(如 Object)，返回新的模型
+pub fn simplify<M: Model>(model: &M, target: usize) -> IndexedMesh {
+  IndexedMesh::from_model(model).simplify(target)
+}
+
+#[test]
+fn test_simplify() {
+  use crate::bounds::Aabb;
+  let model = crate::model::Object::from_file("models/spot/spot_triangulated.obj").unwrap();
+  let mesh = IndexedMesh::from_model(&model);
+  let simplified = mesh.simplify(1000);
+  assert!(simplified.face_count() <= 1000);
+  assert!(simplified.face_count() > 900);
+  // 形状基本不变
+  let (a, b) = (Aabb::from_model(&mesh), Aabb::from_model(&simplified));
+  assert!((a.size() - b.size()).norm() < 0.05 * a.size().norm());
+  // 保留的顶点的属性不变，接缝两侧仍然是不同的顶点
+  let seams = |m: &IndexedMesh| {
+    let mut count: HashMap<[u32; 3], usize> = HashMap::new();
+    for p in &m.positions {
+      *count
+        .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+        .or_default() += 1;
+    }
+    count.values().filter(|&&n| n > 1).count()
+  };
+  assert!(seams(&simplified) > 0);
+}
@@ -1,4 +1,6 @@
+use crate::bounds::Aabb;
 use crate::prelude::*;
+use crate::ray::{intersect_aabb, Ray};
 pub struct Texture {
   pub image: PixImage,
   lookup: fn(img: &PixImage, u: f32, v: f32) -> Vec3<f32>,
@@ -106,67 +108,60 @@ impl Cubemap {
       ],
     }
   }
-  pub fn get_uv(&self, point: Vec3<f32>) -> Vec3<f32> {
-    let cube = [
-      // 由一个点和一个法向量定义平面
-      (Vec3::new(0., 0., 1.), Vec3::new(0., 0., 1.)), // front
-      (Vec3::new(-1., -1., -1.), Vec3::new(0., 0., -1.)), // back
-      (Vec3::new(-1., -1., 1.), Vec3::new(-1., 0., 0.)), // left
-      (Vec3::new(1., -1., 1.), Vec3::new(1., 0., 0.)), // right
-      (Vec3::new(1., 1., 1.), Vec3::new(0., 1., 0.)), // top
-      (Vec3::new(1., -1., 1.), Vec3::new(0., -1., 0.)), // bottom
-    ];
-    for (ind, (p, normal)) in cube.iter().enumerate() {
-      let p = util::intersect_of_plan_line(
-        *p, *normal, point, point, // point - Vec3::new(0.,0.,0.)
-      );
-      if let Some(p) = p {
-        if p.x >= -1. && p.x <= 1. && p.y >= -1. && p.y <= 1. && p.z >= -1. && p.z <= 1. {
-          let (u, v) = match ind {
-            0 => ((p.x + 1.) / 2., (p.y + 1.) / 2.),
-            1 => ((-p.x + 1.) / 2., (p.y + 1.) / 2.),
-            2 => ((p.z + 1.) / 2., (p.y + 1.) / 2.),
-            // right
-            3 => ((-p.z + 1.) / 2., (p.y + 1.) / 2.),
-            // top
-            4 => ((-p.x + 1.) / 2., (p.z + 1.) / 2.),
-            _ => ((p.x + 1.) / 2., (p.z + 1.) / 2.),
-          };
-          return Vec3::new(u, v, 0.);
-        }
+  // 从立方体中心沿 point 方向的射线与立方体的交点所在的面和面上的纹理坐标
+  fn lookup(point: Vec3<f32>) -> Option<(usize, f32, f32)> {
+    if point.x == 0. && point.y == 0. && point.z == 0. {
+      return None;
+    }
+    let cube = Aabb::new(Vec3::new(-1., -1., -1.), Vec3::new(1., 1., 1.));
+    let ray = Ray::new(point, point);
+    let (_, t) = intersect_aabb(&ray, &cube)?;
+    let p = ray.at(t);
+    let (ax, ay, az) = (p.x.abs(), p.y.abs(), p.z.abs());
+    // 落在棱上时按 front、back、left、right、top、bottom 的顺序选择
+    let ind = if az >= ax && az >= ay {
+      if p.z > 0. {
+        0
+      } else {
+        1
       }
+    } else if ax >= ay {
+      if p.x < 0. {
+        2
+      } else {
+        3
+      }
+    } else if p.y > 0. {
+      4
+    } else {
+      5
+    };
+    let (u, v) = match ind {
+      // front
+      0 => ((p.x + 1.) / 2., (p.y + 1.) / 2.),
+      // back
+      1 => ((-p.x + 1.) / 2., (p.y + 1.) / 2.),
+      // left
+      2 => ((p.z + 1.) / 2., (p.y + 1.) / 2.),
+      // right
+      3 => ((-p.z + 1.) / 2., (p.y + 1.) / 2.),
+      // top
+      4 => ((-p.x + 1.) / 2., (p.z + 1.) / 2.),
+      // bottom
+      _ => ((p.x + 1.) / 2., (p.z + 1.) / 2.),
+    };
+    Some((ind, u, v))
+  }
+  pub fn get_uv(&self, point: Vec3<f32>) -> Vec3<f32> {
+    match Cubemap::lookup(point) {
+      Some((_, u, v)) => Vec3::new(u, v, 0.),
+      None => Vec3::new(1., 1., 0.),
     }
-    return Vec3::new(1., 1., 0.);
   }
   pub fn get(&self, point: Vec3<f32>) -> Vec3<f32> {
-    let cube = [
-      // 由一个点和一个法向量定义平面
-      (Vec3::new(0., 0., 1.), Vec3::new(0., 0., 1.)), // front
-      (Vec3::new(-1., -1., -1.), Vec3::new(0., 0., -1.)), // back
-      (Vec3::new(-1., -1., 1.), Vec3::new(-1., 0., 0.)), // left
-      (Vec3::new(1., -1., 1.), Vec3::new(1., 0., 0.)), // right
-      (Vec3::new(1., 1., 1.), Vec3::new(0., 1., 0.)), // top
-      (Vec3::new(1., -1., 1.), Vec3::new(0., -1., 0.)), // bottom
-    ];
-    for (ind, (p, normal)) in cube.iter().enumerate() {
-      let p = util::intersect_of_plan_line(
-        *p, *normal, point, point, // point - Vec3::new(0.,0.,0.)
-      );
-      if let Some(p) = p {
-        if p.x >= -1. && p.x <= 1. && p.y >= -1. && p.y <= 1. && p.z >= -1. && p.z <= 1. {
-          return match ind {
-            0 => self.texture[ind].get((p.x + 1.) / 2., (p.y + 1.) / 2.),
-            1 => self.texture[ind].get((-p.x + 1.) / 2., (p.y + 1.) / 2.),
-            2 => self.texture[ind].get((p.z + 1.) / 2., (p.y + 1.) / 2.),
-            // right
-            3 => self.texture[ind].get((-p.z + 1.) / 2., (p.y + 1.) / 2.),
-            // top
-            4 => self.texture[ind].get((-p.x + 1.) / 2., (p.z + 1.) / 2.),
-            _ => self.texture[ind].get((p.x + 1.) / 2., (p.z + 1.) / 2.),
-          };
-        }
-      }
+    match Cubemap::lookup(point) {
+      Some((ind, u, v)) => self.texture[ind].get(u, v),
+      None => Vec3::new(1., 1., 0.),
     }
-    return Vec3::new(1., 1., 0.);
   }
 }
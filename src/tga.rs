@@ -0,0 +1,529 @@
+// TGA 编解码。
+// 解码：支持颜色表(类型 1/9)、真彩色(类型 2/10)和灰度(类型 3/11)，15/16 位(ARGB1555)、24/32 位的像素和颜色表，
+// 以及四种屏幕原点；数据不完整或不支持时返回 TGAError。
+// 编码：24/32 位真彩色或 8 位灰度，可选 RLE 压缩和第一个像素的位置。
+use std::borrow::Cow;
+
+use crate::{
+  error::Error,
+  image::{luma, Image, ImageOriginPos, PixImage},
+  image_decoder::Decoder,
+  image_encoder::Encoder,
+};
+
+const HEADER_SIZE: usize = 18;
+
+pub struct TGA {
+  // 编码时的像素格式
+  pub format: TGAFormat,
+  // 编码时是否使用 RLE 压缩
+  pub rle: bool,
+  // 编码时第一个像素在屏幕上的位置
+  pub origin: ScreenDestination,
+}
+impl TGA {
+  pub fn new() -> TGA {
+    TGA {
+      format: TGAFormat::Rgb24,
+      rle: false,
+      origin: ScreenDestination::BottomLeft,
+    }
+  }
+  pub fn format(mut self, format: TGAFormat) -> TGA {
+    self.format = format;
+    self
+  }
+  pub fn rle(mut self, rle: bool) -> TGA {
+    self.rle = rle;
+    self
+  }
+  pub fn origin(mut self, origin: ScreenDestination) -> TGA {
+    self.origin = origin;
+    self
+  }
+}
+impl Default for TGA {
+  fn default() -> TGA {
+    TGA::new()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TGAFormat {
+  Rgb24,
+  Rgba32,
+  Gray8,
+}
+
+// 第一个像素在屏幕上的位置，即图片描述字节的第 4、5 位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenDestination {
+  BottomLeft,
+  BottomRight,
+  TopLeft,
+  TopRight,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TGAError {
+  // 数据在图片结束之前就结束了
+  Truncated,
+  // 类型 0，文件中没有图片
+  NoImageData,
+  UnsupportedImageType(u8),
+  UnsupportedDepth { image_type: u8, depth: u8 },
+  // 颜色表类型的图片没有颜色表
+  MissingColorMap,
+  UnsupportedColorMapDepth(u8),
+  ColorIndexOutOfRange(u16),
+}
+impl std::fmt::Display for TGAError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TGAError::Truncated => write!(f, "unexpected end of data"),
+      TGAError::NoImageData => write!(f, "file contains no image data"),
+      TGAError::UnsupportedImageType(t) => write!(f, "unsupported image type {}", t),
+      TGAError::UnsupportedDepth { image_type, depth } => {
+        write!(
+          f,
+          "unsupported depth {} for image type {}",
+          depth, image_type
+        )
+      }
+      TGAError::MissingColorMap => write!(f, "color-mapped image has no color map"),
+      TGAError::UnsupportedColorMapDepth(d) => write!(f, "unsupported color map depth {}", d),
+      TGAError::ColorIndexOutOfRange(i) => write!(f, "color index {} out of range", i),
+    }
+  }
+}
+impl std::error::Error for TGAError {}
+impl From<TGAError> for Error {
+  fn from(e: TGAError) -> Error {
+    match e {
+      TGAError::UnsupportedImageType(_)
+      | TGAError::UnsupportedDepth { .. }
+      | TGAError::UnsupportedColorMapDepth(_) => Error::unsupported(e.to_string()),
+      _ => Error::format(e.to_string()),
+    }
+  }
+}
+
+// 把一个像素(或颜色表的一项)的原始字节转换为 RGBA。
+// 16 位为 ARGB1555，只有图片描述中有透明度位时才使用最高位作为透明度
+fn to_rgba(bytes: &[u8], gray: bool, alpha_1555: bool) -> [u8; 4] {
+  match (gray, bytes) {
+    (true, &[v]) => [v, v, v, 255],
+    (true, &[v, a]) => [v, v, v, a],
+    (false, &[lo, hi]) => {
+      let v = u16::from_le_bytes([lo, hi]);
+      let c = |shift: u16| (((v >> shift) & 0x1f) as u32 * 255 / 31) as u8;
+      let a = if alpha_1555 && v & 0x8000 == 0 {
+        0
+      } else {
+        255
+      };
+      [c(10), c(5), c(0), a]
+    }
+    (false, &[b, g, r]) => [r, g, b, 255],
+    _ => [bytes[2], bytes[1], bytes[0], bytes[3]],
+  }
+}
+
+// 展开 RLE 数据为 count 个 bpp 字节的像素，包可以跨行
+fn expand_rle(data: &[u8], bpp: usize, count: usize) -> Result<Vec<u8>, TGAError> {
+  // 像素数来自文件头，一个 1 + bpp 字节的重复包最多展开为 128 个像素，数据不够时不分配内存
+  if count > data.len() / (1 + bpp) * 128 {
+    return Err(TGAError::Truncated);
+  }
+  let mut ret = Vec::with_capacity(count * bpp);
+  let mut pos = 0;
+  while ret.len() < count * bpp {
+    let packet = *data.get(pos).ok_or(TGAError::Truncated)?;
+    pos += 1;
+    let n = (packet & 0x7f) as usize + 1;
+    if packet & 0x80 != 0 {
+      let pixel = data.get(pos..pos + bpp).ok_or(TGAError::Truncated)?;
+      pos += bpp;
+      for _ in 0..n {
+        ret.extend_from_slice(pixel);
+      }
+    } else {
+      let pixels = data.get(pos..pos + n * bpp).ok_or(TGAError::Truncated)?;
+      pos += n * bpp;
+      ret.extend_from_slice(pixels);
+    }
+  }
+  // 最后一个包可能超出图片
+  ret.truncate(count * bpp);
+  Ok(ret)
+}
+
+impl TGA {
+  pub fn try_decode(&self, data: &[u8]) -> Result<PixImage, TGAError> {
+    let header = data.get(..HEADER_SIZE).ok_or(TGAError::Truncated)?;
+    let u16_at = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+    let id_len = header[0] as usize;
+    let has_color_map = header[1] == 1;
+    let image_type = header[2];
+    let (first_entry, map_len, map_depth) = (u16_at(3), u16_at(5) as usize, header[7]);
+    let (width, height) = (u16_at(12) as usize, u16_at(14) as usize);
+    let depth = header[16];
+    let descriptor = header[17];
+
+    let (color_mapped, gray) = match image_type & !8 {
+      0 => return Err(TGAError::NoImageData),
+      1 => (true, false),
+      2 => (false, false),
+      3 => (false, true),
+      _ => return Err(TGAError::UnsupportedImageType(image_type)),
+    };
+    let depth_ok = match (color_mapped, gray) {
+      (true, _) => matches!(depth, 8 | 16),
+      (_, true) => matches!(depth, 8 | 16),
+      _ => matches!(depth, 15 | 16 | 24 | 32),
+    };
+    if !depth_ok || image_type & 0xf0 != 0 {
+      return Err(if depth_ok {
+        TGAError::UnsupportedImageType(image_type)
+      } else {
+        TGAError::UnsupportedDepth { image_type, depth }
+      });
+    }
+    let alpha_1555 = descriptor & 0x0f != 0;
+
+    // 真彩色和灰度图片也可以带有颜色表，跳过即可
+    let mut pos = HEADER_SIZE + id_len;
+    let mut palette = vec![];
+    if has_color_map {
+      if !matches!(map_depth, 15 | 16 | 24 | 32) {
+        return Err(TGAError::UnsupportedColorMapDepth(map_depth));
+      }
+      let entry_size = (map_depth as usize).div_ceil(8);
+      let entries = data
+        .get(pos..pos + map_len * entry_size)
+        .ok_or(TGAError::Truncated)?;
+      pos += map_len * entry_size;
+      if color_mapped {
+        palette = entries
+          .chunks_exact(entry_size)
+          .map(|entry| to_rgba(entry, false, alpha_1555))
+          .collect();
+      }
+    } else if color_mapped {
+      return Err(TGAError::MissingColorMap);
+    }
+
+    let bpp = (depth as usize).div_ceil(8);
+    let count = width * height;
+    let pixels: Cow<[u8]> = if image_type & 8 != 0 {
+      Cow::Owned(expand_rle(data.get(pos..).unwrap_or(&[]), bpp, count)?)
+    } else {
+      Cow::Borrowed(
+        data
+          .get(pos..pos + count * bpp)
+          .ok_or(TGAError::Truncated)?,
+      )
+    };
+
+    let right = descriptor & 0x10 != 0;
+    let top = descriptor & 0x20 != 0;
+    let mut ret = vec![0u8; count * 4];
+    for (i, pixel) in pixels.chunks_exact(bpp).enumerate() {
+      let (row, col) = (i / width, i % width);
+      let col = if right { width - 1 - col } else { col };
+      let rgba = if color_mapped {
+        let index = pixel.iter().rev().fold(0u16, |v, &b| v << 8 | b as u16);
+        *index
+          .checked_sub(first_entry)
+          .and_then(|i| palette.get(i as usize))
+          .ok_or(TGAError::ColorIndexOutOfRange(index))?
+      } else {
+        to_rgba(pixel, gray, alpha_1555)
+      };
+      ret[(row * width + col) * 4..][..4].copy_from_slice(&rgba);
+    }
+    // 从上到下存储时第一行是图片的最上面一行
+    let origin = if top {
+      ImageOriginPos::LeftBottom
+    } else {
+      ImageOriginPos::LeftTop
+    };
+    Ok(PixImage::from_data(
+      ret,
+      width as u32,
+      height as u32,
+      origin,
+    ))
+  }
+}
+
+impl Decoder for TGA {
+  fn decode(&self, data: Vec<u8>) -> crate::error::Result<PixImage> {
+    Ok(self.try_decode(&data)?)
+  }
+}
+
+// 每个 RLE 包最多 128 个像素
+const TGA_MAX_PACKET: usize = 128;
+
+// 把一行像素(每个 bpp 字节)压缩为 RLE 包，包不跨行
+fn tga_rle_row(row: &[u8], bpp: usize, out: &mut Vec<u8>) {
+  let pixels: Vec<&[u8]> = row.chunks_exact(bpp).collect();
+  let mut i = 0;
+  while i < pixels.len() {
+    let mut run = 1;
+    while i + run < pixels.len() && run < TGA_MAX_PACKET && pixels[i + run] == pixels[i] {
+      run += 1;
+    }
+    if run > 1 {
+      out.push(0x80 | (run - 1) as u8);
+      out.extend_from_slice(pixels[i]);
+      i += run;
+      continue;
+    }
+    // 不压缩的包延伸到下一段重复的像素之前
+    let mut len = 1;
+    while i + len < pixels.len()
+      && len < TGA_MAX_PACKET
+      && (i + len + 1 == pixels.len() || pixels[i + len] != pixels[i + len + 1])
+    {
+      len += 1;
+    }
+    out.push((len - 1) as u8);
+    for p in &pixels[i..i + len] {
+      out.extend_from_slice(p);
+    }
+    i += len;
+  }
+}
+
+// 24/32 位真彩色(BGR/BGRA)或 8 位灰度，可选 RLE 压缩和第一个像素的位置
+impl Encoder for TGA {
+  fn encode<I: Image>(&self, img: &I) -> Vec<u8> {
+    let (w, h) = (img.width(), img.height());
+    assert!(
+      w <= u16::MAX as u32 && h <= u16::MAX as u32,
+      "image is too large for TGA"
+    );
+    let (image_type, depth, alpha_bits) = match self.format {
+      TGAFormat::Rgb24 => (2, 24, 0),
+      TGAFormat::Rgba32 => (2, 32, 8),
+      TGAFormat::Gray8 => (3, 8, 0),
+    };
+    let (right, top) = match self.origin {
+      ScreenDestination::BottomLeft => (false, false),
+      ScreenDestination::BottomRight => (true, false),
+      ScreenDestination::TopLeft => (false, true),
+      ScreenDestination::TopRight => (true, true),
+    };
+    let mut ret = Vec::with_capacity(18 + (w * h * depth / 8) as usize);
+    // 没有 image id 和颜色表，RLE 的类型为不压缩的类型 + 8
+    ret.extend([0, 0, image_type + if self.rle { 8 } else { 0 }]);
+    ret.extend([0; 9]);
+    ret.extend((w as u16).to_le_bytes());
+    ret.extend((h as u16).to_le_bytes());
+    ret.push(depth as u8);
+    ret.push(alpha_bits | (right as u8) << 4 | (top as u8) << 5);
+
+    let bpp = depth as usize / 8;
+    let mut row = Vec::with_capacity(w as usize * bpp);
+    for i in 0..h {
+      let y = if top { h - 1 - i } else { i };
+      row.clear();
+      for j in 0..w {
+        let x = if right { w - 1 - j } else { j };
+        let c = img.get_rgba(x, y);
+        match self.format {
+          TGAFormat::Rgb24 => row.extend([c.z, c.y, c.x]),
+          TGAFormat::Rgba32 => row.extend([c.z, c.y, c.x, c.w]),
+          TGAFormat::Gray8 => row.push(luma(c)),
+        }
+      }
+      if self.rle {
+        tga_rle_row(&row, bpp, &mut ret);
+      } else {
+        ret.extend_from_slice(&row);
+      }
+    }
+    ret
+  }
+}
+
+#[test]
+fn test_tga_encode() {
+  use crate::geometry::Vec4;
+  let mut img = PixImage::new(140, 3);
+  for y in 0..3 {
+    for x in 0..140 {
+      // 前 130 个像素重复，超过一个 RLE 包的长度
+      let v = if x < 130 { 10 } else { x as u8 };
+      img.set_rgba32(x, y, Vec4::new(v, y as u8 * 100, 7, 200 - y as u8));
+    }
+  }
+  let mut rle_row = vec![];
+  tga_rle_row(&[1, 1, 1, 2, 3, 3], 1, &mut rle_row);
+  assert_eq!(rle_row, [0x82, 1, 0, 2, 0x81, 3]);
+
+  for format in [TGAFormat::Rgb24, TGAFormat::Rgba32, TGAFormat::Gray8] {
+    for rle in [false, true] {
+      for origin in [
+        ScreenDestination::BottomLeft,
+        ScreenDestination::BottomRight,
+        ScreenDestination::TopLeft,
+        ScreenDestination::TopRight,
+      ] {
+        let tga = TGA::new().format(format).rle(rle).origin(origin);
+        let data = tga.encode(&img);
+        if rle {
+          assert!(data.len() < 18 + 140 * 3 * 3);
+        }
+        let decoded = TGA::new().decode(data).unwrap();
+        for y in 0..3 {
+          for x in 0..140 {
+            let (a, b) = (img.get_rgba(x, y), decoded.get_rgba(x, y));
+            let expected = match format {
+              TGAFormat::Rgb24 => [a.x, a.y, a.z, 255],
+              TGAFormat::Rgba32 => [a.x, a.y, a.z, a.w],
+              TGAFormat::Gray8 => {
+                let l = luma(a);
+                [l, l, l, 255]
+              }
+            };
+            assert_eq!([b.x, b.y, b.z, b.w], expected);
+          }
+        }
+      }
+    }
+  }
+}
+
+#[test]
+fn test_tga_decode() {
+  // extra 为 image id 和颜色表
+  let tga = |image_type: u8,
+             map: (u16, u16, u8),
+             depth: u8,
+             descriptor: u8,
+             extra: &[u8],
+             pixels: &[u8]| {
+    let mut data = vec![3, (map.1 > 0) as u8, image_type];
+    data.extend(map.0.to_le_bytes());
+    data.extend(map.1.to_le_bytes());
+    data.push(map.2);
+    data.extend([0; 4]);
+    data.extend(2u16.to_le_bytes());
+    data.extend(2u16.to_le_bytes());
+    data.extend([depth, descriptor]);
+    data.extend(b"id!");
+    data.extend(extra);
+    data.extend(pixels);
+    data
+  };
+  // 第一行是最上面一行
+  let rows = |img: &PixImage| -> Vec<[u8; 4]> {
+    [(0, 1), (1, 1), (0, 0), (1, 0)]
+      .iter()
+      .map(|&(x, y)| {
+        let c = img.get_rgba(x, y);
+        [c.x, c.y, c.z, c.w]
+      })
+      .collect()
+  };
+  let (r, g, b, w) = (
+    [255, 0, 0, 255],
+    [0, 255, 0, 255],
+    [0, 0, 255, 255],
+    [255; 4],
+  );
+
+  // RLE 颜色表，第一项的索引为 10，24 位的项；包跨越两行，从下到上
+  let map = [0, 0, 255, 0, 255, 0, 255, 0, 0];
+  let data = tga(9, (10, 3, 24), 8, 0, &map, &[0x82, 10, 0, 12]);
+  assert_eq!(rows(&TGA::new().decode(data).unwrap()), [r, b, r, r]);
+
+  // 16 位 ARGB1555，有 1 位透明度，右上角为原点
+  let pixels: Vec<u8> = [0xfc00u16, 0x03e0, 0x801f, 0xffff]
+    .iter()
+    .flat_map(|v| v.to_le_bytes())
+    .collect();
+  let img = TGA::new()
+    .decode(tga(2, (0, 0, 0), 16, 0x31, &[], &pixels))
+    .unwrap();
+  assert_eq!(rows(&img), [[0, 255, 0, 0], [255, 0, 0, 255], w, b]);
+
+  // 不压缩的颜色表，16 位的项；灰度
+  let map = [0x00, 0x7c, 0xe0, 0x03];
+  let img = TGA::new()
+    .decode(tga(1, (0, 2, 16), 8, 0x20, &map, &[0, 1, 1, 0]))
+    .unwrap();
+  assert_eq!(rows(&img), [r, g, g, r]);
+  let img = TGA::new()
+    .decode(tga(3, (0, 0, 0), 8, 0, &[], &[0, 85, 170, 255]))
+    .unwrap();
+  let gray = |v| [v, v, v, 255];
+  assert_eq!(rows(&img), [gray(170), w, gray(0), gray(85)]);
+
+  // 不完整或不支持的数据返回错误
+  let data = tga(
+    9,
+    (10, 3, 24),
+    8,
+    0,
+    &[0, 0, 255, 0, 255, 0, 255, 0, 0],
+    &[0x82, 10, 0, 12],
+  );
+  for len in 0..data.len() {
+    assert_eq!(
+      TGA::new().try_decode(&data[..len]).err(),
+      Some(TGAError::Truncated)
+    );
+  }
+  let errors = [
+    (
+      tga(9, (10, 3, 24), 8, 0, &map[..0], &[]),
+      TGAError::Truncated,
+    ),
+    (tga(0, (0, 0, 0), 8, 0, &[], &[]), TGAError::NoImageData),
+    (
+      tga(32, (0, 0, 0), 8, 0, &[], &[]),
+      TGAError::UnsupportedImageType(32),
+    ),
+    (
+      tga(2, (0, 0, 0), 8, 0, &[], &[]),
+      TGAError::UnsupportedDepth {
+        image_type: 2,
+        depth: 8,
+      },
+    ),
+    (
+      tga(1, (0, 0, 0), 8, 0, &[], &[0; 4]),
+      TGAError::MissingColorMap,
+    ),
+    (
+      tga(1, (0, 1, 8), 8, 0, &[0], &[0; 4]),
+      TGAError::UnsupportedColorMapDepth(8),
+    ),
+    (
+      tga(1, (0, 2, 16), 8, 0, &map, &[0, 1, 2, 0]),
+      TGAError::ColorIndexOutOfRange(2),
+    ),
+  ];
+  for (data, error) in errors {
+    assert_eq!(TGA::new().try_decode(&data).err(), Some(error));
+  }
+  // 23 字节的 RLE 文件声明 65535x65535 的 32 位图片，不按文件头分配内存
+  let mut huge = vec![
+    0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 32, 8,
+  ];
+  huge.extend([0xff, 1, 2, 3, 4]);
+  assert_eq!(huge.len(), 23);
+  assert_eq!(
+    TGA::new().try_decode(&huge).err(),
+    Some(TGAError::Truncated)
+  );
+
+  let texture = TGA::new()
+    .decode(std::fs::read("models/spot/spot_texture.tga").unwrap())
+    .unwrap();
+  assert_eq!((texture.width, texture.height), (1024, 1024));
+}
@@ -87,6 +87,15 @@ pub fn viewport(w: f32, h: f32) -> Mat4 {
     .build()
 }
 
+// 将标准立方体映射到图像中的矩形区域 [x,x+w]x[y,y+h]，z 从 [-1,1] 映射到 [near,far]
+pub fn viewport_rect(x: f32, y: f32, w: f32, h: f32, near: f32, far: f32) -> Mat4 {
+  Transform::new()
+    .translate(1., 1., 1.)
+    .scale(w / 2., h / 2., (far - near) / 2.)
+    .translate(x, y, near)
+    .build()
+}
+
 pub fn camera(up: Vec3<f32>, pos: Vec3<f32>, lookat: Vec3<f32>) -> Mat4 {
   let up = up.normalize();
   let looking = (lookat - pos).normalize();
@@ -121,6 +130,42 @@ pub fn perspective(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Mat4 {
   &orthographic(left, right, bottom, top, far, near) * &m
 }
 
+// 投影后 x,y 的缩放系数，与 perspective 一致
+fn perspective_scale(fov: f32, aspect_ratio: f32) -> (f32, f32) {
+  let t = (fov * std::f32::consts::PI / 180. / 2.).tan();
+  (-1. / (aspect_ratio * t), -1. / t)
+}
+
+// 反向 Z(reversed-Z)：近平面映射到 1，远平面映射到 0，仍然是越大越近。
+// 浮点数在 0 附近精度最高，正好抵消透视除法带来的远处精度损失
+pub fn perspective_reversed(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Mat4 {
+  let (sx, sy) = perspective_scale(fov, aspect_ratio);
+  // z_ndc = a + b / z，z=near 时为 1，z=far 时为 0
+  let b = near * far / (far - near);
+  let a = -near / (far - near);
+  #[rustfmt::skip]
+  let m = Mat4([
+    sx, 0., 0., 0.,
+    0., sy, 0., 0.,
+    0., 0., a,  b,
+    0., 0., 1., 0.
+  ]);
+  m
+}
+
+// 远平面在无穷远处的反向 Z 投影，z_ndc = near / z
+pub fn perspective_infinite_reversed(fov: f32, aspect_ratio: f32, near: f32) -> Mat4 {
+  let (sx, sy) = perspective_scale(fov, aspect_ratio);
+  #[rustfmt::skip]
+  let m = Mat4([
+    sx, 0., 0., 0.,
+    0., sy, 0., 0.,
+    0., 0., 0., near,
+    0., 0., 1., 0.
+  ]);
+  m
+}
+
 pub struct Transform {
   mat: Mat4,
 }
@@ -139,6 +184,12 @@ impl Transform {
   pub fn perspective(mut self, fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Transform {
     self.then_mat(&perspective(fov, aspect_ratio, near, far))
   }
+  pub fn perspective_reversed(self, fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Transform {
+    self.then_mat(&perspective_reversed(fov, aspect_ratio, near, far))
+  }
+  pub fn perspective_infinite_reversed(self, fov: f32, aspect_ratio: f32, near: f32) -> Transform {
+    self.then_mat(&perspective_infinite_reversed(fov, aspect_ratio, near))
+  }
   pub fn scale(mut self, sx: f32, sy: f32, sz: f32) -> Transform {
     self.mat = &scale(sx, sy, sz) * &self.mat;
     self
@@ -175,3 +226,20 @@ impl Transform {
     self.mat.clone()
   }
 }
+
+#[test]
+fn test_perspective_reversed() {
+  let near = -0.1;
+  let far = -100.;
+  let m = perspective_reversed(60., 1., near, far);
+  let z = |z: f32| (&m * &Vec3::new(0., 0., z)).z;
+  assert!((z(near) - 1.).abs() < 1e-5);
+  assert!(z(far).abs() < 1e-5);
+  let m = perspective_infinite_reversed(60., 1., near);
+  let p = &m * &Vec3::new(0., 0., near);
+  assert!((p.z - 1.).abs() < 1e-5);
+  let p = perspective(60., 1., near, far);
+  let q = perspective_reversed(60., 1., near, far);
+  let v = Vec3::new(0.3, -0.2, -2.);
+  assert!(((&p * &v).x - (&q * &v).x).abs() < 1e-5);
+}
@@ -1,9 +1,9 @@
 use crate::prelude::{Image, PixImage, Vec3};
 
-pub fn load_image<P: AsRef<std::path::Path>>(p: P) -> crate::image::PixImage {
-  use crate::image_decoder::*;
-  let tga = TGA;
-  tga.decode(std::fs::read(p).unwrap())
+// 按文件头和扩展名识别格式，见 format 模块。
+// 读取失败或解码失败时返回的错误带有文件路径
+pub fn load_image<P: AsRef<std::path::Path>>(p: P) -> crate::error::Result<crate::image::PixImage> {
+  crate::format::load(p)
 }
 
 pub fn linear_interpolation<S, T>(t: S, a: T, b: T) -> T
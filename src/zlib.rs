@@ -0,0 +1,914 @@
+// 自己实现的 deflate(RFC 1951) 和 zlib(RFC 1950) 压缩与解压，以及 PNG 使用的 CRC32，
+// 使 PNG 编解码不需要依赖其他的库。
+// 压缩：哈希链查找 LZ77 匹配(带一步惰性匹配)，每个块在存储、固定哈夫曼和动态哈夫曼中选最小的。
+// 解压：短码查表，长码逐位按规范哈夫曼码解码。
+
+// 长度码 257~285 的基础长度和额外位数
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+  3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+  163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+  0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+// 距离码 0~29 的基础距离和额外位数
+pub(crate) const DIST_BASE: [u16; 30] = [
+  1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049,
+  3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(crate) const DIST_EXTRA: [u8; 30] = [
+  0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// 动态块头部中码长的码长的顺序
+pub(crate) const CODE_LENGTH_ORDER: [usize; 19] = [
+  16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+pub(crate) const END_OF_BLOCK: usize = 256;
+
+const WINDOW_SIZE: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+// 哈希链上最多比较的位置数，越大压缩率越高、越慢
+const MAX_CHAIN: usize = 128;
+// 找到这么长的匹配后不再做惰性匹配
+const GOOD_MATCH: usize = 32;
+// 每个块最多包含的记号数
+const BLOCK_TOKENS: usize = 1 << 14;
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE: u8 = 7;
+// 解码时直接查表的码长
+const FAST_BITS: u32 = 9;
+
+// 压缩数据损坏或不完整
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InflateError(pub &'static str);
+impl std::fmt::Display for InflateError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "invalid deflate data: {}", self.0)
+  }
+}
+impl std::error::Error for InflateError {}
+// 解压后的数据超过 decompress_limited 的限制
+const TOO_LARGE: InflateError = InflateError("output exceeds the size limit");
+
+pub fn adler32(data: &[u8]) -> u32 {
+  const MOD: u32 = 65521;
+  let (mut a, mut b) = (1u32, 0u32);
+  // 5552 是 b 不会溢出 u32 的最大块长
+  for chunk in data.chunks(5552) {
+    for &byte in chunk {
+      a += byte as u32;
+      b += a;
+    }
+    a %= MOD;
+    b %= MOD;
+  }
+  b << 16 | a
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+  crc32_update(0, data)
+}
+
+// 在 crc 的基础上继续计算，PNG 的块类型和数据可以分开计算
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+  static TABLE: std::sync::OnceLock<[u32; 256]> = std::sync::OnceLock::new();
+  let table = TABLE.get_or_init(|| {
+    std::array::from_fn(|n| {
+      (0..8).fold(n as u32, |c, _| {
+        if c & 1 == 1 {
+          0xedb88320 ^ (c >> 1)
+        } else {
+          c >> 1
+        }
+      })
+    })
+  });
+  !data.iter().fold(!crc, |c, &byte| {
+    table[((c ^ byte as u32) & 0xff) as usize] ^ (c >> 8)
+  })
+}
+
+// zlib 格式：2 字节头部、deflate 数据和大端的 Adler32
+pub fn compress(data: &[u8]) -> Vec<u8> {
+  // 32K 窗口的 deflate，默认压缩级别，(CMF * 256 + FLG) 是 31 的倍数
+  let mut out = vec![0x78, 0x9c];
+  out.extend(deflate(data));
+  out.extend(adler32(data).to_be_bytes());
+  out
+}
+
+// 解压 zlib 格式的数据并检查 Adler32
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, InflateError> {
+  decompress_limited(data, usize::MAX)
+}
+
+// 解压后的数据超过 max 字节时返回错误，用于预先知道大小的数据，避免少量的输入展开为大量的输出
+pub fn decompress_limited(data: &[u8], max: usize) -> Result<Vec<u8>, InflateError> {
+  if data.len() < 6 {
+    return Err(InflateError("zlib stream is too short"));
+  }
+  let (cmf, flg) = (data[0], data[1]);
+  if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+    return Err(InflateError("unknown zlib compression method"));
+  }
+  if !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+    return Err(InflateError("bad zlib header check"));
+  }
+  if flg & 0x20 != 0 {
+    return Err(InflateError("preset dictionary is not supported"));
+  }
+  let mut reader = BitReader::new(&data[2..]);
+  let out = inflate_from(&mut reader, max)?;
+  reader.align();
+  let end = 2 + reader.byte_pos();
+  let checksum = data
+    .get(end..end + 4)
+    .ok_or(InflateError("missing Adler32"))?;
+  if u32::from_be_bytes(checksum.try_into().unwrap()) != adler32(&out) {
+    return Err(InflateError("Adler32 mismatch"));
+  }
+  Ok(out)
+}
+
+// 解压原始的 deflate 数据
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, InflateError> {
+  inflate_from(&mut BitReader::new(data), usize::MAX)
+}
+
+// 按从低位到高位的顺序读取，越过结尾时读到 0 并记录
+struct BitReader<'a> {
+  data: &'a [u8],
+  // 下一个要装入 acc 的字节
+  pos: usize,
+  acc: u64,
+  n: u32,
+  // 越过结尾装入的字节数
+  overrun: usize,
+}
+impl<'a> BitReader<'a> {
+  fn new(data: &'a [u8]) -> BitReader<'a> {
+    BitReader {
+      data,
+      pos: 0,
+      acc: 0,
+      n: 0,
+      overrun: 0,
+    }
+  }
+  fn refill(&mut self) {
+    while self.n <= 56 {
+      let byte = match self.data.get(self.pos) {
+        Some(&b) => {
+          self.pos += 1;
+          b
+        }
+        None => {
+          self.overrun += 1;
+          0
+        }
+      };
+      self.acc |= (byte as u64) << self.n;
+      self.n += 8;
+    }
+  }
+  fn peek(&mut self, bits: u32) -> u32 {
+    if self.n < bits {
+      self.refill();
+    }
+    (self.acc & ((1u64 << bits) - 1)) as u32
+  }
+  fn consume(&mut self, bits: u32) -> Result<(), InflateError> {
+    self.n -= bits;
+    self.acc >>= bits;
+    // 使用的位超出了数据的结尾
+    if self.overrun * 8 > self.n as usize {
+      return Err(InflateError("unexpected end of data"));
+    }
+    Ok(())
+  }
+  fn read(&mut self, bits: u32) -> Result<u32, InflateError> {
+    let v = self.peek(bits);
+    self.consume(bits)?;
+    Ok(v)
+  }
+  fn align(&mut self) {
+    let skip = self.n % 8;
+    self.n -= skip;
+    self.acc >>= skip;
+  }
+  // 已经读取的字节数(按字节对齐)
+  fn byte_pos(&self) -> usize {
+    self.pos - (self.n / 8) as usize + self.overrun
+  }
+}
+
+// 规范哈夫曼码的解码表
+struct Huffman {
+  // 码长为 FAST_BITS 以内的码：按位反转的码 -> 符号 << 4 | 码长，为 0 时需要逐位解码
+  fast: Vec<u16>,
+  // 每个码长的码的个数
+  count: [u16; 16],
+  // 按码的顺序排列的符号
+  symbols: Vec<u16>,
+}
+impl Huffman {
+  fn new(lengths: &[u8]) -> Result<Huffman, InflateError> {
+    let mut count = [0u16; 16];
+    for &l in lengths {
+      count[l as usize] += 1;
+    }
+    count[0] = 0;
+    // 检查码是否超额：每个码长剩余可用的码的个数不能为负
+    let mut left = 1i32;
+    for &c in &count[1..] {
+      left = left * 2 - c as i32;
+      if left < 0 {
+        return Err(InflateError("over-subscribed Huffman code"));
+      }
+    }
+    let mut offsets = [0u16; 16];
+    for len in 1..15 {
+      offsets[len + 1] = offsets[len] + count[len];
+    }
+    let mut symbols = vec![0; lengths.len()];
+    for (sym, &l) in lengths.iter().enumerate() {
+      if l > 0 {
+        symbols[offsets[l as usize] as usize] = sym as u16;
+        offsets[l as usize] += 1;
+      }
+    }
+    let codes = canonical_codes(lengths);
+    let mut fast = vec![0u16; 1 << FAST_BITS];
+    for (sym, &l) in lengths.iter().enumerate() {
+      if l == 0 || l as u32 > FAST_BITS {
+        continue;
+      }
+      let reversed = (codes[sym].reverse_bits() >> (16 - l as u32)) as usize;
+      for k in 0..1 << (FAST_BITS - l as u32) {
+        fast[reversed | k << l] = (sym as u16) << 4 | l as u16;
+      }
+    }
+    Ok(Huffman {
+      fast,
+      count,
+      symbols,
+    })
+  }
+  fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+    let entry = self.fast[reader.peek(FAST_BITS) as usize];
+    if entry != 0 {
+      reader.consume((entry & 0xf) as u32)?;
+      return Ok(entry >> 4);
+    }
+    // 逐位解码：first 为当前码长的第一个码，index 为它在 symbols 中的位置
+    let bits = reader.peek(MAX_CODE_LENGTH as u32);
+    let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+    for len in 1..=MAX_CODE_LENGTH as u32 {
+      code |= (bits >> (len - 1) & 1) as i32;
+      let count = self.count[len as usize] as i32;
+      if code - first < count {
+        reader.consume(len)?;
+        return Ok(self.symbols[(index + code - first) as usize]);
+      }
+      index += count;
+      first = (first + count) << 1;
+      code <<= 1;
+    }
+    Err(InflateError("invalid Huffman code"))
+  }
+}
+
+fn inflate_from(reader: &mut BitReader, max: usize) -> Result<Vec<u8>, InflateError> {
+  let mut out = vec![];
+  loop {
+    let last = reader.read(1)? == 1;
+    match reader.read(2)? {
+      0 => {
+        reader.align();
+        let len = reader.read(16)?;
+        let nlen = reader.read(16)?;
+        if len != !nlen & 0xffff {
+          return Err(InflateError("stored block length mismatch"));
+        }
+        if out.len() + len as usize > max {
+          return Err(TOO_LARGE);
+        }
+        for _ in 0..len {
+          out.push(reader.read(8)? as u8);
+        }
+      }
+      1 => {
+        let (lit, dist) = fixed_lengths();
+        inflate_block(
+          reader,
+          &Huffman::new(&lit)?,
+          &Huffman::new(&dist)?,
+          &mut out,
+          max,
+        )?;
+      }
+      2 => {
+        let (lit, dist) = read_dynamic_lengths(reader)?;
+        inflate_block(
+          reader,
+          &Huffman::new(&lit)?,
+          &Huffman::new(&dist)?,
+          &mut out,
+          max,
+        )?;
+      }
+      _ => return Err(InflateError("invalid block type")),
+    }
+    if last {
+      return Ok(out);
+    }
+  }
+}
+
+fn read_dynamic_lengths(reader: &mut BitReader) -> Result<(Vec<u8>, Vec<u8>), InflateError> {
+  let hlit = reader.read(5)? as usize + 257;
+  let hdist = reader.read(5)? as usize + 1;
+  let hclen = reader.read(4)? as usize + 4;
+  if hlit > 286 || hdist > 30 {
+    return Err(InflateError("too many length or distance codes"));
+  }
+  let mut cl_lengths = [0u8; 19];
+  for &i in &CODE_LENGTH_ORDER[..hclen] {
+    cl_lengths[i] = reader.read(3)? as u8;
+  }
+  let cl = Huffman::new(&cl_lengths)?;
+  let mut lengths = Vec::with_capacity(hlit + hdist);
+  while lengths.len() < hlit + hdist {
+    let (value, repeat) = match cl.decode(reader)? {
+      sym @ 0..=15 => (sym as u8, 1),
+      16 => {
+        let &prev = lengths
+          .last()
+          .ok_or(InflateError("repeat with no previous length"))?;
+        (prev, 3 + reader.read(2)?)
+      }
+      17 => (0, 3 + reader.read(3)?),
+      _ => (0, 11 + reader.read(7)?),
+    };
+    if lengths.len() + repeat as usize > hlit + hdist {
+      return Err(InflateError("too many code lengths"));
+    }
+    lengths.extend(std::iter::repeat_n(value, repeat as usize));
+  }
+  if lengths[END_OF_BLOCK] == 0 {
+    return Err(InflateError("missing end-of-block code"));
+  }
+  let dist = lengths.split_off(hlit);
+  Ok((lengths, dist))
+}
+
+fn inflate_block(
+  reader: &mut BitReader,
+  lit: &Huffman,
+  dist: &Huffman,
+  out: &mut Vec<u8>,
+  max: usize,
+) -> Result<(), InflateError> {
+  loop {
+    let sym = lit.decode(reader)? as usize;
+    if sym < END_OF_BLOCK {
+      if out.len() >= max {
+        return Err(TOO_LARGE);
+      }
+      out.push(sym as u8);
+      continue;
+    }
+    if sym == END_OF_BLOCK {
+      return Ok(());
+    }
+    let lc = sym - 257;
+    if lc >= LENGTH_BASE.len() {
+      return Err(InflateError("invalid length code"));
+    }
+    let len = LENGTH_BASE[lc] as usize + reader.read(LENGTH_EXTRA[lc] as u32)? as usize;
+    let dc = dist.decode(reader)? as usize;
+    if dc >= DIST_BASE.len() {
+      return Err(InflateError("invalid distance code"));
+    }
+    let d = DIST_BASE[dc] as usize + reader.read(DIST_EXTRA[dc] as u32)? as usize;
+    if d > out.len() {
+      return Err(InflateError("distance is too far back"));
+    }
+    if out.len() + len > max {
+      return Err(TOO_LARGE);
+    }
+    // 匹配可以与正在输出的数据重叠，需要逐字节复制
+    let start = out.len() - d;
+    for k in 0..len {
+      out.push(out[start + k]);
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+  Literal(u8),
+  Match { len: u16, dist: u16 },
+}
+
+// 按从低位到高位的顺序写入
+pub(crate) struct BitWriter {
+  bytes: Vec<u8>,
+  acc: u64,
+  n: u32,
+}
+impl BitWriter {
+  pub(crate) fn new() -> BitWriter {
+    BitWriter {
+      bytes: vec![],
+      acc: 0,
+      n: 0,
+    }
+  }
+  pub(crate) fn write(&mut self, value: u32, bits: u32) {
+    self.acc |= (value as u64) << self.n;
+    self.n += bits;
+    while self.n >= 8 {
+      self.bytes.push(self.acc as u8);
+      self.acc >>= 8;
+      self.n -= 8;
+    }
+  }
+  // 哈夫曼码从最高位开始写
+  fn write_code(&mut self, code: u16, len: u8) {
+    self.write(code.reverse_bits() as u32 >> (16 - len as u32), len as u32);
+  }
+  pub(crate) fn align(&mut self) {
+    if self.n > 0 {
+      self.write(0, 8 - self.n);
+    }
+  }
+  pub(crate) fn finish(mut self) -> Vec<u8> {
+    self.align();
+    self.bytes
+  }
+}
+
+// 原始的 deflate 数据，没有 zlib 头部
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+  let tokens = lz77(data);
+  let mut out = BitWriter::new();
+  let mut pos = 0;
+  let mut blocks = tokens.chunks(BLOCK_TOKENS).peekable();
+  if blocks.peek().is_none() {
+    // 空数据也需要一个结束块
+    write_fixed_block(&mut out, &[], true);
+  }
+  while let Some(block) = blocks.next() {
+    let last = blocks.peek().is_none();
+    let raw_len: usize = block
+      .iter()
+      .map(|t| match t {
+        Token::Literal(_) => 1,
+        Token::Match { len, .. } => *len as usize,
+      })
+      .sum();
+    write_block(&mut out, block, &data[pos..pos + raw_len], last);
+    pos += raw_len;
+  }
+  out.finish()
+}
+
+// 哈希链：head[h] 为哈希值为 h 的最近位置加 1，prev 为链上的前一个位置加 1，0 表示没有
+struct Matcher<'a> {
+  data: &'a [u8],
+  head: Vec<usize>,
+  prev: Vec<usize>,
+}
+impl<'a> Matcher<'a> {
+  fn new(data: &'a [u8]) -> Matcher<'a> {
+    Matcher {
+      data,
+      head: vec![0; 1 << HASH_BITS],
+      prev: vec![0; WINDOW_SIZE],
+    }
+  }
+  fn hash(&self, i: usize) -> usize {
+    let d = self.data;
+    let v = (d[i] as u32) << 16 | (d[i + 1] as u32) << 8 | d[i + 2] as u32;
+    (v.wrapping_mul(0x9e3779b1) >> (32 - HASH_BITS)) as usize
+  }
+  fn insert(&mut self, i: usize) {
+    if i + MIN_MATCH <= self.data.len() {
+      let h = self.hash(i);
+      self.prev[i % WINDOW_SIZE] = self.head[h];
+      self.head[h] = i + 1;
+    }
+  }
+  // 位置 i 处最长的匹配 (长度, 距离)，没有时长度为 0
+  fn find(&self, i: usize) -> (usize, usize) {
+    let data = self.data;
+    if i + MIN_MATCH > data.len() {
+      return (0, 0);
+    }
+    let max_len = MAX_MATCH.min(data.len() - i);
+    let (mut best_len, mut best_dist) = (0, 0);
+    let mut candidate = self.head[self.hash(i)];
+    for _ in 0..MAX_CHAIN {
+      if candidate == 0 {
+        break;
+      }
+      let j = candidate - 1;
+      if j >= i || i - j > WINDOW_SIZE {
+        break;
+      }
+      // 先比较能使匹配变长的字节
+      let k = best_len.min(max_len - 1);
+      if data[j + k] == data[i + k] {
+        let len = data[j..j + max_len]
+          .iter()
+          .zip(&data[i..i + max_len])
+          .take_while(|(a, b)| a == b)
+          .count();
+        if len > best_len {
+          (best_len, best_dist) = (len, i - j);
+          if len == max_len {
+            break;
+          }
+        }
+      }
+      // 链上的位置必须越来越早，否则这一项已经被窗口中更新的位置覆盖
+      let next = self.prev[j % WINDOW_SIZE];
+      if next == 0 || next > j {
+        break;
+      }
+      candidate = next;
+    }
+    if best_len >= MIN_MATCH {
+      (best_len, best_dist)
+    } else {
+      (0, 0)
+    }
+  }
+}
+
+fn lz77(data: &[u8]) -> Vec<Token> {
+  let mut matcher = Matcher::new(data);
+  let mut tokens = vec![];
+  let mut i = 0;
+  while i < data.len() {
+    let (mut len, mut dist) = matcher.find(i);
+    matcher.insert(i);
+    if len > 0 && len < GOOD_MATCH {
+      // 惰性匹配：下一个位置的匹配更长时，先输出当前字节
+      let (next_len, next_dist) = matcher.find(i + 1);
+      if next_len > len {
+        tokens.push(Token::Literal(data[i]));
+        i += 1;
+        matcher.insert(i);
+        (len, dist) = (next_len, next_dist);
+      }
+    }
+    if len == 0 {
+      tokens.push(Token::Literal(data[i]));
+      i += 1;
+      continue;
+    }
+    tokens.push(Token::Match {
+      len: len as u16,
+      dist: dist as u16,
+    });
+    for k in i + 1..i + len {
+      matcher.insert(k);
+    }
+    i += len;
+  }
+  tokens
+}
+
+fn length_code(len: u16) -> usize {
+  LENGTH_BASE.partition_point(|&base| base <= len) - 1
+}
+fn dist_code(dist: u16) -> usize {
+  DIST_BASE.partition_point(|&base| base <= dist) - 1
+}
+
+// 限制最大码长的哈夫曼码长。超过限制时把频率减半后重新构造
+pub(crate) fn code_lengths(freqs: &[u32], limit: u8) -> Vec<u8> {
+  let mut freqs = freqs.to_vec();
+  loop {
+    let lengths = huffman_lengths(&freqs);
+    if lengths.iter().all(|&l| l <= limit) {
+      return lengths;
+    }
+    for f in freqs.iter_mut().filter(|f| **f > 0) {
+      *f = f.div_ceil(2);
+    }
+  }
+}
+
+fn huffman_lengths(freqs: &[u32]) -> Vec<u8> {
+  use std::{cmp::Reverse, collections::BinaryHeap};
+  let mut lengths = vec![0u8; freqs.len()];
+  let used: Vec<usize> = (0..freqs.len()).filter(|&i| freqs[i] > 0).collect();
+  if used.len() <= 1 {
+    // 只有一个符号时也使用 1 位的码，使码是完整的
+    for &i in used.iter().chain(if used == [0] { &[1] } else { &[0] }) {
+      lengths[i] = 1;
+    }
+    return lengths;
+  }
+  // 节点：叶子为符号，内部节点记录两个子节点
+  let mut children: Vec<Option<(usize, usize)>> = vec![None; used.len()];
+  let mut heap: BinaryHeap<Reverse<(u64, usize)>> = used
+    .iter()
+    .enumerate()
+    .map(|(node, &sym)| Reverse((freqs[sym] as u64, node)))
+    .collect();
+  while heap.len() > 1 {
+    let Reverse((fa, a)) = heap.pop().unwrap();
+    let Reverse((fb, b)) = heap.pop().unwrap();
+    children.push(Some((a, b)));
+    heap.push(Reverse((fa + fb, children.len() - 1)));
+  }
+  let mut stack = vec![(children.len() - 1, 0u8)];
+  while let Some((node, depth)) = stack.pop() {
+    match children[node] {
+      Some((a, b)) => {
+        stack.push((a, depth + 1));
+        stack.push((b, depth + 1));
+      }
+      None => lengths[used[node]] = depth,
+    }
+  }
+  lengths
+}
+
+// 由码长得到规范哈夫曼码
+pub(crate) fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+  let mut count = [0u16; 16];
+  for &l in lengths {
+    count[l as usize] += 1;
+  }
+  count[0] = 0;
+  let mut next = [0u16; 16];
+  let mut code = 0;
+  for bits in 1..16 {
+    code = (code + count[bits - 1]) << 1;
+    next[bits] = code;
+  }
+  lengths
+    .iter()
+    .map(|&l| {
+      if l == 0 {
+        return 0;
+      }
+      let c = next[l as usize];
+      next[l as usize] += 1;
+      c
+    })
+    .collect()
+}
+
+pub(crate) fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+  let lit = (0..288)
+    .map(|i| match i {
+      0..=143 => 8,
+      144..=255 => 9,
+      256..=279 => 7,
+      _ => 8,
+    })
+    .collect();
+  (lit, vec![5; 30])
+}
+
+struct Codes {
+  lit_lengths: Vec<u8>,
+  lit_codes: Vec<u16>,
+  dist_lengths: Vec<u8>,
+  dist_codes: Vec<u16>,
+}
+impl Codes {
+  fn new(lit_lengths: Vec<u8>, dist_lengths: Vec<u8>) -> Codes {
+    Codes {
+      lit_codes: canonical_codes(&lit_lengths),
+      dist_codes: canonical_codes(&dist_lengths),
+      lit_lengths,
+      dist_lengths,
+    }
+  }
+  // 数据部分的位数
+  fn cost(&self, lit_freqs: &[u32], dist_freqs: &[u32]) -> usize {
+    let lit: usize = (0..lit_freqs.len())
+      .map(|i| {
+        let extra = if i > END_OF_BLOCK {
+          LENGTH_EXTRA[i - 257] as usize
+        } else {
+          0
+        };
+        lit_freqs[i] as usize * (self.lit_lengths[i] as usize + extra)
+      })
+      .sum();
+    let dist: usize = (0..dist_freqs.len())
+      .map(|i| dist_freqs[i] as usize * (self.dist_lengths[i] as usize + DIST_EXTRA[i] as usize))
+      .sum();
+    lit + dist
+  }
+  fn write_tokens(&self, out: &mut BitWriter, tokens: &[Token]) {
+    for token in tokens {
+      match *token {
+        Token::Literal(byte) => out.write_code(
+          self.lit_codes[byte as usize],
+          self.lit_lengths[byte as usize],
+        ),
+        Token::Match { len, dist } => {
+          let lc = length_code(len);
+          out.write_code(self.lit_codes[257 + lc], self.lit_lengths[257 + lc]);
+          out.write((len - LENGTH_BASE[lc]) as u32, LENGTH_EXTRA[lc] as u32);
+          let dc = dist_code(dist);
+          out.write_code(self.dist_codes[dc], self.dist_lengths[dc]);
+          out.write((dist - DIST_BASE[dc]) as u32, DIST_EXTRA[dc] as u32);
+        }
+      }
+    }
+    out.write_code(self.lit_codes[END_OF_BLOCK], self.lit_lengths[END_OF_BLOCK]);
+  }
+}
+
+fn write_fixed_block(out: &mut BitWriter, tokens: &[Token], last: bool) {
+  let (lit, dist) = fixed_lengths();
+  out.write(last as u32, 1);
+  out.write(1, 2);
+  Codes::new(lit, dist).write_tokens(out, tokens);
+}
+
+// 动态块头部中码长序列的游程编码：(码长的码, 额外位的值)
+fn run_length_encode(lengths: &[u8]) -> Vec<(u8, u8)> {
+  let mut symbols = vec![];
+  let mut i = 0;
+  while i < lengths.len() {
+    let l = lengths[i];
+    let run = lengths[i..].iter().take_while(|&&x| x == l).count();
+    if l == 0 && run >= 11 {
+      let n = run.min(138);
+      symbols.push((18, (n - 11) as u8));
+      i += n;
+    } else if l == 0 && run >= 3 {
+      symbols.push((17, (run - 3) as u8));
+      i += run;
+    } else if l != 0 && run >= 4 {
+      // 先写一个码长，再重复前一个码长 3~6 次
+      let n = (run - 1).min(6);
+      symbols.push((l, 0));
+      symbols.push((16, (n - 3) as u8));
+      i += 1 + n;
+    } else {
+      symbols.push((l, 0));
+      i += 1;
+    }
+  }
+  symbols
+}
+
+fn write_block(out: &mut BitWriter, tokens: &[Token], raw: &[u8], last: bool) {
+  let mut lit_freqs = [0u32; 286];
+  let mut dist_freqs = [0u32; 30];
+  for token in tokens {
+    match *token {
+      Token::Literal(byte) => lit_freqs[byte as usize] += 1,
+      Token::Match { len, dist } => {
+        lit_freqs[257 + length_code(len)] += 1;
+        dist_freqs[dist_code(dist)] += 1;
+      }
+    }
+  }
+  lit_freqs[END_OF_BLOCK] = 1;
+
+  let dynamic = Codes::new(
+    code_lengths(&lit_freqs, MAX_CODE_LENGTH),
+    code_lengths(&dist_freqs, MAX_CODE_LENGTH),
+  );
+  let hlit = 257.max(
+    dynamic
+      .lit_lengths
+      .iter()
+      .rposition(|&l| l > 0)
+      .unwrap_or(0)
+      + 1,
+  );
+  let hdist = 1.max(
+    dynamic
+      .dist_lengths
+      .iter()
+      .rposition(|&l| l > 0)
+      .unwrap_or(0)
+      + 1,
+  );
+  let rle =
+    run_length_encode(&[&dynamic.lit_lengths[..hlit], &dynamic.dist_lengths[..hdist]].concat());
+  let mut cl_freqs = [0u32; 19];
+  for &(sym, _) in &rle {
+    cl_freqs[sym as usize] += 1;
+  }
+  let cl_lengths = code_lengths(&cl_freqs, MAX_CODE_LENGTH_CODE);
+  let cl_codes = canonical_codes(&cl_lengths);
+  let hclen = 4.max(
+    CODE_LENGTH_ORDER
+      .iter()
+      .rposition(|&i| cl_lengths[i] > 0)
+      .unwrap_or(0)
+      + 1,
+  );
+  let extra_bits = |sym: u8| match sym {
+    16 => 2,
+    17 => 3,
+    18 => 7,
+    _ => 0,
+  };
+  let header_cost = 14
+    + 3 * hclen
+    + rle
+      .iter()
+      .map(|&(sym, _)| cl_lengths[sym as usize] as usize + extra_bits(sym))
+      .sum::<usize>();
+  let dynamic_cost = header_cost + dynamic.cost(&lit_freqs, &dist_freqs);
+  let (fixed_lit, fixed_dist) = fixed_lengths();
+  let fixed = Codes::new(fixed_lit, fixed_dist);
+  let fixed_cost = fixed.cost(&lit_freqs, &dist_freqs);
+  // 存储块：对齐后每 65535 字节需要 4 字节的长度和 3 位的块头
+  let stored_cost = raw.len() * 8 + raw.len().div_ceil(65535).max(1) * (32 + 3 + 7);
+
+  if stored_cost < fixed_cost.min(dynamic_cost) {
+    let chunks: Vec<&[u8]> = raw.chunks(65535).collect();
+    for (k, chunk) in chunks.iter().enumerate() {
+      out.write((last && k + 1 == chunks.len()) as u32, 1);
+      out.write(0, 2);
+      out.align();
+      let len = chunk.len() as u16;
+      out.write(len as u32, 16);
+      out.write(!len as u32, 16);
+      for &byte in chunk.iter() {
+        out.write(byte as u32, 8);
+      }
+    }
+  } else if fixed_cost <= dynamic_cost {
+    out.write(last as u32, 1);
+    out.write(1, 2);
+    fixed.write_tokens(out, tokens);
+  } else {
+    out.write(last as u32, 1);
+    out.write(2, 2);
+    out.write((hlit - 257) as u32, 5);
+    out.write((hdist - 1) as u32, 5);
+    out.write((hclen - 4) as u32, 4);
+    for &i in &CODE_LENGTH_ORDER[..hclen] {
+      out.write(cl_lengths[i] as u32, 3);
+    }
+    for &(sym, extra) in &rle {
+      out.write_code(cl_codes[sym as usize], cl_lengths[sym as usize]);
+      out.write(extra as u32, extra_bits(sym) as u32);
+    }
+    dynamic.write_tokens(out, tokens);
+  }
+}
+
+#[test]
+fn test_checksums() {
+  assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+  assert_eq!(crc32(b"123456789"), 0xcbf43926);
+  assert_eq!(crc32_update(crc32(b"1234"), b"56789"), 0xcbf43926);
+  let lengths = code_lengths(&[1, 1, 2, 4, 1000, 0, 3], 3);
+  assert!(lengths.iter().all(|&l| l <= 3));
+  assert_eq!(lengths[5], 0);
+  // Kraft 不等式取等号，码是完整的
+  let kraft: f64 = lengths
+    .iter()
+    .filter(|&&l| l > 0)
+    .map(|&l| 0.5f64.powi(l as i32))
+    .sum();
+  assert_eq!(kraft, 1.);
+}
+
+#[test]
+fn test_inflate() {
+  let mut data: Vec<u8> = (0..5000u32).map(|i| (i * i / 7 % 251) as u8).collect();
+  data.extend(std::iter::repeat_n(b'a', 1000));
+  data.extend(b"abcabcabcabd");
+  for input in [&data[..], &data[..1], &[]] {
+    assert_eq!(decompress(&compress(input)).unwrap(), input);
+  }
+  // 存储块：长度 3，NLEN 为其反码
+  assert_eq!(inflate(&[1, 3, 0, 0xfc, 0xff, 1, 2, 3]).unwrap(), [1, 2, 3]);
+  let mut bad = compress(&data);
+  let n = bad.len();
+  bad[n - 1] ^= 1;
+  assert_eq!(decompress(&bad), Err(InflateError("Adler32 mismatch")));
+  assert!(decompress(&compress(&data)[..n / 2]).is_err());
+  // 限制输出的大小：存储块、字面值和匹配超出限制时都返回错误
+  let len = data.len();
+  assert_eq!(decompress_limited(&compress(&data), len).unwrap(), data);
+  for input in [&data[..], &data[..10]] {
+    let max = input.len() - 1;
+    assert_eq!(decompress_limited(&compress(input), max), Err(TOO_LARGE));
+  }
+  let stored = [0x78, 0x01, 1, 3, 0, 0xfc, 0xff, 1, 2, 3, 0, 0x0d, 0, 0x07];
+  assert_eq!(decompress(&stored).unwrap(), [1, 2, 3]);
+  assert_eq!(decompress_limited(&stored, 2), Err(TOO_LARGE));
+}
@@ -0,0 +1,1056 @@
+// 以固定尺寸无窗口地渲染各个教程的场景，并与 tests/golden 下的参考图片(RGB 的 PNG)对比，
+// 避免重构 pipeline2、texture、transform 时无意中改变渲染结果。没有对应场景的示例见 README。
+//
+// 更新参考图片：YATSR_BLESS=1 cargo test --no-default-features --test golden
+// 对比失败时实际结果和差异图写入 target/tmp/golden
+use std::path::{Path, PathBuf};
+use yatsr::compare::{compare, diff_image, Tolerance};
+use yatsr::image_decoder::Decoder;
+use yatsr::pipeline2::{Rect, RenderOptions, Viewport};
+use yatsr::pixel::ImageF32;
+use yatsr::png::{ColorType, PNG};
+use yatsr::prelude::*;
+use yatsr::raytracer::{Camera, Light, Material, PathTracer, Scene, Whitted};
+
+const SIZE: u32 = 128;
+
+fn asset(path: &str) -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+// 参考图片不透明，保存为 RGB 的 PNG
+fn png() -> PNG {
+  PNG::new().color_type(ColorType::Rgb)
+}
+
+fn check(name: &str, img: &PixImage) {
+  check_with(name, img, Tolerance::default())
+}
+
+fn check_with(name: &str, img: &PixImage, tolerance: Tolerance) {
+  let reference = asset("tests/golden").join(format!("{}.png", name));
+  if std::env::var("YATSR_BLESS").is_ok() {
+    save_image(&reference, img, png()).unwrap();
+    return;
+  }
+  let data = std::fs::read(&reference).unwrap_or_else(|_| {
+    panic!(
+      "Missing reference image {:?}, run with YATSR_BLESS=1 to create it",
+      reference
+    )
+  });
+  let expected = PNG::new().decode(data).unwrap();
+  let diff = compare(img, &expected, &tolerance);
+  if !diff.within(&tolerance) {
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&out).unwrap();
+    let actual = out.join(format!("{}.actual.png", name));
+    let diff_file = out.join(format!("{}.diff.png", name));
+    save_image(&actual, img, png()).unwrap();
+    save_image(&diff_file, &diff_image(img, &expected), png()).unwrap();
+    panic!(
+      "{} differs from reference: {:?}, see {:?} and {:?}",
+      name, diff, actual, diff_file
+    );
+  }
+}
+
+fn spot() -> Object {
+  Object::from_file(asset("models/spot/spot_triangulated.obj")).unwrap()
+}
+
+struct Triangle([Vec3<f32>; 3]);
+impl Model for Triangle {
+  fn vert_count(&self) -> usize {
+    3
+  }
+  fn face_count(&self) -> usize {
+    1
+  }
+  fn vert(&self, _: usize, nth_vert: usize) -> Vec3<f32> {
+    self.0[nth_vert]
+  }
+  fn normal(&self, face: usize, _: usize) -> Vec3<f32> {
+    self.normal_of_face(face)
+  }
+  fn normal_of_face(&self, _: usize) -> Vec3<f32> {
+    Vec3::new(0., 0., 1.)
+  }
+  fn texture_coord(&self, _: usize, nth_vert: usize) -> Vec3<f32> {
+    self.0[nth_vert]
+  }
+}
+
+// 顶点颜色插值
+struct ColorShader {
+  colors: [Vec3<f32>; 3],
+}
+impl<M: Model> pipeline2::Shader<M> for ColorShader {
+  fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+    Vec4::from_point(&model.vert(face, nth_vert))
+  }
+  fn fragment(&self, info: pipeline2::FragmentInfo) -> Fragment {
+    Fragment::Color(info.barycentric_interpolate(&self.colors))
+  }
+}
+
+// 插值法向量的 lambert 光照
+struct LambertShader {
+  mat: Mat4,
+  normals: [Vec3<f32>; 3],
+}
+impl<M: Model> pipeline2::Shader<M> for LambertShader {
+  fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+    self.normals[nth_vert] = model.normal(face, nth_vert);
+    &self.mat * Vec4::from_point(&model.vert(face, nth_vert))
+  }
+  fn fragment(&self, info: pipeline2::FragmentInfo) -> Fragment {
+    let n = info.barycentric_interpolate(&self.normals).normalize();
+    let light = Vec3::new(1., 1., 1.).normalize();
+    Fragment::Color(Vec3::new(1., 1., 1.) * (n * light).max(0.1))
+  }
+}
+fn lambert(mat: Mat4) -> LambertShader {
+  LambertShader {
+    mat,
+    normals: [Vec3::default(); 3],
+  }
+}
+
+struct TextureShader<'a> {
+  texture: &'a Texture,
+  mat: Mat4,
+  uvs: [Vec3<f32>; 3],
+}
+impl<'a, M: Model> pipeline2::Shader<M> for TextureShader<'a> {
+  fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+    self.uvs[nth_vert] = model.texture_coord(face, nth_vert);
+    &self.mat * Vec4::from_point(&model.vert(face, nth_vert))
+  }
+  fn fragment(&self, info: pipeline2::FragmentInfo) -> Fragment {
+    let uv = info.barycentric_interpolate(&self.uvs);
+    Fragment::Color(self.texture.get(uv.x, uv.y))
+  }
+}
+
+fn perspective_camera() -> Mat4 {
+  Transform::new()
+    .rotate_y(0.6)
+    .camera(
+      Vec3::new(0., 1., 0.),
+      Vec3::new(0., 0.5, 2.5),
+      Vec3::new(0., 0., 0.),
+    )
+    .perspective(60., 1., -0.1, -100.)
+    .build()
+}
+
+fn render_triangle(super_sampling: u32) -> PixImage {
+  let mut img = PixImage::new(SIZE, SIZE);
+  draw_triangle(&mut img, 1., super_sampling);
+  img
+}
+
+// 顶点颜色为 intensity 倍的红、绿、蓝
+fn draw_triangle<I: Image>(img: &mut I, intensity: f32, super_sampling: u32) {
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    img,
+    &mut depth,
+    &mut ColorShader {
+      colors: [
+        Vec3::new(intensity, 0., 0.),
+        Vec3::new(0., intensity, 0.),
+        Vec3::new(0., 0., intensity),
+      ],
+    },
+    &Triangle([
+      Vec3::new(-0.6, -0.6, 0.),
+      Vec3::new(0.6, -0.2, 0.),
+      Vec3::new(-0.5, 0.6, 0.),
+    ]),
+    super_sampling,
+  );
+}
+
+// 1.basic/1.triangle
+#[test]
+fn basic_triangle() {
+  check("basic_triangle", &render_triangle(0));
+}
+
+// 1.basic/2.super-sampling
+#[test]
+fn basic_super_sampling() {
+  check("basic_super_sampling", &render_triangle(2));
+}
+
+// 渲染到浮点颜色缓冲时超出 [0,1] 的颜色不被截断
+#[test]
+fn float_target() {
+  for super_sampling in [0, 2] {
+    let mut hdr = ImageF32::new(SIZE, SIZE);
+    draw_triangle(&mut hdr, 4., super_sampling);
+    let ldr = render_triangle(super_sampling);
+    let mut max = 0f32;
+    for y in 0..SIZE {
+      for x in 0..SIZE {
+        let [r, g, b, _] = hdr.pixel(x, y);
+        max = max.max(r).max(g).max(b);
+        // 缩小到 [0,1] 后与 8 位的结果只差量化误差
+        let c = ldr.get_rgbaf(x, y);
+        for (v, expected) in [(r, c.x), (g, c.y), (b, c.z)] {
+          assert!(
+            (v / 4. - expected).abs() < 1.01 / 255.,
+            "{} {}",
+            v,
+            expected
+          );
+        }
+      }
+    }
+    assert!(max > 3.5, "{}", max);
+  }
+}
+
+// 1.basic/3.draw_model ~ 5.pipeline
+#[test]
+fn basic_pipeline() {
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  render(
+    &mut img,
+    &mut depth,
+    &mut shaders::FlatShader::with_mvp(
+      Mat4::identity(),
+      transform::camera(
+        Vec3::new(0., 1., 0.),
+        Vec3::new(1., 1., 2.),
+        Vec3::new(0., 0., 0.),
+      ),
+      transform::orthographic(-1.5, 1.5, -1.5, 1.5, -5., 5.),
+      transform::viewport(SIZE as f32, SIZE as f32),
+    ),
+    &spot(),
+    false,
+  );
+  check("basic_pipeline", &img);
+}
+
+// 1.basic/6.alpha
+#[test]
+fn basic_alpha() {
+  let mut img = PixImage::new(SIZE, SIZE);
+  let circles = [
+    (64., 76., Vec4::new(1., 0., 0., 0.5)),
+    (48., 48., Vec4::new(0., 1., 0., 0.5)),
+    (80., 48., Vec4::new(0., 0., 1., 0.5)),
+  ];
+  for y in 0..SIZE {
+    for x in 0..SIZE {
+      img.set_rgb(x, y, Vec3::new(1., 1., 1.));
+      for (cx, cy, color) in circles {
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        if dx * dx + dy * dy < 30. * 30. {
+          img.blending(x, y, color);
+        }
+      }
+    }
+  }
+  check("basic_alpha", &img);
+}
+
+// 1.basic/7.bitmap-font
+#[test]
+fn basic_bitmap_font() {
+  let mut img = PixImage::new(SIZE, SIZE);
+  get_cal_lite().draw_text(&mut img, 4, 90, 2, Vec3::new(1., 0., 0.), "yatsr\n123");
+  check("basic_bitmap_font", &img);
+}
+
+// 正方形的四个顶点经过 mat 变换后画出，顶点直接是屏幕坐标
+struct Flat2dShader {
+  mat: Mat4,
+}
+impl<M: Model> Shader<M> for Flat2dShader {
+  fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec3<f32> {
+    &self.mat * &model.vert(face, nth_vert)
+  }
+  fn fragment(&self, _: Vec3<f32>, _: Vec3<f32>) -> Fragment {
+    Fragment::Color(Vec3::new(0., 0., 1.))
+  }
+}
+
+// 2.transform/1.2d ~ 2.2d-matrix：从左到右为原样、错切、缩放、绕原点旋转和绕正方形中心旋转
+#[test]
+fn transform_2d() {
+  let panel = SIZE / 2;
+  let angle = std::f32::consts::PI / 4.;
+  #[rustfmt::skip]
+  let shear = Mat4([
+    1., 1., 0., 0.,
+    0., 1., 0., 0.,
+    0., 0., 1., 0.,
+    0., 0., 0., 1.,
+  ]);
+  let transforms = [
+    Mat4::identity(),
+    shear,
+    transform::scale(1.5, 1.5, 1.),
+    transform::rotate_z(angle),
+    Transform::new()
+      .translate(-12., -12., 0.)
+      .rotate_z(angle)
+      .translate(12., 12., 0.)
+      .build(),
+  ];
+  let panels: Vec<PixImage> = transforms
+    .iter()
+    .map(|mat| {
+      let mut img = PixImage::new(panel, panel);
+      let mut depth = vec![f32::MIN; (panel * panel) as usize];
+      // 边长 24 的正方形，左下角在原点
+      let square = Transform::new()
+        .translate(1., 1., 0.)
+        .scale(12., 12., 1.)
+        .then_mat(mat)
+        .build();
+      render(
+        &mut img,
+        &mut depth,
+        &mut Flat2dShader { mat: square },
+        &shape::Plane::new(),
+        false,
+      );
+      // 间隔 8 个像素的网格
+      for y in 0..panel {
+        for x in 0..panel {
+          if x % 8 == 0 || y % 8 == 0 {
+            img.set_rgb(x, y, Vec3::new(1., 1., 1.));
+          }
+        }
+      }
+      img
+    })
+    .collect();
+  let mut img = PixImage::new(panel * panels.len() as u32, panel);
+  for (i, p) in panels.iter().enumerate() {
+    for y in 0..panel {
+      for x in 0..panel {
+        img.set_rgba32(i as u32 * panel + x, y, p.get_rgba(x, y));
+      }
+    }
+  }
+  check("transform_2d", &img);
+}
+
+fn normalized(mut model: Object) -> Object {
+  model.normalize_verts();
+  model
+}
+
+// [-1,1] 映射到左下角在 (x, y)、边长为 size 的正方形
+fn viewport_at(x: u32, y: u32, size: u32) -> Mat4 {
+  transform::viewport_rect(x as f32, y as f32, size as f32, size as f32, -1., 1.)
+}
+
+// 2.transform/3.3d-viewport：两种写法的视口变换得到相同的结果
+#[test]
+fn transform_3d_viewport() {
+  let model = normalized(spot());
+  let mut img = PixImage::new(SIZE * 2, SIZE);
+  let mut depth = vec![f32::MIN; (img.width * img.height) as usize];
+  let size = SIZE as f32;
+  let viewports = [
+    Transform::new()
+      .translate(1., 1., 0.)
+      .scale(0.5, 0.5, 1.)
+      .scale(size, size, 1.)
+      .build(),
+    Transform::new()
+      .translate(1., 1., 0.)
+      .scale(size / 2., size / 2., 1.)
+      .translate(size, 0., 0.)
+      .build(),
+  ];
+  for vp in viewports {
+    let mut shader =
+      shaders::FlatShader::with_mvp(Mat4::identity(), Mat4::identity(), Mat4::identity(), vp);
+    render(&mut img, &mut depth, &mut shader, &model, false);
+  }
+  check("transform_3d_viewport", &img);
+}
+
+// 四个视口中分别是原样和绕 y、x、z 轴旋转 45 度的模型。
+// transform_normals 为 false 时旋转放在观察矩阵中，法向量不随模型旋转，光照是错的
+fn render_rotations(transform_normals: bool) -> PixImage {
+  let model = normalized(spot());
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  let half = SIZE / 2;
+  let angle = std::f32::consts::PI / 4.;
+  let panels = [
+    (0, 0, Mat4::identity()),
+    (half, 0, transform::rotate_y(angle)),
+    (half, half, transform::rotate_x(angle)),
+    (0, half, transform::rotate_z(angle)),
+  ];
+  for (x, y, rotation) in panels {
+    let (m, v) = if transform_normals {
+      (rotation, Mat4::identity())
+    } else {
+      (Mat4::identity(), rotation)
+    };
+    let mut shader = shaders::FlatShader::with_mvp(m, v, Mat4::identity(), viewport_at(x, y, half));
+    render(&mut img, &mut depth, &mut shader, &model, false);
+  }
+  img
+}
+
+// 2.transform/4.3d-rotation/main.rs
+#[test]
+fn transform_3d_rotation() {
+  check("transform_3d_rotation", &render_rotations(false));
+}
+
+// 2.transform/4.3d-rotation/normal.rs：法向量乘以模型矩阵的逆的转置
+#[test]
+fn transform_3d_normal_transform() {
+  check("transform_3d_normal_transform", &render_rotations(true));
+}
+
+// 2.transform/5.arbitrary-rotation：绕 (1,1,1) 旋转 0、30、60、90 度的立方体，每个面的颜色为它的法向量
+#[test]
+fn transform_arbitrary_rotation() {
+  struct NormalColorShader {
+    mat: Mat4,
+    color: Vec3<f32>,
+  }
+  impl<M: Model> Shader<M> for NormalColorShader {
+    fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec3<f32> {
+      self.color = model.normal(face, nth_vert);
+      &self.mat * &model.vert(face, nth_vert)
+    }
+    fn fragment(&self, _: Vec3<f32>, _: Vec3<f32>) -> Fragment {
+      Fragment::Color(self.color)
+    }
+  }
+  let model = normalized(cube());
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  let half = SIZE / 2;
+  for (i, (x, y)) in [(0, half), (half, half), (0, 0), (half, 0)]
+    .into_iter()
+    .enumerate()
+  {
+    let angle = (i as f32 * 30.).to_radians();
+    let mat = Transform::new()
+      .scale(0.5, 0.5, 0.5)
+      .rotate(Vec3::new(1., 1., 1.), angle)
+      .then_mat(&viewport_at(x, y, half))
+      .build();
+    let mut shader = NormalColorShader {
+      mat,
+      color: Vec3::default(),
+    };
+    render(&mut img, &mut depth, &mut shader, &model, false);
+  }
+  check("transform_arbitrary_rotation", &img);
+}
+
+// 2.transform/6.orthographic-projection：正交投影下的牛和立方体
+#[test]
+fn transform_orthographic_projection() {
+  let orth = transform::orthographic(-2., 2., -2., 2., -2., 2.);
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  let spot_mat = Transform::new()
+    .rotate_y(-std::f32::consts::PI / 4.)
+    .translate(-1., 0., 0.)
+    .then_mat(&orth)
+    .build();
+  let cube_mat = Transform::new()
+    .rotate_x(std::f32::consts::PI / 4.)
+    .rotate_y(std::f32::consts::PI / 4.)
+    .rotate_z(std::f32::consts::PI / 4.)
+    .scale(0.5, 0.5, 0.5)
+    .translate(1., 0., 0.)
+    .then_mat(&orth)
+    .build();
+  for (mat, model) in [(spot_mat, spot()), (cube_mat, cube())] {
+    let mut shader = shaders::FlatShader::with_transform(mat, SIZE as f32, SIZE as f32);
+    render(&mut img, &mut depth, &mut shader, &model, false);
+  }
+  check("transform_orthographic_projection", &img);
+}
+
+// 2.transform/7.viewing：从左到右为 c1、c2、c3 三个相机，c1 看到头在右、尾巴在左的侧面，c2 上下颠倒
+#[test]
+fn transform_viewing() {
+  let origin = Vec3::new(0., 0., 0.);
+  let cameras = [
+    transform::camera(Vec3::new(0., 1., 0.), origin, Vec3::new(-1., 0., 0.)),
+    transform::camera(Vec3::new(0., -1., 0.), origin, Vec3::new(-1., 0., 0.)),
+    transform::camera(Vec3::new(0., 1., 0.), origin, Vec3::new(-1., -1., -1.)),
+  ];
+  let model = spot();
+  let mut img = PixImage::new(SIZE * 3, SIZE);
+  let mut depth = vec![f32::MIN; (img.width * img.height) as usize];
+  for (i, camera) in cameras.into_iter().enumerate() {
+    let mut shader = shaders::FlatShader::with_mvp(
+      Mat4::identity(),
+      camera,
+      transform::orthographic(-2., 2., -2., 2., -2., 2.),
+      viewport_at(i as u32 * SIZE, 0, SIZE),
+    );
+    render(&mut img, &mut depth, &mut shader, &model, false);
+  }
+  check("transform_viewing", &img);
+}
+
+// 2.transform/8.perspective-projection
+#[test]
+fn transform_perspective() {
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut img,
+    &mut depth,
+    &mut lambert(perspective_camera()),
+    &spot(),
+    0,
+  );
+  check("transform_perspective", &img);
+}
+
+// 3.texture/1.diffuse
+#[test]
+fn texture_diffuse() {
+  let texture = Texture::new(util::load_image(asset("models/spot/spot_texture.tga")).unwrap());
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut img,
+    &mut depth,
+    &mut TextureShader {
+      texture: &texture,
+      mat: perspective_camera(),
+      uvs: [Vec3::default(); 3],
+    },
+    &spot(),
+    0,
+  );
+  check("texture_diffuse", &img);
+}
+
+fn render_magnified(texture: &Texture) -> PixImage {
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut img,
+    &mut depth,
+    &mut TextureShader {
+      texture,
+      mat: Mat4::identity(),
+      uvs: [Vec3::default(); 3],
+    },
+    &shape::Plane::new(),
+    0,
+  );
+  img
+}
+
+// 3.texture/2.bilinear
+#[test]
+fn texture_bilinear() {
+  let image = util::load_image(asset("textures/sui.tga")).unwrap();
+  let sub = util::sub_img(&image, 0.4, 0.4, 0.1, 0.1);
+  check("texture_nearest", &render_magnified(&Texture::neareat(sub)));
+  let sub = util::sub_img(&image, 0.4, 0.4, 0.1, 0.1);
+  check("texture_bilinear", &render_magnified(&Texture::new(sub)));
+}
+
+// 3.texture/2.bilinear/mosaic.rs：整张纹理铺满画面，直接取最近的纹素
+#[test]
+fn texture_mosaic() {
+  struct MosaicShader {
+    texture: PixImage,
+    mat: Mat4,
+    uvs: [Vec3<f32>; 3],
+  }
+  impl<M: Model> Shader<M> for MosaicShader {
+    fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec3<f32> {
+      self.uvs[nth_vert] = model.texture_coord(face, nth_vert);
+      &self.mat * &model.vert(face, nth_vert)
+    }
+    fn fragment(&self, _: Vec3<f32>, bar: Vec3<f32>) -> Fragment {
+      let uv = self.uvs[0] * bar.x + self.uvs[1] * bar.y + self.uvs[2] * bar.z;
+      Fragment::Color(self.texture.get_vec3f(uv.x, uv.y))
+    }
+  }
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  render(
+    &mut img,
+    &mut depth,
+    &mut MosaicShader {
+      texture: util::load_image(asset("textures/sui.tga")).unwrap(),
+      mat: transform::viewport(SIZE as f32, SIZE as f32),
+      uvs: [Vec3::default(); 3],
+    },
+    &shape::Plane::new(),
+    false,
+  );
+  check("texture_mosaic", &img);
+}
+
+// 3.texture/3.perspective-correct-interpolation
+#[test]
+fn texture_perspective_correct() {
+  let texture = Texture::neareat(util::load_image(asset("textures/grid2.tga")).unwrap());
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut img,
+    &mut depth,
+    &mut TextureShader {
+      texture: &texture,
+      mat: Transform::new()
+        .rotate_x(-90. * std::f32::consts::PI / 180.)
+        .camera(
+          Vec3::new(0., 1., 0.),
+          Vec3::new(0., 1.5, 1.5),
+          Vec3::new(0., 0., 0.),
+        )
+        .perspective(65., 1., -1., -4.)
+        .build(),
+      uvs: [Vec3::default(); 3],
+    },
+    &shape::Plane::new(),
+    0,
+  );
+  check("texture_perspective_correct", &img);
+}
+
+// 斜着看 grid1 平面，远处一个像素覆盖很多纹素，比较各种纹理过滤方法
+fn render_filtered<F: Fn(Vec3<f32>, Vec3<f32>, Vec3<f32>) -> Vec3<f32>>(filter: F) -> PixImage {
+  // 参数为纹理坐标和向上、向右一个像素时纹理坐标的变化
+  struct FilterShader<F> {
+    filter: F,
+    mat: Mat4,
+    uvs: [Vec3<f32>; 3],
+  }
+  impl<F: Fn(Vec3<f32>, Vec3<f32>, Vec3<f32>) -> Vec3<f32>, M: Model> pipeline2::Shader<M>
+    for FilterShader<F>
+  {
+    fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+      self.uvs[nth_vert] = model.texture_coord(face, nth_vert);
+      &self.mat * Vec4::from_point(&model.vert(face, nth_vert))
+    }
+    fn fragment(&self, info: pipeline2::FragmentInfo) -> Fragment {
+      let uv = info.barycentric_interpolate(&self.uvs);
+      let top = util::barycentric_interpolate(&self.uvs, info.top_barycentric()) - uv;
+      let right = util::barycentric_interpolate(&self.uvs, info.right_barycentry()) - uv;
+      Fragment::Color((self.filter)(uv, top, right))
+    }
+  }
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut img,
+    &mut depth,
+    &mut FilterShader {
+      filter,
+      mat: Transform::new()
+        .rotate_x(-90. * std::f32::consts::PI / 180.)
+        .camera(
+          Vec3::new(0., 1., 0.),
+          Vec3::new(0.9, 0.2, 1.1),
+          Vec3::new(0., 0., 0.),
+        )
+        .perspective(75., 1., -0.1, -10000.)
+        .build(),
+      uvs: [Vec3::default(); 3],
+    },
+    &shape::Plane::new(),
+    0,
+  );
+  img
+}
+
+// 把 fx * fy 个纹素平均为一个，用于生成 mipmap 和 ripmap
+fn shrink(img: &PixImage, fx: u32, fy: u32) -> PixImage {
+  let mut out = PixImage::new((img.width / fx).max(1), (img.height / fy).max(1));
+  for y in 0..out.height {
+    for x in 0..out.width {
+      let mut sum = Vec3::default();
+      for (dx, dy) in (0..fx).flat_map(|dx| (0..fy).map(move |dy| (dx, dy))) {
+        let (sx, sy) = (
+          (x * fx + dx).min(img.width - 1),
+          (y * fy + dy).min(img.height - 1),
+        );
+        sum = sum + img.get_rgbf(sx, sy);
+      }
+      out.set_rgb(x, y, sum * (1. / (fx * fy) as f32));
+    }
+  }
+  out
+}
+
+// 像素在纹理上覆盖的范围，以纹素为单位
+fn footprint(top: Vec3<f32>, right: Vec3<f32>, img: &PixImage) -> (f32, f32) {
+  (
+    top.x.abs().max(right.x.abs()) * img.width as f32,
+    top.y.abs().max(right.y.abs()) * img.height as f32,
+  )
+}
+
+fn grid() -> PixImage {
+  util::load_image(asset("textures/grid1.tga")).unwrap()
+}
+
+// 3.texture/4.mipmap/aliasing.rs：只用原始纹理，远处出现摩尔纹
+#[test]
+fn texture_aliasing() {
+  let texture = Texture::new(grid());
+  check(
+    "texture_aliasing",
+    &render_filtered(|uv, _, _| texture.get(uv.x, uv.y)),
+  );
+}
+
+// 每一级的宽高是上一级的一半，直到 1x1
+fn mipmap() -> Vec<Texture> {
+  let mut levels = vec![Texture::new(grid())];
+  while let Some(img) = levels
+    .last()
+    .map(|t| &t.image)
+    .filter(|i| i.width > 1 || i.height > 1)
+  {
+    levels.push(Texture::new(shrink(img, 2, 2)));
+  }
+  levels
+}
+
+// 在相邻两级之间三线性插值
+fn trilinear(levels: &[Texture], level: f32, uv: Vec3<f32>) -> Vec3<f32> {
+  let level = level.clamp(0., (levels.len() - 1) as f32);
+  let (a, b) = (level.floor() as usize, level.ceil() as usize);
+  util::linear_interpolation(
+    level - a as f32,
+    levels[a].get(uv.x, uv.y),
+    levels[b].get(uv.x, uv.y),
+  )
+}
+
+// 3.texture/4.mipmap
+#[test]
+fn texture_mipmap() {
+  let levels = mipmap();
+  let img = render_filtered(|uv, top, right| {
+    let (du, dv) = footprint(top, right, &levels[0].image);
+    trilinear(&levels, du.max(dv).log2(), uv)
+  });
+  check("texture_mipmap", &img);
+}
+
+// 3.texture/5.anisotropic/unconstrained.rs：按覆盖范围的短边选择 mipmap 的级别，
+// 沿长边的方向采样 长边/短边 次(最多 16 次)后取平均
+#[test]
+fn texture_unconstrained() {
+  let levels = mipmap();
+  let img = render_filtered(|uv, top, right| {
+    let image = &levels[0].image;
+    let texels = |d: Vec3<f32>| (d.x * image.width as f32).hypot(d.y * image.height as f32);
+    let (longer, shorter) = if texels(top) > texels(right) {
+      (top, texels(right))
+    } else {
+      (right, texels(top))
+    };
+    let count = (texels(longer) / shorter).clamp(1., 16.) as u32;
+    let level = shorter.log2();
+    let mut color = Vec3::default();
+    for i in 0..count {
+      let t = (i as f32 + 0.5) / count as f32 - 0.5;
+      color = color + trilinear(&levels, level, uv + longer * t);
+    }
+    color * (1. / count as f32)
+  });
+  check("texture_unconstrained", &img);
+}
+
+// 3.texture/5.anisotropic：ripmap 按 u、v 方向分别选择缩小的级别，SAT 求覆盖范围内纹素的平均值
+#[test]
+fn texture_anisotropic() {
+  // ripmap[j][i] 的宽缩小 2^i 倍，高缩小 2^j 倍
+  let mut ripmap = vec![vec![grid()]];
+  while ripmap[0].last().unwrap().width > 1 {
+    let img = shrink(ripmap[0].last().unwrap(), 2, 1);
+    ripmap[0].push(img);
+  }
+  while ripmap.last().unwrap()[0].height > 1 {
+    let row = ripmap
+      .last()
+      .unwrap()
+      .iter()
+      .map(|img| shrink(img, 1, 2))
+      .collect();
+    ripmap.push(row);
+  }
+  let ripmap: Vec<Vec<Texture>> = ripmap
+    .into_iter()
+    .map(|row| row.into_iter().map(Texture::new).collect())
+    .collect();
+  let img = render_filtered(|uv, top, right| {
+    let (du, dv) = footprint(top, right, &ripmap[0][0].image);
+    let level = |d: f32, n: usize| d.log2().clamp(0., (n - 1) as f32).round() as usize;
+    ripmap[level(dv, ripmap.len())][level(du, ripmap[0].len())].get(uv.x, uv.y)
+  });
+  check("texture_ripmap", &img);
+
+  // sat[y][x] 为 [0,x) * [0,y) 内纹素的和，用 f64 避免大纹理的和损失精度
+  let image = grid();
+  let (w, h) = (image.width as usize, image.height as usize);
+  let mut sat = vec![Vec3::<f64>::default(); (w + 1) * (h + 1)];
+  for y in 0..h {
+    for x in 0..w {
+      let c = image.get_rgbf(x as u32, y as u32);
+      let c = Vec3::new(c.x as f64, c.y as f64, c.z as f64);
+      sat[(y + 1) * (w + 1) + x + 1] =
+        c + sat[y * (w + 1) + x + 1] + sat[(y + 1) * (w + 1) + x] - sat[y * (w + 1) + x];
+    }
+  }
+  let img = render_filtered(|uv, top, right| {
+    // 覆盖范围的包围盒，至少一个纹素
+    let (du, dv) = footprint(top, right, &image);
+    let range = |center: f32, d: f32, n: usize| {
+      let lo = ((center * n as f32 - d / 2.).floor().max(0.) as usize).min(n - 1);
+      let hi = ((center * n as f32 + d / 2.).ceil() as usize).clamp(lo + 1, n);
+      (lo, hi)
+    };
+    let ((x0, x1), (y0, y1)) = (range(uv.x, du, w), range(uv.y, dv, h));
+    let at = |x: usize, y: usize| sat[y * (w + 1) + x];
+    let sum = at(x1, y1) - at(x0, y1) - at(x1, y0) + at(x0, y0);
+    let c = sum * (1. / ((x1 - x0) * (y1 - y0)) as f64);
+    Vec3::new(c.x as f32, c.y as f32, c.z as f32)
+  });
+  check("texture_sat", &img);
+}
+
+// 3.texture/6.cubemap
+#[test]
+fn texture_cubemap() {
+  struct CubemapShader<'a> {
+    cubemap: &'a Cubemap,
+    mat: Mat4,
+    invert: Mat4,
+  }
+  impl<'a, M: Model> pipeline2::Shader<M> for CubemapShader<'a> {
+    fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+      &self.mat * Vec4::from_point(&model.vert(face, nth_vert))
+    }
+    fn fragment(&self, info: pipeline2::FragmentInfo) -> Fragment {
+      let p = &self.invert * &info.coordinate();
+      Fragment::Color(self.cubemap.get(p))
+    }
+  }
+  let mut model = Object::from_file(asset("models/earth/earth.obj")).unwrap();
+  model.normalize_verts();
+  let mat = transform::rotate_y(0.5);
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut img,
+    &mut depth,
+    &mut CubemapShader {
+      cubemap: &Cubemap::colored(),
+      invert: mat.invert(),
+      mat,
+    },
+    &model,
+    0,
+  );
+  check("texture_cubemap", &img);
+}
+
+// 3.texture/7.bumpmap：切线空间的法线贴图
+#[test]
+fn texture_bumpmap() {
+  struct TangentShader {
+    normal_map: Texture,
+    tangent: Vec3<f32>,
+    bitangent: Vec3<f32>,
+    normals: [Vec3<f32>; 3],
+    uvs: [Vec3<f32>; 3],
+  }
+  impl<M: Model> pipeline2::Shader<M> for TangentShader {
+    fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+      // 由三角形的边和纹理坐标的差求切线 T 和副切线 B：e1 = du1 T + dv1 B，e2 = du2 T + dv2 B
+      if nth_vert == 0 {
+        let p = [0, 1, 2].map(|i| model.vert(face, i));
+        let uv = [0, 1, 2].map(|i| model.texture_coord(face, i));
+        let (e1, e2) = (p[1] - p[0], p[2] - p[0]);
+        let (d1, d2) = (uv[1] - uv[0], uv[2] - uv[0]);
+        let k = 1. / (d1.x * d2.y - d2.x * d1.y);
+        self.tangent = ((e1 * d2.y - e2 * d1.y) * k).normalize();
+        self.bitangent = ((e2 * d1.x - e1 * d2.x) * k).normalize();
+      }
+      self.normals[nth_vert] = model.normal(face, nth_vert);
+      self.uvs[nth_vert] = model.texture_coord(face, nth_vert);
+      Vec4::from_point(&model.vert(face, nth_vert))
+    }
+    fn fragment(&self, info: pipeline2::FragmentInfo) -> Fragment {
+      let normal = info.barycentric_interpolate(&self.normals).normalize();
+      let uv = info.barycentric_interpolate(&self.uvs);
+      let n = self.normal_map.get(uv.x, uv.y) * 2. - Vec3::new(1., 1., 1.);
+      let n = (self.tangent * n.x + self.bitangent * n.y + normal * n.z).normalize();
+      let light = Vec3::new(1., 1., 1.).normalize();
+      Fragment::Color(Vec3::new(1., 1., 1.) * (n * light).max(0.))
+    }
+  }
+  let normal_map = util::load_image(asset("models/diablo/diablo3_pose_nm_tangent.tga")).unwrap();
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut img,
+    &mut depth,
+    &mut TangentShader {
+      normal_map: Texture::neareat(normal_map),
+      tangent: Vec3::default(),
+      bitangent: Vec3::default(),
+      normals: [Vec3::default(); 3],
+      uvs: [Vec3::default(); 3],
+    },
+    &Object::from_file(asset("models/diablo/diablo3_pose.obj")).unwrap(),
+    0,
+  );
+  check("texture_bumpmap", &img);
+}
+
+// 分屏：左右两个视口，右边的视口再用裁剪矩形截掉上半部分
+#[test]
+fn viewport_and_scissor() {
+  let model = spot();
+  let mut img = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  let half = SIZE / 2;
+  pipeline2::render_with(
+    &mut img,
+    &mut depth,
+    &mut lambert(perspective_camera()),
+    &model,
+    &RenderOptions::new().viewport(Viewport::new(0, 0, half, SIZE)),
+  );
+  pipeline2::render_with(
+    &mut img,
+    &mut depth,
+    &mut lambert(perspective_camera()),
+    &model,
+    &RenderOptions::new()
+      .viewport(Viewport::new(half, 0, half, SIZE))
+      .scissor(Rect::new(half, 0, half, half)),
+  );
+  check("viewport_and_scissor", &img);
+}
+
+fn cube() -> Object {
+  Object::from_file(asset("models/cube/cube.obj")).unwrap()
+}
+
+fn cube_view() -> (Mat4, Mat4) {
+  (
+    transform::camera(
+      Vec3::new(0., 1., 0.),
+      Vec3::new(2., 2.5, 4.),
+      Vec3::new(0., 0., 0.),
+    ),
+    transform::perspective(45., 1., -0.1, -100.),
+  )
+}
+
+fn cube_scene<'a>(cube: &Object, floor: &shape::Plane) -> Scene<'a> {
+  let mut scene = Scene::new();
+  scene.add(
+    cube,
+    &transform::scale(0.6, 0.6, 0.6),
+    Material::diffuse(Vec3::new(0.9, 0.3, 0.2)).reflectivity(0.3),
+  );
+  scene.add(
+    floor,
+    &Transform::new()
+      .rotate_x(-std::f32::consts::PI / 2.)
+      .scale(3., 1., 3.)
+      .translate(0., -0.6, 0.)
+      .build(),
+    Material::diffuse(Vec3::new(0.8, 0.8, 0.8)),
+  );
+  scene.add_light(Light::Directional {
+    dir: Vec3::new(-1.5, 2., 0.5),
+    color: Vec3::new(1., 1., 1.),
+  });
+  scene.background = Vec3::new(0.2, 0.3, 0.5);
+  scene
+}
+
+#[test]
+fn raytrace_whitted() {
+  let (view, projection) = cube_view();
+  let scene = cube_scene(&cube(), &shape::Plane::new());
+  let img = Whitted::new().render(&scene, &Camera::new(&view, &projection), SIZE, SIZE);
+  check("raytrace_whitted", &img);
+}
+
+#[test]
+fn raytrace_path() {
+  let (view, projection) = cube_view();
+  let scene = cube_scene(&cube(), &shape::Plane::new());
+  let img = PathTracer::new(4).render(&scene, &Camera::new(&view, &projection), SIZE, SIZE);
+  check("raytrace_path", &img);
+}
+
+// 同一场景的光栅化和光线追踪结果只在三角形边缘上有差别
+#[test]
+fn raytrace_matches_rasterizer() {
+  struct FlatLambert {
+    mat: Mat4,
+    color: Vec3<f32>,
+    normal: Vec3<f32>,
+  }
+  impl<M: Model> pipeline2::Shader<M> for FlatLambert {
+    fn vertext(&mut self, model: &M, face: usize, nth_vert: usize) -> Vec4<f32> {
+      self.normal = model.normal(face, nth_vert);
+      &self.mat * Vec4::from_point(&model.vert(face, nth_vert))
+    }
+    fn fragment(&self, _: pipeline2::FragmentInfo) -> Fragment {
+      let light = Vec3::new(1., 2., 1.5).normalize();
+      Fragment::Color(self.color * (0.1 + (self.normal * light).max(0.)))
+    }
+  }
+  let (view, projection) = cube_view();
+  let model = cube();
+  let color = Vec3::new(0.9, 0.3, 0.2);
+
+  let mut rasterized = PixImage::new(SIZE, SIZE);
+  let mut depth = vec![f32::MIN; (SIZE * SIZE) as usize];
+  pipeline2::render(
+    &mut rasterized,
+    &mut depth,
+    &mut FlatLambert {
+      mat: &projection * &view,
+      color,
+      normal: Vec3::default(),
+    },
+    &model,
+    0,
+  );
+
+  let mut scene = Scene::new();
+  scene.add(&model, &Mat4::identity(), Material::diffuse(color));
+  scene.add_light(Light::Directional {
+    dir: Vec3::new(1., 2., 1.5),
+    color: Vec3::new(1., 1., 1.),
+  });
+  let traced = Whitted::new().render(&scene, &Camera::new(&view, &projection), SIZE, SIZE);
+  let diff = compare(&rasterized, &traced, &Tolerance::default());
+  assert!(diff.psnr > 30., "{:?}", diff);
+}